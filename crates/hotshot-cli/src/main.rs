@@ -1,8 +1,13 @@
 use anyhow::{Context, Result};
 use clap::{Args, Parser, Subcommand};
-use hotshot_core::capture::{self, CaptureMode};
-use hotshot_core::config::{Config, ImageFormat};
+use hotshot_core::capture::{self, CaptureMode, CaptureOptions, ScrollSpec, WindowSelector};
+use hotshot_core::config::{Config, ImageFormat, ImageScale};
+use hotshot_core::recording::{self, RecordControl, RecordOptions, RecordingFormat};
+use hotshot_core::regions::{self, RegionStore, SavedRegion};
 use hotshot_core::storage::Storage;
+use hotshot_core::watch::{self, ChangeFilter};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(name = "hotshot", about = "Screenshot tool with organization", version)]
@@ -17,10 +22,56 @@ enum Commands {
     #[command(subcommand)]
     Capture(CaptureCommand),
 
+    /// Record the screen as an animated GIF, APNG or WebP, or as MP4/WebM video
+    #[command(subcommand)]
+    Record(RecordCommand),
+
+    /// Capture a region or monitor repeatedly, keeping only frames that changed
+    Watch(WatchArgs),
+
+    /// Pick a colour from the screen and print it as hex, rgb() and hsl()
+    PickColor {
+        /// Average an N×N square around the clicked pixel
+        #[arg(short, long, default_value_t = 1,
+              value_parser = clap::value_parser!(u32).range(1..=64))]
+        average: u32,
+
+        /// Copy the colour to the clipboard, as hex (default), rgb or hsl
+        #[arg(short, long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "hex")]
+        clipboard: Option<ColorFormat>,
+
+        /// Print JSON with the colour, coordinates and monitor
+        #[arg(long)]
+        json: bool,
+
+        /// Limit the picker to one display: name, index, model, "cursor" or "focused"
+        #[arg(short, long)]
+        display: Option<String>,
+    },
+
+    /// Measure distances on screen; prints the measurements when done
+    Measure {
+        /// Print the measurements as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Limit the ruler to one display: name, index, model, "cursor" or "focused"
+        #[arg(short, long)]
+        display: Option<String>,
+    },
+
     /// List or query connected displays/monitors
     #[command(subcommand)]
     Display(DisplayCommand),
 
+    /// List top-level windows
+    #[command(subcommand)]
+    Window(WindowCommand),
+
+    /// Manage named regions
+    #[command(subcommand)]
+    Region(RegionCommand),
+
     /// List recent screenshots
     List {
         /// Maximum number of screenshots to show
@@ -71,8 +122,35 @@ enum Commands {
 
 #[derive(Subcommand)]
 enum DisplayCommand {
-    /// List connected displays with their geometry
+    /// List connected displays with geometry, refresh, DPI and model
+    List,
+}
+
+#[derive(Subcommand)]
+enum WindowCommand {
+    /// List windows with id, class, title, pid, desktop and geometry
+    List,
+}
+
+#[derive(Subcommand)]
+enum RegionCommand {
+    /// Save a region under a name for `capture region --named`
+    Save {
+        /// Region name (letters, digits, '-' and '_')
+        name: String,
+        /// X,Y,W,H or WxH+X+Y (relative to --display when given)
+        geometry: String,
+        /// Keep the region relative to this display so it follows the monitor
+        #[arg(short, long)]
+        display: Option<String>,
+    },
+    /// List named regions and recent selections
     List,
+    /// Delete a named region
+    Delete {
+        /// Region name
+        name: String,
+    },
 }
 
 #[derive(Args, Clone)]
@@ -81,6 +159,11 @@ struct CaptureOpts {
     #[arg(short, long)]
     format: Option<ImageFormat>,
 
+    /// Size on HiDPI monitors: physical, logical, or a factor like 0.5
+    /// (overrides config)
+    #[arg(long)]
+    scale: Option<ImageScale>,
+
     /// Copy to clipboard
     #[arg(short, long)]
     clipboard: bool,
@@ -89,9 +172,22 @@ struct CaptureOpts {
     #[arg(short, long)]
     output: Option<String>,
 
-    /// Target a specific display (name like "HDMI-1" or index like "0")
+    /// Target a display: name ("HDMI-1"), index ("0"), model ("U2720Q"),
+    /// "cursor", "focused", or "all" for one screenshot per monitor
     #[arg(short, long)]
     display: Option<String>,
+
+    /// Draw the mouse cursor into the screenshot
+    #[arg(long)]
+    cursor: bool,
+
+    /// Wait this many seconds before capturing (e.g. 3, 1.5, 500ms)
+    #[arg(long, value_parser = capture::parse_duration)]
+    delay: Option<Duration>,
+
+    /// Don't show the on-screen countdown during --delay
+    #[arg(long)]
+    no_countdown: bool,
 }
 
 #[derive(Subcommand)]
@@ -103,14 +199,58 @@ enum CaptureCommand {
     },
     /// Capture a region (interactive selection, or pass --geometry)
     Region {
-        /// Explicit region: X,Y,W,H or WxH+X+Y (omit for interactive)
+        /// Explicit region: X,Y,W,H, WxH+X+Y or a saved name (omit for interactive)
         #[arg(short, long)]
         geometry: Option<String>,
+        /// Capture the last interactively selected region again
+        #[arg(long, conflicts_with_all = ["geometry", "recent", "named"])]
+        last: bool,
+        /// Capture the Nth most recent selected region again (1 = last)
+        #[arg(long, value_name = "N", conflicts_with_all = ["geometry", "named"])]
+        recent: Option<usize>,
+        /// Capture a region saved with `hotshot region save`
+        #[arg(long, conflicts_with = "geometry")]
+        named: Option<String>,
         #[command(flatten)]
         opts: CaptureOpts,
     },
-    /// Capture the active window
+    /// Capture the active window (or click to pick one with --pick)
     Window {
+        /// Highlight the window under the pointer and capture it on click
+        #[arg(long, conflicts_with_all = ["window_id", "class", "title"])]
+        pick: bool,
+        /// Capture the window with this id (hex like 0x3a00007, or decimal)
+        #[arg(long, value_parser = capture::parse_window_id)]
+        window_id: Option<u32>,
+        /// Capture the topmost window whose WM_CLASS matches this regex
+        #[arg(long)]
+        class: Option<String>,
+        /// Capture the topmost window whose title matches this regex
+        #[arg(long)]
+        title: Option<String>,
+        /// Include window manager decorations (title bar, borders)
+        #[arg(long)]
+        decorations: bool,
+        /// Trim client-side decoration shadows (GTK)
+        #[arg(long)]
+        no_shadow: bool,
+        #[command(flatten)]
+        opts: CaptureOpts,
+    },
+    /// Scroll through a region and stitch it into one tall screenshot
+    Scroll {
+        /// Region to scroll: X,Y,W,H, WxH+X+Y or a saved name (omit for interactive)
+        #[arg(short, long)]
+        geometry: Option<String>,
+        /// Stop once the stitched image is this many pixels tall
+        #[arg(long, default_value_t = ScrollSpec::default().max_height)]
+        max_height: u32,
+        /// Mouse wheel clicks between frames
+        #[arg(long, default_value_t = ScrollSpec::default().clicks)]
+        clicks: u32,
+        /// Time for the content to settle after each scroll (e.g. 300ms)
+        #[arg(long, default_value = "300ms", value_parser = capture::parse_duration)]
+        settle: Duration,
         #[command(flatten)]
         opts: CaptureOpts,
     },
@@ -121,22 +261,277 @@ impl CaptureCommand {
         match self {
             CaptureCommand::Fullscreen { opts } => opts,
             CaptureCommand::Region { opts, .. } => opts,
-            CaptureCommand::Window { opts } => opts,
+            CaptureCommand::Window { opts, .. } => opts,
+            CaptureCommand::Scroll { opts, .. } => opts,
+        }
+    }
+
+    fn capture_options(&self, config: &Config) -> CaptureOptions {
+        let (decorations, strip_shadow) = match self {
+            CaptureCommand::Window {
+                decorations,
+                no_shadow,
+                ..
+            } => (*decorations, *no_shadow),
+            _ => (false, false),
+        };
+        // Earlier selections are only needed to snap the interactive selector.
+        let snap_regions = match self {
+            CaptureCommand::Region {
+                geometry: None,
+                last: false,
+                recent: None,
+                named: None,
+                ..
+            }
+            | CaptureCommand::Scroll { geometry: None, .. } => RegionStore::load()
+                .map(|store| store.snap_regions())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let opts = self.opts();
+        CaptureOptions {
+            include_cursor: opts.cursor || config.capture.include_cursor,
+            decorations,
+            strip_shadow,
+            delay: opts.delay,
+            countdown: config.capture.countdown && !opts.no_countdown,
+            selector: config.selector.clone(),
+            snap_regions,
         }
     }
 
     fn to_capture_mode(&self) -> Result<CaptureMode> {
         Ok(match self {
             CaptureCommand::Fullscreen { .. } => CaptureMode::Fullscreen,
-            CaptureCommand::Region { geometry, .. } => match geometry {
-                Some(g) => {
-                    let region =
-                        capture::parse_region(g).map_err(|e| anyhow::anyhow!(e))?;
-                    CaptureMode::Region(region)
+            CaptureCommand::Region {
+                geometry: Some(g), ..
+            } => {
+                let region = capture::parse_region(g).map_err(|e| anyhow::anyhow!(e))?;
+                CaptureMode::Region(region)
+            }
+            CaptureCommand::Region {
+                last,
+                recent,
+                named,
+                ..
+            } if *last || recent.is_some() || named.is_some() => {
+                let store = RegionStore::load().context("failed to load regions")?;
+                let saved = match named {
+                    Some(name) => store.named(name),
+                    None => store.recent(recent.unwrap_or(1)),
                 }
-                None => CaptureMode::RegionInteractive,
-            },
+                .map_err(|e| anyhow::anyhow!(e))?;
+                CaptureMode::Region(saved.resolve().map_err(|e| anyhow::anyhow!(e))?)
+            }
+            CaptureCommand::Region { .. } => CaptureMode::RegionInteractive,
+            CaptureCommand::Window { pick: true, .. } => CaptureMode::WindowInteractive,
+            CaptureCommand::Window {
+                window_id,
+                class,
+                title,
+                ..
+            } if window_id.is_some() || class.is_some() || title.is_some() => {
+                CaptureMode::Window(WindowSelector {
+                    id: *window_id,
+                    class: class.clone(),
+                    title: title.clone(),
+                })
+            }
             CaptureCommand::Window { .. } => CaptureMode::ActiveWindow,
+            CaptureCommand::Scroll {
+                geometry,
+                max_height,
+                clicks,
+                settle,
+                ..
+            } => CaptureMode::Scroll(ScrollSpec {
+                region: geometry
+                    .as_deref()
+                    .map(capture::parse_region)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!(e))?,
+                max_height: *max_height,
+                clicks: *clicks,
+                settle: *settle,
+            }),
+        })
+    }
+}
+
+/// How `pick-color --clipboard` writes the colour.
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ColorFormat {
+    Hex,
+    Rgb,
+    Hsl,
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// Region to watch: X,Y,W,H, WxH+X+Y or a saved name (default: whole screen)
+    #[arg(short, long, conflicts_with = "display")]
+    geometry: Option<String>,
+
+    /// Monitor to watch: name, index, model, "cursor" or "focused"
+    #[arg(short, long)]
+    display: Option<String>,
+
+    /// Time between captures (e.g. 30s, 5m)
+    #[arg(short, long, default_value = "5s", value_parser = capture::parse_duration)]
+    interval: Duration,
+
+    /// Keep a capture only once this much of it changed since the last
+    /// kept one (e.g. 2%); unchanged frames are always dropped
+    #[arg(short, long, default_value = "0%", value_parser = watch::parse_threshold)]
+    threshold: f64,
+
+    /// Session name, added as a tag to every kept capture
+    #[arg(short, long)]
+    name: Option<String>,
+
+    /// Stop after keeping this many captures
+    #[arg(long)]
+    count: Option<usize>,
+
+    /// Stop after this long (e.g. 90m); runs until Ctrl-C otherwise
+    #[arg(long, value_parser = capture::parse_duration)]
+    duration: Option<Duration>,
+
+    /// Image format (png, jpeg, webp — overrides config)
+    #[arg(short, long)]
+    format: Option<ImageFormat>,
+
+    /// Draw the mouse cursor into the screenshots
+    #[arg(long)]
+    cursor: bool,
+}
+
+#[derive(Args, Clone)]
+struct RecordOpts {
+    /// Output format: gif, apng, webp, or mp4/webm (needs ffmpeg)
+    #[arg(short, long, default_value_t = RecordingFormat::Gif)]
+    format: RecordingFormat,
+
+    /// How long to record (e.g. 10s, 1.5, 2m). Defaults to 5s for
+    /// animations; video runs until Ctrl-C or --max-duration
+    #[arg(long, value_parser = capture::parse_duration)]
+    duration: Option<Duration>,
+
+    /// Never record longer than this, pauses not included
+    #[arg(long, default_value = "30m", value_parser = capture::parse_duration)]
+    max_duration: Duration,
+
+    /// Frames per second
+    #[arg(long, default_value_t = RecordOptions::default().fps,
+          value_parser = clap::value_parser!(u32).range(1..=60))]
+    fps: u32,
+
+    /// Save to specific path instead of default storage
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Target a display: name ("HDMI-1"), index ("0"), model ("U2720Q"),
+    /// "cursor" or "focused"
+    #[arg(short, long)]
+    display: Option<String>,
+
+    /// Draw the mouse cursor into every frame
+    #[arg(long)]
+    cursor: bool,
+
+    /// Draw a ring around the pointer while a mouse button is down
+    #[arg(long)]
+    highlight_clicks: bool,
+
+    /// Wait this many seconds before recording (e.g. 3, 1.5, 500ms)
+    #[arg(long, value_parser = capture::parse_duration)]
+    delay: Option<Duration>,
+
+    /// Don't show the on-screen countdown during --delay
+    #[arg(long)]
+    no_countdown: bool,
+}
+
+#[derive(Subcommand)]
+enum RecordCommand {
+    /// Record the full screen
+    Fullscreen {
+        #[command(flatten)]
+        opts: RecordOpts,
+    },
+    /// Record a region (interactive selection, or pass --geometry)
+    Region {
+        /// Explicit region: X,Y,W,H, WxH+X+Y or a saved name (omit for interactive)
+        #[arg(short, long)]
+        geometry: Option<String>,
+        #[command(flatten)]
+        opts: RecordOpts,
+    },
+    /// Record the active window (or click to pick one with --pick)
+    Window {
+        /// Highlight the window under the pointer and record it on click
+        #[arg(long, conflicts_with_all = ["class", "title"])]
+        pick: bool,
+        /// Record the topmost window whose WM_CLASS matches this regex
+        #[arg(long)]
+        class: Option<String>,
+        /// Record the topmost window whose title matches this regex
+        #[arg(long)]
+        title: Option<String>,
+        /// Include window manager decorations (title bar, borders)
+        #[arg(long)]
+        decorations: bool,
+        #[command(flatten)]
+        opts: RecordOpts,
+    },
+}
+
+impl RecordCommand {
+    fn opts(&self) -> &RecordOpts {
+        match self {
+            RecordCommand::Fullscreen { opts } => opts,
+            RecordCommand::Region { opts, .. } => opts,
+            RecordCommand::Window { opts, .. } => opts,
+        }
+    }
+
+    fn capture_options(&self, config: &Config) -> CaptureOptions {
+        let decorations = matches!(self, RecordCommand::Window { decorations: true, .. });
+        let snap_regions = match self {
+            RecordCommand::Region { geometry: None, .. } => RegionStore::load()
+                .map(|store| store.snap_regions())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let opts = self.opts();
+        CaptureOptions {
+            include_cursor: opts.cursor || config.capture.include_cursor,
+            decorations,
+            strip_shadow: false,
+            delay: opts.delay,
+            countdown: config.capture.countdown && !opts.no_countdown,
+            selector: config.selector.clone(),
+            snap_regions,
+        }
+    }
+
+    fn to_capture_mode(&self) -> Result<CaptureMode> {
+        Ok(match self {
+            RecordCommand::Fullscreen { .. } => CaptureMode::Fullscreen,
+            RecordCommand::Region {
+                geometry: Some(g), ..
+            } => CaptureMode::Region(capture::parse_region(g).map_err(|e| anyhow::anyhow!(e))?),
+            RecordCommand::Region { .. } => CaptureMode::RegionInteractive,
+            RecordCommand::Window { pick: true, .. } => CaptureMode::WindowInteractive,
+            RecordCommand::Window { class, title, .. } if class.is_some() || title.is_some() => {
+                CaptureMode::Window(WindowSelector {
+                    id: None,
+                    class: class.clone(),
+                    title: title.clone(),
+                })
+            }
+            RecordCommand::Window { .. } => CaptureMode::ActiveWindow,
         })
     }
 }
@@ -164,7 +559,18 @@ fn main() -> Result<()> {
 
     match cli.command {
         Commands::Capture(cmd) => cmd_capture(config, cmd),
+        Commands::Record(cmd) => cmd_record(config, cmd),
+        Commands::Watch(args) => cmd_watch(config, args),
+        Commands::PickColor {
+            average,
+            clipboard,
+            json,
+            display,
+        } => cmd_pick_color(config, average, clipboard, json, display),
+        Commands::Measure { json, display } => cmd_measure(config, json, display),
         Commands::Display(cmd) => cmd_display(cmd),
+        Commands::Window(cmd) => cmd_window(cmd),
+        Commands::Region(cmd) => cmd_region(cmd),
         Commands::List { limit, tag } => cmd_list(config, limit, tag),
         Commands::Open { id } => cmd_open(config, id),
         Commands::Tag { id, tags } => cmd_tag(config, id, tags),
@@ -184,8 +590,23 @@ fn cmd_capture(config: Config, cmd: CaptureCommand) -> Result<()> {
     let capture_mode = cmd.to_capture_mode()?;
     let opts = cmd.opts().clone();
 
+    // --display all captures the whole screen once and splits it per monitor
+    let all_displays = opts.display.as_deref() == Some(capture::ALL_DISPLAYS);
+    if all_displays {
+        if !matches!(
+            capture_mode,
+            CaptureMode::Fullscreen | CaptureMode::RegionInteractive
+        ) {
+            anyhow::bail!("--display all only works for fullscreen and interactive region capture");
+        }
+        if opts.output.is_some() {
+            anyhow::bail!("--output can't be combined with --display all");
+        }
+    }
+
     // Resolve --display to monitor bounds
     let display_bounds = match &opts.display {
+        Some(_) if all_displays => None,
         Some(spec) => {
             let monitor = capture::resolve_display(spec)
                 .context("failed to resolve display")?;
@@ -198,11 +619,43 @@ fn cmd_capture(config: Config, cmd: CaptureCommand) -> Result<()> {
     let display_server = capture::detect_display_server()?;
     eprintln!("capturing ({display_server})...");
 
-    let image = capture::capture(&capture_mode, display_bounds)?;
-    eprintln!("captured {}x{}", image.width(), image.height());
+    let capture_opts = cmd.capture_options(&config);
+
+    let mut shot = capture::capture(&capture_mode, display_bounds, &capture_opts)?;
+    eprintln!("captured {}x{}", shot.image.width(), shot.image.height());
+
+    // Parts are scaled separately: each monitor can have its own DPI.
+    let scale = opts.scale.unwrap_or(config.image.scale);
+    if !all_displays {
+        shot.apply_scale(scale)?;
+        if shot.physical_size.is_some() {
+            eprintln!("scaled to {}x{}", shot.image.width(), shot.image.height());
+        }
+    }
+    let image = &shot.image;
 
     // Save to custom output or default storage
-    if let Some(output_path) = &opts.output {
+    if all_displays {
+        let monitors = capture::list_monitors().context("failed to list monitors")?;
+        let mut parts = capture::split_by_monitor(&shot, &monitors)?;
+        for (_, part) in &mut parts {
+            part.apply_scale(scale)?;
+        }
+        let storage = Storage::new(config.clone());
+        let entries = storage
+            .save_group(&parts, &capture_mode, display_server, opts.format.as_ref())
+            .context("failed to save screenshots")?;
+        for entry in &entries {
+            eprintln!(
+                "saved: {} ({})",
+                entry.path.display(),
+                entry.monitor.as_deref().unwrap_or("?")
+            );
+        }
+        if let Some(group) = entries.first().and_then(|e| e.group.as_ref()) {
+            eprintln!("group: {group}");
+        }
+    } else if let Some(output_path) = &opts.output {
         let path = std::path::Path::new(output_path);
         let dynamic = hotshot_core::image::DynamicImage::ImageRgba8(image.clone());
         dynamic.save(path).context("failed to save image")?;
@@ -211,7 +664,7 @@ fn cmd_capture(config: Config, cmd: CaptureCommand) -> Result<()> {
         let storage = Storage::new(config.clone());
         let entry = storage
             .save(
-                &image,
+                &shot,
                 &capture_mode,
                 display_server,
                 opts.format.as_ref(),
@@ -221,15 +674,355 @@ fn cmd_capture(config: Config, cmd: CaptureCommand) -> Result<()> {
         eprintln!("id:    {}", entry.id);
     }
 
+    // Remember interactive selections for --last / --recent
+    if let CaptureMode::RegionInteractive = capture_mode
+        && let Some(region) = shot.region
+        && let Err(e) = regions::remember(region)
+    {
+        eprintln!("failed to remember region: {e}");
+    }
+
     // Copy to clipboard if requested
     if opts.clipboard || config.behavior.copy_to_clipboard {
-        hotshot_core::clipboard::copy_image(&image).context("failed to copy to clipboard")?;
+        hotshot_core::clipboard::copy_image(image).context("failed to copy to clipboard")?;
         eprintln!("copied to clipboard");
     }
 
     Ok(())
 }
 
+fn cmd_record(config: Config, cmd: RecordCommand) -> Result<()> {
+    let capture_mode = cmd.to_capture_mode()?;
+    let opts = cmd.opts().clone();
+
+    let display_bounds = match &opts.display {
+        Some(spec) => {
+            let monitor = capture::resolve_display(spec)
+                .context("failed to resolve display")?;
+            eprintln!("display: {monitor}");
+            Some(monitor.to_region())
+        }
+        None => None,
+    };
+
+    let display_server = capture::detect_display_server()?;
+    let capture_opts = cmd.capture_options(&config);
+    let area = recording::pick_area(&capture_mode, display_bounds, &capture_opts)?;
+    let duration = match opts.duration {
+        Some(duration) => duration,
+        None if opts.format.is_video() => opts.max_duration,
+        None => RecordOptions::default().duration,
+    }
+    .min(opts.max_duration);
+    let rec = RecordOptions {
+        duration,
+        fps: opts.fps,
+        format: opts.format,
+        highlight_clicks: opts.highlight_clicks,
+    };
+    let control = Arc::new(RecordControl::default());
+    listen_for_controls(&control)?;
+    eprintln!(
+        "recording {}x{} for up to {:.1}s at {} fps ({display_server})...",
+        area.width,
+        area.height,
+        rec.duration.as_secs_f64(),
+        rec.fps
+    );
+    eprintln!("Ctrl-C stops, Enter (or SIGUSR1) pauses and resumes");
+
+    if let Some(output_path) = &opts.output {
+        let recording = recording::record(
+            std::path::Path::new(output_path),
+            area,
+            &capture_opts,
+            &rec,
+            &control,
+        )
+        .context("failed to record")?;
+        eprintln!(
+            "recorded {} frames, {:.1}s",
+            recording.frames,
+            recording.duration.as_secs_f64()
+        );
+        eprintln!("saved: {output_path}");
+    } else {
+        let storage = Storage::new(config.clone());
+        let entry = storage
+            .save_recording(rec.format, display_server, |path| {
+                recording::record(path, area, &capture_opts, &rec, &control)
+            })
+            .context("failed to save recording")?;
+        eprintln!(
+            "recorded {} frames, {:.1}s",
+            entry.frame_count.unwrap_or_default(),
+            entry.duration_ms.unwrap_or_default() as f64 / 1000.0
+        );
+        eprintln!("saved: {}", entry.path.display());
+        eprintln!("id:    {}", entry.id);
+    }
+
+    if let CaptureMode::RegionInteractive = capture_mode
+        && let Err(e) = regions::remember(area)
+    {
+        eprintln!("failed to remember region: {e}");
+    }
+
+    Ok(())
+}
+
+fn cmd_pick_color(
+    config: Config,
+    average: u32,
+    clipboard: Option<ColorFormat>,
+    json: bool,
+    display: Option<String>,
+) -> Result<()> {
+    let display_bounds = match &display {
+        Some(spec) => Some(
+            capture::resolve_display(spec)
+                .context("failed to resolve display")?
+                .to_region(),
+        ),
+        None => None,
+    };
+    let opts = CaptureOptions {
+        selector: config.selector.clone(),
+        ..Default::default()
+    };
+    let color = capture::pick_color(display_bounds, average, &opts)?;
+
+    if json {
+        let (h, s, l) = color.hsl();
+        let out = serde_json::json!({
+            "hex": color.hex(),
+            "rgb": color.css_rgb(),
+            "hsl": color.css_hsl(),
+            "r": color.r,
+            "g": color.g,
+            "b": color.b,
+            "h": h,
+            "s": s,
+            "l": l,
+            "x": color.x,
+            "y": color.y,
+            "sample": color.sample,
+            "monitor": color.monitor,
+        });
+        println!("{}", serde_json::to_string_pretty(&out)?);
+    } else {
+        println!("{}", color.hex());
+        println!("{}", color.css_rgb());
+        println!("{}", color.css_hsl());
+        if let (Some(x), Some(y)) = (color.x, color.y) {
+            let on = color.monitor.as_deref().map(|m| format!(" on {m}")).unwrap_or_default();
+            let avg = match color.sample {
+                1 => String::new(),
+                n => format!(", averaged over {n}x{n}"),
+            };
+            eprintln!("at {x},{y}{on}{avg}");
+        }
+    }
+
+    if let Some(format) = clipboard {
+        let text = match format {
+            ColorFormat::Hex => color.hex(),
+            ColorFormat::Rgb => color.css_rgb(),
+            ColorFormat::Hsl => color.css_hsl(),
+        };
+        hotshot_core::clipboard::copy_text(&text).context("failed to copy to clipboard")?;
+        eprintln!("copied {text} to clipboard");
+    }
+    Ok(())
+}
+
+fn cmd_measure(config: Config, json: bool, display: Option<String>) -> Result<()> {
+    let display_bounds = match &display {
+        Some(spec) => Some(
+            capture::resolve_display(spec)
+                .context("failed to resolve display")?
+                .to_region(),
+        ),
+        None => None,
+    };
+    let opts = CaptureOptions {
+        selector: config.selector.clone(),
+        ..Default::default()
+    };
+    let measurements = capture::measure(display_bounds, &opts)?;
+
+    if json {
+        let out: Vec<_> = measurements
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "from": [m.x1, m.y1],
+                    "to": [m.x2, m.y2],
+                    "dx": m.dx(),
+                    "dy": m.dy(),
+                    "length": m.length(),
+                    "angle": m.angle(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
+    }
+
+    if measurements.is_empty() {
+        eprintln!("no measurements");
+        return Ok(());
+    }
+    println!(
+        "{:<14} {:<14} {:>6} {:>6} {:>9} {:>8}",
+        "From", "To", "dX", "dY", "Length", "Angle"
+    );
+    println!("{}", "-".repeat(62));
+    for m in &measurements {
+        println!(
+            "{:<14} {:<14} {:>6} {:>6} {:>9.1} {:>7.1}°",
+            format!("{},{}", m.x1, m.y1),
+            format!("{},{}", m.x2, m.y2),
+            m.dx(),
+            m.dy(),
+            m.length(),
+            m.angle()
+        );
+    }
+    Ok(())
+}
+
+/// How often a waiting `watch` checks for Ctrl-C.
+const WATCH_POLL: Duration = Duration::from_millis(100);
+
+fn cmd_watch(config: Config, args: WatchArgs) -> Result<()> {
+    if args.interval.is_zero() {
+        anyhow::bail!("--interval must be more than 0");
+    }
+    let capture_mode = match &args.geometry {
+        Some(g) => CaptureMode::Region(capture::parse_region(g).map_err(|e| anyhow::anyhow!(e))?),
+        None => CaptureMode::Fullscreen,
+    };
+    let display_bounds = match &args.display {
+        Some(spec) => {
+            let monitor = capture::resolve_display(spec)
+                .context("failed to resolve display")?;
+            eprintln!("display: {monitor}");
+            Some(monitor.to_region())
+        }
+        None => None,
+    };
+    let capture_opts = CaptureOptions {
+        include_cursor: args.cursor || config.capture.include_cursor,
+        selector: config.selector.clone(),
+        ..Default::default()
+    };
+    let name = args
+        .name
+        .unwrap_or_else(|| format!("watch-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let tags = [name.clone()];
+
+    // Finish the capture in progress on Ctrl-C rather than dying mid-save.
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&stop))
+            .context("failed to install signal handlers")?;
+    }
+    let stopped = || stop.load(std::sync::atomic::Ordering::SeqCst);
+
+    let display_server = capture::detect_display_server()?;
+    let storage = Storage::new(config.clone());
+    let mut filter = ChangeFilter::new(args.threshold);
+    let (mut kept, mut dropped) = (0, 0);
+    eprintln!(
+        "watching every {:.1}s as '{name}' ({display_server}), Ctrl-C to stop...",
+        args.interval.as_secs_f64()
+    );
+
+    let start = Instant::now();
+    let mut next = start;
+    while !stopped()
+        && args.count.is_none_or(|n| kept < n)
+        && args.duration.is_none_or(|d| start.elapsed() < d)
+    {
+        // A failed capture (a screensaver, a display briefly gone) shouldn't
+        // end an unattended session, and neither should a frame that fails
+        // to scale.
+        match capture::capture(&capture_mode, display_bounds, &capture_opts) {
+            Ok(mut shot) => match filter.check(&shot.image) {
+                Some(changed) => match shot.apply_scale(config.image.scale) {
+                    Ok(()) => {
+                        let entry = storage
+                            .save(&shot, &capture_mode, display_server, args.format.as_ref())
+                            .context("failed to save screenshot")?;
+                        storage.tag(&entry.id, &tags)?;
+                        kept += 1;
+                        eprintln!(
+                            "{} kept {} ({:.1}% changed)",
+                            chrono::Local::now().format("%H:%M:%S"),
+                            entry.id,
+                            changed * 100.0
+                        );
+                    }
+                    Err(e) => eprintln!("scaling failed: {e}"),
+                },
+                None => dropped += 1,
+            },
+            Err(e) => eprintln!("capture failed: {e}"),
+        }
+
+        // Skip slots missed while a slow capture ran rather than bursting.
+        next += args.interval;
+        while next < Instant::now() {
+            next += args.interval;
+        }
+        // Short naps so Ctrl-C doesn't wait out a long interval.
+        while !stopped() && Instant::now() < next {
+            std::thread::sleep(next.saturating_duration_since(Instant::now()).min(WATCH_POLL));
+        }
+    }
+
+    eprintln!("kept {kept}, dropped {dropped} unchanged; tag: {}", name.to_lowercase());
+    Ok(())
+}
+
+/// Stop the recording on SIGINT/SIGTERM so the file is finalized rather
+/// than cut off, and toggle pause on SIGUSR1 or Enter in the terminal.
+fn listen_for_controls(control: &Arc<RecordControl>) -> Result<()> {
+    use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1};
+    use std::io::IsTerminal;
+
+    let toggle = |control: &RecordControl| {
+        let state = if control.toggle_pause() { "paused" } else { "resumed" };
+        eprintln!("{state}");
+    };
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGUSR1])
+        .context("failed to install signal handlers")?;
+    let c = Arc::clone(control);
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGUSR1 => toggle(&c),
+                _ => {
+                    eprintln!("stopping...");
+                    c.stop();
+                }
+            }
+        }
+    });
+
+    if std::io::stdin().is_terminal() {
+        let c = Arc::clone(control);
+        std::thread::spawn(move || {
+            for _ in std::io::stdin().lines() {
+                toggle(&c);
+            }
+        });
+    }
+    Ok(())
+}
+
 fn cmd_display(cmd: DisplayCommand) -> Result<()> {
     match cmd {
         DisplayCommand::List => {
@@ -239,14 +1032,139 @@ fn cmd_display(cmd: DisplayCommand) -> Result<()> {
                 eprintln!("no monitors found");
                 return Ok(());
             }
+            println!(
+                "{:>2} {:<12} {:<20} {:>3} {:>3} {:>8} {:>10} {:>4} {:>5}  Model",
+                "#", "Name", "Geometry", "Pri", "Rot", "Refresh", "Size (mm)", "DPI", "Scale"
+            );
+            println!("{}", "-".repeat(96));
             for (i, m) in monitors.iter().enumerate() {
-                println!("{i}: {m}");
+                let geometry = format!("{}x{}+{}+{}", m.width, m.height, m.x, m.y);
+                let primary = if m.primary { "*" } else { "" };
+                let refresh = m
+                    .refresh
+                    .map(|r| format!("{r:.2}Hz"))
+                    .unwrap_or_else(|| "-".to_string());
+                let size = match (m.width_mm, m.height_mm) {
+                    (Some(w), Some(h)) => format!("{w}x{h}"),
+                    _ => "-".to_string(),
+                };
+                let dpi = m
+                    .dpi
+                    .map(|d| format!("{d:.0}"))
+                    .unwrap_or_else(|| "-".to_string());
+                let scale = m
+                    .scale
+                    .map(|s| format!("{s:.2}"))
+                    .unwrap_or_else(|| "-".to_string());
+                // Wayland outputs without make and model still have a description.
+                let model = m
+                    .identity()
+                    .or_else(|| m.description.clone())
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{:>2} {:<12} {:<20} {:>3} {:>3} {:>8} {:>10} {:>4} {:>5}  {}",
+                    i,
+                    m.name,
+                    geometry,
+                    primary,
+                    m.rotation,
+                    refresh,
+                    size,
+                    dpi,
+                    scale,
+                    model
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn cmd_window(cmd: WindowCommand) -> Result<()> {
+    match cmd {
+        WindowCommand::List => {
+            let windows = capture::list_windows().context("failed to list windows")?;
+            if windows.is_empty() {
+                eprintln!("no windows found");
+                return Ok(());
+            }
+            println!(
+                "{:<10} {:<24} {:>8} {:>4} {:<20} Title",
+                "ID", "Class", "PID", "Desk", "Geometry"
+            );
+            println!("{}", "-".repeat(80));
+            for w in &windows {
+                let class = format!("{}.{}", w.instance, w.class);
+                let pid = w.pid.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string());
+                let desktop = w
+                    .desktop
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| "*".to_string());
+                let g = w.geometry;
+                let geometry = format!("{}x{}+{}+{}", g.width, g.height, g.x, g.y);
+                println!(
+                    "{:<10} {:<24} {:>8} {:>4} {:<20} {}",
+                    format!("{:#x}", w.id),
+                    class,
+                    pid,
+                    desktop,
+                    geometry,
+                    w.title
+                );
             }
             Ok(())
         }
     }
 }
 
+fn cmd_region(cmd: RegionCommand) -> Result<()> {
+    let mut store = RegionStore::load().context("failed to load regions")?;
+    match cmd {
+        RegionCommand::Save {
+            name,
+            geometry,
+            display,
+        } => {
+            let region = capture::parse_region(&geometry).map_err(|e| anyhow::anyhow!(e))?;
+            let monitor = match display {
+                Some(spec) => Some(
+                    capture::resolve_display(&spec)
+                        .context("failed to resolve display")?
+                        .name,
+                ),
+                None => None,
+            };
+            let saved = SavedRegion::relative_to(region, monitor);
+            let shown = saved.to_string();
+            store.set_named(&name, saved).map_err(|e| anyhow::anyhow!(e))?;
+            store.save().context("failed to save regions")?;
+            eprintln!("saved {name}: {shown}");
+        }
+        RegionCommand::List => {
+            if store.named.is_empty() && store.recent.is_empty() {
+                eprintln!("no saved or recent regions");
+                return Ok(());
+            }
+            for (name, saved) in &store.named {
+                println!("{name:<20} {saved}");
+            }
+            for (i, saved) in store.recent.iter().enumerate() {
+                println!(
+                    "{:<20} {saved}  ({})",
+                    format!("recent {}", i + 1),
+                    saved.timestamp.format("%Y-%m-%d %H:%M")
+                );
+            }
+        }
+        RegionCommand::Delete { name } => {
+            let saved = store.remove_named(&name).map_err(|e| anyhow::anyhow!(e))?;
+            store.save().context("failed to save regions")?;
+            eprintln!("deleted {name}: {saved}");
+        }
+    }
+    Ok(())
+}
+
 fn cmd_list(config: Config, limit: usize, tag: Option<String>) -> Result<()> {
     let storage = Storage::new(config);
     let entries = storage.list(Some(limit))?;
@@ -259,10 +1177,10 @@ fn cmd_list(config: Config, limit: usize, tag: Option<String>) -> Result<()> {
     print_header();
 
     for m in &entries {
-        if let Some(ref tag_filter) = tag {
-            if !m.tags.iter().any(|t| t.contains(tag_filter)) {
-                continue;
-            }
+        if let Some(ref tag_filter) = tag
+            && !m.tags.iter().any(|t| t.contains(tag_filter))
+        {
+            continue;
         }
 
         print_entry(m);
@@ -321,10 +1239,7 @@ fn cmd_delete(config: Config, id: String) -> Result<()> {
 }
 
 fn print_header() {
-    println!(
-        "{:<24} {:<20} {:>10} {}",
-        "ID", "Date", "Size", "Tags"
-    );
+    println!("{:<24} {:<20} {:>10} Tags", "ID", "Date", "Size");
     println!("{}", "-".repeat(80));
 }
 
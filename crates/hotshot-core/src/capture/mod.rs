@@ -1,9 +1,11 @@
 pub mod wayland;
 pub mod x11;
 
+use crate::config::{ImageScale, SelectorConfig};
 use image::RgbaImage;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -29,8 +31,162 @@ pub enum CaptureMode {
     Region(Region),
     RegionInteractive,
     ActiveWindow,
+    WindowInteractive,
+    Window(WindowSelector),
+    Scroll(ScrollSpec),
 }
 
+/// A scrolling capture: the area to scroll through and when to stop.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrollSpec {
+    /// Area to capture; selected interactively when `None`.
+    pub region: Option<Region>,
+    /// Stop once the stitched image is this tall.
+    pub max_height: u32,
+    /// Wheel clicks between frames. Fewer is slower but keeps more overlap.
+    pub clicks: u32,
+    /// How long to let the content settle after scrolling.
+    pub settle: Duration,
+}
+
+impl Default for ScrollSpec {
+    fn default() -> Self {
+        Self {
+            region: None,
+            max_height: 20_000,
+            clicks: 3,
+            settle: Duration::from_millis(300),
+        }
+    }
+}
+
+/// Picks a top-level window by id and/or regexes on its class and title.
+/// Every criterion that is set must match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WindowSelector {
+    pub id: Option<u32>,
+    /// Matched against both the WM_CLASS instance and class names.
+    pub class: Option<String>,
+    pub title: Option<String>,
+}
+
+/// A top-level client window as reported by the window manager.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub id: u32,
+    /// WM_CLASS instance name
+    pub instance: String,
+    /// WM_CLASS class name
+    pub class: String,
+    pub title: String,
+    pub pid: Option<u32>,
+    /// Virtual desktop index; `None` if unknown or shown on all desktops.
+    pub desktop: Option<u32>,
+    pub geometry: Region,
+}
+
+/// Knobs that apply to every capture mode.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CaptureOptions {
+    /// Draw the mouse pointer into the image.
+    pub include_cursor: bool,
+    /// Window modes: include the window manager's frame (title bar, borders).
+    pub decorations: bool,
+    /// Window modes: trim client-side decoration shadows.
+    pub strip_shadow: bool,
+    /// Wait this long before capturing (before the selector, for interactive modes).
+    pub delay: Option<Duration>,
+    /// Show an on-screen countdown during `delay` (X11 only).
+    pub countdown: bool,
+    /// Key bindings and aids for the interactive region selector (X11 only).
+    pub selector: SelectorConfig,
+    /// Earlier regions (root coordinates) the region selector can snap to.
+    pub snap_regions: Vec<Region>,
+}
+
+/// Pointer hotspot position, in image coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CursorPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A captured image plus what we learned about the screen while taking it.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub image: RgbaImage,
+    /// Set when the cursor was requested and drawn into the image.
+    pub cursor: Option<CursorPosition>,
+    /// Screen area the image was taken from, in root coordinates, if known.
+    pub region: Option<Region>,
+    /// Size as captured, once the image has been resampled.
+    pub physical_size: Option<(u32, u32)>,
+}
+
+impl From<RgbaImage> for Capture {
+    fn from(image: RgbaImage) -> Self {
+        Self { image, cursor: None, region: None, physical_size: None }
+    }
+}
+
+impl Capture {
+    /// Resample for `--scale`. `logical` divides by the scale of the monitor
+    /// the capture came from: its DPI over 96 in half steps, or `Xft.dpi`
+    /// when the monitor doesn't report a physical size. On Wayland the
+    /// image is at the outputs' pixel density, so the scale is how much
+    /// larger it is than the area it shows.
+    pub fn apply_scale(&mut self, scale: ImageScale) -> Result<(), CaptureError> {
+        let factor = match scale {
+            ImageScale::Physical => return Ok(()),
+            ImageScale::Factor(factor) => factor,
+            ImageScale::Logical => 1.0 / self.device_scale()?,
+        };
+        self.resample(factor);
+        Ok(())
+    }
+
+    fn device_scale(&self) -> Result<f64, CaptureError> {
+        if detect_display_server()? != DisplayServer::X11 {
+            return Ok(self
+                .region
+                .filter(|r| r.width > 0)
+                .map_or(1.0, |r| self.image.width() as f64 / r.width as f64));
+        }
+        let monitor = self
+            .region
+            .and_then(|r| largest_overlap(list_monitors().ok()?, &r));
+        let dpi = match monitor.and_then(|m| m.dpi) {
+            Some(dpi) => Some(dpi),
+            None => x11::xft_dpi()?,
+        };
+        Ok(dpi.map_or(1.0, |dpi| ((dpi / BASE_DPI) * 2.0).round().max(2.0) / 2.0))
+    }
+
+    /// Resize the image by `factor`, keeping the cursor position in step.
+    pub fn resample(&mut self, factor: f64) {
+        let (w, h) = self.image.dimensions();
+        let width = ((w as f64 * factor).round() as u32).max(1);
+        let height = ((h as f64 * factor).round() as u32).max(1);
+        if (width, height) == (w, h) {
+            return;
+        }
+        self.image = image::imageops::resize(
+            &self.image,
+            width,
+            height,
+            image::imageops::FilterType::Lanczos3,
+        );
+        self.cursor = self.cursor.map(|c| CursorPosition {
+            x: (c.x as f64 * factor).round() as i32,
+            y: (c.y as f64 * factor).round() as i32,
+        });
+        self.physical_size.get_or_insert((w, h));
+    }
+}
+
+/// DPI at a scale of 1; `--scale logical` sizes captures back to it.
+const BASE_DPI: f64 = 96.0;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Region {
     pub x: i32,
@@ -39,13 +195,59 @@ pub struct Region {
     pub height: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Region {
+    /// The overlapping part of two regions, if they overlap.
+    pub fn intersect(&self, other: &Region) -> Option<Region> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width as i32).min(other.x + other.width as i32);
+        let bottom = (self.y + self.height as i32).min(other.y + other.height as i32);
+        (right > x && bottom > y).then(|| Region {
+            x,
+            y,
+            width: (right - x) as u32,
+            height: (bottom - y) as u32,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Monitor {
     pub name: String,
     pub x: i16,
     pub y: i16,
     pub width: u16,
     pub height: u16,
+    #[serde(default)]
+    pub primary: bool,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    #[serde(default)]
+    pub rotation: u16,
+    /// Refresh rate in Hz.
+    #[serde(default)]
+    pub refresh: Option<f64>,
+    /// Physical size, when the monitor reports one.
+    #[serde(default)]
+    pub width_mm: Option<u32>,
+    #[serde(default)]
+    pub height_mm: Option<u32>,
+    /// Horizontal pixels per inch, from the physical size.
+    #[serde(default)]
+    pub dpi: Option<f64>,
+    /// Three-letter PNP manufacturer id from the EDID, e.g. "DEL". On
+    /// Wayland, the make the compositor reports.
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+    /// Model name from the EDID, or its product code when it has none.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// The compositor's human-readable description (Wayland).
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Hardware pixels per logical pixel, fractional scaling included
+    /// (Wayland).
+    #[serde(default)]
+    pub scale: Option<f64>,
 }
 
 impl fmt::Display for Monitor {
@@ -59,6 +261,31 @@ impl fmt::Display for Monitor {
 }
 
 impl Monitor {
+    /// Whether the root-space point lies on this monitor.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x as i32
+            && y >= self.y as i32
+            && x < self.x as i32 + self.width as i32
+            && y < self.y as i32 + self.height as i32
+    }
+
+    /// Pixels per inch across the monitor's width, if its size is known.
+    pub fn compute_dpi(&self) -> Option<f64> {
+        self.width_mm
+            .filter(|&mm| mm > 0)
+            .map(|mm| self.width as f64 * 25.4 / mm as f64)
+    }
+
+    /// "MANUFACTURER MODEL" from the EDID, if there is one.
+    pub fn identity(&self) -> Option<String> {
+        match (&self.manufacturer, &self.model) {
+            (Some(m), Some(model)) => Some(format!("{m} {model}")),
+            (None, Some(model)) => Some(model.clone()),
+            (Some(m), None) => Some(m.clone()),
+            (None, None) => None,
+        }
+    }
+
     pub fn to_region(&self) -> Region {
         Region {
             x: self.x as i32,
@@ -69,6 +296,89 @@ impl Monitor {
     }
 }
 
+/// A colour picked off the screen.
+#[derive(Debug, Clone, Serialize)]
+pub struct PickedColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /// Point picked, in root coordinates. The Wayland portal doesn't say.
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    /// Side of the square averaged around the point.
+    pub sample: u32,
+    /// Monitor the point is on.
+    pub monitor: Option<String>,
+}
+
+impl PickedColor {
+    /// "#RRGGBB"
+    pub fn hex(&self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+
+    /// CSS "rgb(r, g, b)"
+    pub fn css_rgb(&self) -> String {
+        format!("rgb({}, {}, {})", self.r, self.g, self.b)
+    }
+
+    /// Hue in degrees, saturation and lightness in percent.
+    pub fn hsl(&self) -> (f64, f64, f64) {
+        let [r, g, b] = [self.r, self.g, self.b].map(|c| c as f64 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+        let d = max - min;
+        if d == 0.0 {
+            return (0.0, 0.0, l * 100.0);
+        }
+        let s = d / (1.0 - (2.0 * l - 1.0).abs());
+        let h = if max == r {
+            60.0 * ((g - b) / d).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / d + 2.0)
+        } else {
+            60.0 * ((r - g) / d + 4.0)
+        };
+        (h, s * 100.0, l * 100.0)
+    }
+
+    /// CSS "hsl(h, s%, l%)", rounded to whole numbers.
+    pub fn css_hsl(&self) -> String {
+        let (h, s, l) = self.hsl();
+        format!("hsl({:.0}, {:.0}%, {:.0}%)", h.round() % 360.0, s, l)
+    }
+}
+
+/// A distance measured on screen, between two points in root coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Measurement {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+impl Measurement {
+    pub fn dx(&self) -> i32 {
+        self.x2 - self.x1
+    }
+
+    pub fn dy(&self) -> i32 {
+        self.y2 - self.y1
+    }
+
+    /// Straight-line length in pixels.
+    pub fn length(&self) -> f64 {
+        (self.dx() as f64).hypot(self.dy() as f64)
+    }
+
+    /// Angle in degrees, counter-clockwise from pointing right (so up is 90).
+    pub fn angle(&self) -> f64 {
+        (-self.dy() as f64).atan2(self.dx() as f64).to_degrees()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DisplayServer {
@@ -105,11 +415,58 @@ pub fn detect_display_server() -> Result<DisplayServer, CaptureError> {
     Err(CaptureError::NoDisplay)
 }
 
-pub fn capture(mode: &CaptureMode, display_bounds: Option<Region>) -> Result<RgbaImage, CaptureError> {
+pub fn capture(
+    mode: &CaptureMode,
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Capture, CaptureError> {
     let display = detect_display_server()?;
+    if let Some(delay) = opts.delay {
+        match display {
+            DisplayServer::X11 if opts.countdown => x11::countdown(delay, display_bounds)?,
+            _ => std::thread::sleep(delay),
+        }
+    }
     match display {
-        DisplayServer::X11 => x11::capture(mode, display_bounds),
-        DisplayServer::Wayland => wayland::capture(mode),
+        DisplayServer::X11 => x11::capture(mode, display_bounds, opts),
+        DisplayServer::Wayland => wayland::capture(mode, display_bounds, opts),
+    }
+}
+
+/// Pick a colour with the frozen-screen picker (X11) or the desktop portal
+/// (Wayland), averaged over a `sample`-pixel square around the point. The
+/// portal picks a single pixel and doesn't report where.
+pub fn pick_color(
+    display_bounds: Option<Region>,
+    sample: u32,
+    opts: &CaptureOptions,
+) -> Result<PickedColor, CaptureError> {
+    match detect_display_server()? {
+        DisplayServer::X11 => {
+            let ((x, y), [r, g, b]) = x11::pick_color(display_bounds, sample, opts)?;
+            let monitor = x11::list_monitors()
+                .ok()
+                .and_then(|monitors| monitors.into_iter().find(|m| m.contains(x, y)))
+                .map(|m| m.name);
+            Ok(PickedColor { r, g, b, x: Some(x), y: Some(y), sample, monitor })
+        }
+        DisplayServer::Wayland => {
+            let [r, g, b] = wayland::pick_color()?;
+            Ok(PickedColor { r, g, b, x: None, y: None, sample: 1, monitor: None })
+        }
+    }
+}
+
+/// Measure distances on screen with the overlay ruler (X11 only).
+pub fn measure(
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Vec<Measurement>, CaptureError> {
+    match detect_display_server()? {
+        DisplayServer::X11 => x11::measure(display_bounds, opts),
+        DisplayServer::Wayland => Err(CaptureError::Wayland(
+            "measuring is not supported on Wayland".to_string(),
+        )),
     }
 }
 
@@ -117,19 +474,163 @@ pub fn list_monitors() -> Result<Vec<Monitor>, CaptureError> {
     let display = detect_display_server()?;
     match display {
         DisplayServer::X11 => x11::list_monitors(),
+        DisplayServer::Wayland => wayland::list_monitors(),
+    }
+}
+
+/// List top-level windows, bottom to top in stacking order.
+pub fn list_windows() -> Result<Vec<WindowInfo>, CaptureError> {
+    let display = detect_display_server()?;
+    match display {
+        DisplayServer::X11 => x11::list_windows(),
         DisplayServer::Wayland => Err(CaptureError::Other(
-            "monitor listing not yet supported on Wayland".to_string(),
+            "window listing not yet supported on Wayland".to_string(),
         )),
     }
 }
 
+/// Find the topmost window in `windows` (bottom-to-top order) matching `selector`.
+pub fn find_window<'a>(
+    windows: &'a [WindowInfo],
+    selector: &WindowSelector,
+) -> Result<&'a WindowInfo, CaptureError> {
+    let compile = |pattern: &Option<String>| {
+        pattern
+            .as_deref()
+            .map(regex::Regex::new)
+            .transpose()
+            .map_err(|e| CaptureError::Other(format!("invalid regex: {e}")))
+    };
+    let class_re = compile(&selector.class)?;
+    let title_re = compile(&selector.title)?;
+
+    windows
+        .iter()
+        .rev()
+        .find(|w| {
+            selector.id.is_none_or(|id| w.id == id)
+                && class_re
+                    .as_ref()
+                    .is_none_or(|re| re.is_match(&w.class) || re.is_match(&w.instance))
+                && title_re.as_ref().is_none_or(|re| re.is_match(&w.title))
+        })
+        .ok_or_else(|| CaptureError::Other("no window matches the selector".to_string()))
+}
+
+/// Parse a window id in hex ("0x3a00007") or decimal.
+pub fn parse_window_id(s: &str) -> Result<u32, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("invalid window id: '{s}'. use: 0x3a00007 or 60817415"))
+}
+
+/// `--display` value that captures every monitor at once.
+pub const ALL_DISPLAYS: &str = "all";
+
+/// The monitor showing most of `rect`, if any of it is on screen.
+fn largest_overlap(monitors: Vec<Monitor>, rect: &Region) -> Option<Monitor> {
+    let overlap = |m: &Monitor| {
+        m.to_region()
+            .intersect(rect)
+            .map_or(0, |r| r.width as u64 * r.height as u64)
+    };
+    monitors
+        .into_iter()
+        .filter(|m| overlap(m) > 0)
+        .max_by_key(overlap)
+}
+
+fn pointer_position() -> Result<(i32, i32), CaptureError> {
+    match detect_display_server()? {
+        DisplayServer::X11 => x11::pointer_position(),
+        DisplayServer::Wayland => Err(CaptureError::Other(
+            "--display cursor is not yet supported on Wayland".to_string(),
+        )),
+    }
+}
+
+fn active_window_rect() -> Result<Region, CaptureError> {
+    match detect_display_server()? {
+        DisplayServer::X11 => x11::active_window_rect(),
+        DisplayServer::Wayland => wayland::active_window_rect(),
+    }
+}
+
+/// Cut a capture spanning several monitors into one capture per monitor
+/// it covers, in `monitors` order.
+pub fn split_by_monitor(
+    capture: &Capture,
+    monitors: &[Monitor],
+) -> Result<Vec<(Monitor, Capture)>, CaptureError> {
+    let area = capture.region.ok_or_else(|| {
+        CaptureError::Other("capture has no screen position to split by monitor".to_string())
+    })?;
+    if area.width == 0 || area.height == 0 {
+        return Err(CaptureError::Other("capture covers an empty area".to_string()));
+    }
+    // Scaled Wayland outputs are captured at more pixels than the area
+    // they cover; this is 1 on X11.
+    let sx = capture.image.width() as f64 / area.width as f64;
+    let sy = capture.image.height() as f64 / area.height as f64;
+    let mut parts = Vec::new();
+    for monitor in monitors {
+        let Some(r) = monitor.to_region().intersect(&area) else {
+            continue;
+        };
+        let dx = ((r.x - area.x) as f64 * sx).round() as i32;
+        let dy = ((r.y - area.y) as f64 * sy).round() as i32;
+        let (width, height) = (
+            ((r.width as f64 * sx).round() as u32).min(capture.image.width() - dx as u32),
+            ((r.height as f64 * sy).round() as u32).min(capture.image.height() - dy as u32),
+        );
+        let image =
+            image::imageops::crop_imm(&capture.image, dx as u32, dy as u32, width, height)
+                .to_image();
+        let cursor = capture
+            .cursor
+            .map(|c| CursorPosition { x: c.x - dx, y: c.y - dy })
+            .filter(|c| c.x >= 0 && c.y >= 0 && c.x < width as i32 && c.y < height as i32);
+        parts.push((monitor.clone(), Capture { image, cursor, region: Some(r), physical_size: None }));
+    }
+    Ok(parts)
+}
+
 /// Resolve a display specifier (name like "HDMI-1" or index like "0") to a Monitor.
+///
+/// Also accepts "cursor" (the monitor under the pointer) and "focused" (the
+/// one showing most of the active window). "all" is handled by the callers
+/// that support it, see [`split_by_monitor`].
 pub fn resolve_display(spec: &str) -> Result<Monitor, CaptureError> {
+    if spec == ALL_DISPLAYS {
+        return Err(CaptureError::Other(
+            "--display all only works for fullscreen and region captures".to_string(),
+        ));
+    }
+
     let monitors = list_monitors()?;
     if monitors.is_empty() {
         return Err(CaptureError::Other("no monitors found".to_string()));
     }
 
+    match spec {
+        "cursor" => {
+            let (x, y) = pointer_position()?;
+            return monitors
+                .into_iter()
+                .find(|m| m.contains(x, y))
+                .ok_or_else(|| CaptureError::Other("pointer is not on any monitor".to_string()));
+        }
+        "focused" => {
+            let rect = active_window_rect()?;
+            return largest_overlap(monitors, &rect).ok_or_else(|| {
+                CaptureError::Other("active window is not on any monitor".to_string())
+            });
+        }
+        _ => {}
+    }
+
     // Try as index first
     if let Ok(idx) = spec.parse::<usize>() {
         let count = monitors.len();
@@ -141,15 +642,57 @@ pub fn resolve_display(spec: &str) -> Result<Monitor, CaptureError> {
         });
     }
 
-    // Try as name
-    monitors
-        .into_iter()
-        .find(|m| m.name == spec)
-        .ok_or_else(|| CaptureError::Other(format!("no display named '{spec}'")))
+    // Then as connector name, then as EDID model ("U2720Q" or "DEL U2720Q"),
+    // which survives docking stations renaming connectors.
+    if let Some(m) = monitors.iter().find(|m| m.name == spec) {
+        return Ok(m.clone());
+    }
+    let by_model = |m: &&Monitor| {
+        m.model.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(spec))
+            || m.identity().is_some_and(|s| s.eq_ignore_ascii_case(spec))
+    };
+    let mut matches = monitors.iter().filter(by_model);
+    match (matches.next(), matches.next()) {
+        (Some(m), None) => Ok(m.clone()),
+        (Some(_), Some(_)) => Err(CaptureError::Other(format!(
+            "several displays match '{spec}'; use a connector name or index"
+        ))),
+        _ => Err(CaptureError::Other(format!("no display named '{spec}'"))),
+    }
+}
+
+/// Parse a duration like "3", "1.5s", "500ms" or "2m". Bare numbers are seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (num, scale) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60.0)
+    } else {
+        (s, 1.0)
+    };
+    let value: f64 = num
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid duration: '{s}'. use: 3, 1.5s, 500ms or 2m"))?;
+    Duration::try_from_secs_f64(value * scale)
+        .map_err(|_| format!("invalid duration: '{s}'. must be non-negative"))
 }
 
-/// Parse a region string like "100,200,800,600" or "800x600+100+200"
+/// Parse a region string like "100,200,800,600" or "800x600+100+200", or
+/// the name of a region saved with `hotshot region save`
 pub fn parse_region(s: &str) -> Result<Region, String> {
+    let geometry = parse_geometry(s);
+    if geometry.is_ok() || !s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return geometry;
+    }
+    let store = crate::regions::RegionStore::load().map_err(|e| e.to_string())?;
+    store.named(s)?.resolve()
+}
+
+fn parse_geometry(s: &str) -> Result<Region, String> {
     // Try WxH+X+Y format
     if s.contains('x') && s.contains('+') {
         let parts: Vec<&str> = s.split(['x', '+']).collect();
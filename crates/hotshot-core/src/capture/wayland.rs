@@ -1,24 +1,165 @@
-use super::{CaptureError, CaptureMode, Region};
+mod client;
+mod image_copy;
+mod ipc;
+mod output;
+mod screencopy;
+mod shm;
+mod toplevel;
+
+use super::{Capture, CaptureError, CaptureMode, CaptureOptions, Monitor, Region, WindowSelector};
 use image::RgbaImage;
 
-pub fn capture(mode: &CaptureMode) -> Result<RgbaImage, CaptureError> {
+/// Capture through wlroots screencopy when the compositor offers it, and
+/// through the desktop portal otherwise. `display_bounds` limits fullscreen
+/// captures to one monitor; the interactive modes can't be confined to one.
+pub fn capture(
+    mode: &CaptureMode,
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Capture, CaptureError> {
+    match mode {
+        CaptureMode::ActiveWindow => return capture_window(None, opts),
+        CaptureMode::Window(selector) => return capture_window(Some(selector), opts),
+        // The portal's picker spans every output and doesn't say where
+        // the selection was.
+        CaptureMode::RegionInteractive | CaptureMode::WindowInteractive
+            if display_bounds.is_some() =>
+        {
+            return Err(CaptureError::Wayland(
+                "--display only applies to fullscreen captures on Wayland".to_string(),
+            ));
+        }
+        _ => {}
+    }
+
+    let area = match mode {
+        CaptureMode::Fullscreen => display_bounds,
+        CaptureMode::Region(region) => Some(*region),
+        _ => None,
+    };
+    let mut client = client::Client::connect().ok();
+    if let CaptureMode::Fullscreen | CaptureMode::Region(_) = mode
+        && let Some(client) = client.as_mut().filter(|c| screencopy::available(c))
+    {
+        // The compositor draws the pointer itself and doesn't say where.
+        let (image, region) = screencopy::capture(client, area, opts.include_cursor)?;
+        return Ok(Capture { image, cursor: None, region: Some(region), physical_size: None });
+    }
+
+    // The portal decides on its own whether to include the pointer.
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .map_err(|e| CaptureError::Wayland(format!("failed to create runtime: {e}")))?;
+    let image = rt.block_on(capture_async(mode))?;
+
+    let layout = client.and_then(|c| output::layout(&c.state.outputs));
+    match (mode, area) {
+        (_, Some(area)) => {
+            let (image, region) = crop(&image, area, layout)?;
+            Ok(Capture { image, cursor: None, region: Some(region), physical_size: None })
+        }
+        (CaptureMode::Fullscreen, None) => {
+            Ok(Capture { image, cursor: None, region: layout, physical_size: None })
+        }
+        _ => Ok(Capture::from(image)),
+    }
+}
 
-    rt.block_on(capture_async(mode))
+/// Outputs as monitors, in the order the compositor announced them.
+pub fn list_monitors() -> Result<Vec<Monitor>, CaptureError> {
+    let client = client::Client::connect()?;
+    Ok(client
+        .state
+        .outputs
+        .iter()
+        .enumerate()
+        .map(|(i, output)| output.to_monitor(i))
+        .collect())
 }
 
 async fn capture_async(mode: &CaptureMode) -> Result<RgbaImage, CaptureError> {
     match mode {
-        CaptureMode::Fullscreen => capture_portal(false).await,
+        // Cropped afterwards.
+        CaptureMode::Fullscreen | CaptureMode::Region(_) => capture_portal(false).await,
         CaptureMode::RegionInteractive => capture_portal(true).await,
-        CaptureMode::Region(region) => capture_fullscreen_and_crop(*region).await,
-        CaptureMode::ActiveWindow => capture_portal(false).await,
+        // The interactive portal dialog lets the user pick a window.
+        CaptureMode::WindowInteractive => capture_portal(true).await,
+        CaptureMode::ActiveWindow | CaptureMode::Window(_) => {
+            unreachable!("window modes are handled in capture")
+        }
+        CaptureMode::Scroll(_) => Err(CaptureError::Wayland(
+            "scroll capture is not supported on Wayland".to_string(),
+        )),
     }
 }
 
+/// Capture one window: the one `selector` matches, or the focused one.
+/// ext-image-copy-capture copies the window itself; failing that, Sway and
+/// Hyprland say where it is and the screen is captured there. The portal
+/// can't do this without asking, so other compositors get an error rather
+/// than a fullscreen shot.
+fn capture_window(
+    selector: Option<&WindowSelector>,
+    opts: &CaptureOptions,
+) -> Result<Capture, CaptureError> {
+    let mut client = client::Client::connect()?;
+    if image_copy::available(&client) {
+        let toplevels = toplevel::list(&mut client)?;
+        let window = match selector {
+            Some(selector) => toplevel::find(&toplevels, selector)?,
+            None => toplevel::focused(&mut client, &toplevels)?,
+        };
+        return image_copy::capture_toplevel(&mut client, &window.handle, opts.include_cursor)
+            .map(Capture::from);
+    }
+    let Some(compositor) = ipc::Compositor::detect() else {
+        return Err(CaptureError::Wayland(
+            "window capture needs the ext-image-copy-capture and ext-foreign-toplevel-list \
+             protocols, or Sway or Hyprland, and this compositor has neither; use --pick"
+                .to_string(),
+        ));
+    };
+    let rect = match selector {
+        Some(selector) => compositor.find(selector)?,
+        None => compositor.focused()?,
+    };
+    drop(client);
+    capture(&CaptureMode::Region(rect), None, opts)
+}
+
+/// Where the focused window is, from the compositor's IPC.
+pub fn active_window_rect() -> Result<Region, CaptureError> {
+    ipc::Compositor::detect()
+        .ok_or_else(|| {
+            CaptureError::Other(
+                "--display focused needs Sway or Hyprland on Wayland".to_string(),
+            )
+        })?
+        .focused()
+}
+
+/// Pick a pixel's colour with the portal's own picker.
+pub fn pick_color() -> Result<[u8; 3], CaptureError> {
+    use ashpd::desktop::Color;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| CaptureError::Wayland(format!("failed to create runtime: {e}")))?;
+
+    let color = rt.block_on(async {
+        Color::pick()
+            .send()
+            .await
+            .map_err(|e| CaptureError::Wayland(format!("pick color request failed: {e}")))?
+            .response()
+            .map_err(|e| CaptureError::Wayland(format!("pick color response failed: {e}")))
+    })?;
+    let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Ok([channel(color.red()), channel(color.green()), channel(color.blue())])
+}
+
 async fn capture_portal(interactive: bool) -> Result<RgbaImage, CaptureError> {
     use ashpd::desktop::screenshot::Screenshot;
 
@@ -42,20 +183,31 @@ async fn capture_portal(interactive: bool) -> Result<RgbaImage, CaptureError> {
     Ok(img.into_rgba8())
 }
 
-async fn capture_fullscreen_and_crop(region: Region) -> Result<RgbaImage, CaptureError> {
-    let full = capture_portal(false).await?;
-
-    let x = region.x.max(0) as u32;
-    let y = region.y.max(0) as u32;
-    let width = region.width.min(full.width().saturating_sub(x));
-    let height = region.height.min(full.height().saturating_sub(y));
+/// Cut `area` (global coordinates) out of a full-screen portal shot that
+/// covers `layout`, at whatever density the portal chose. Without a layout
+/// the shot is taken to start at 0,0 with one pixel per unit. Returns the
+/// image and the part of `area` that was on screen.
+fn crop(
+    full: &RgbaImage,
+    area: Region,
+    layout: Option<Region>,
+) -> Result<(RgbaImage, Region), CaptureError> {
+    let layout =
+        layout.unwrap_or(Region { x: 0, y: 0, width: full.width(), height: full.height() });
+    let visible = area
+        .intersect(&layout)
+        .ok_or_else(|| CaptureError::Wayland("region is outside screen bounds".to_string()))?;
 
+    let sx = full.width() as f64 / layout.width as f64;
+    let sy = full.height() as f64 / layout.height as f64;
+    let x = (((visible.x - layout.x) as f64 * sx).round() as u32).min(full.width());
+    let y = (((visible.y - layout.y) as f64 * sy).round() as u32).min(full.height());
+    let width = ((visible.width as f64 * sx).round() as u32).min(full.width() - x);
+    let height = ((visible.height as f64 * sy).round() as u32).min(full.height() - y);
     if width == 0 || height == 0 {
-        return Err(CaptureError::Wayland(
-            "region is outside screen bounds".to_string(),
-        ));
+        return Err(CaptureError::Wayland("region is outside screen bounds".to_string()));
     }
 
-    let cropped = image::imageops::crop_imm(&full, x, y, width, height).to_image();
-    Ok(cropped)
+    let cropped = image::imageops::crop_imm(full, x, y, width, height).to_image();
+    Ok((cropped, visible))
 }
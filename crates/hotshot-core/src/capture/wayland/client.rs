@@ -0,0 +1,158 @@
+//! Connection to the compositor for the native capture protocols.
+//!
+//! Holds the globals the compositor advertises, what it told us about each
+//! output, and the progress of the frame copy in flight. The protocol
+//! modules add their own `Dispatch` impls for [`State`].
+
+use super::CaptureError;
+use super::output::Output;
+use super::toplevel::{Toplevel, WlrToplevel};
+use wayland_client::globals::{GlobalList, GlobalListContents, registry_queue_init};
+use wayland_client::protocol::{wl_buffer, wl_output, wl_registry, wl_shm, wl_shm_pool};
+use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle, delegate_noop};
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1;
+
+pub(super) struct Client {
+    pub globals: GlobalList,
+    pub queue: EventQueue<State>,
+    pub state: State,
+}
+
+#[derive(Default)]
+pub(super) struct State {
+    pub outputs: Vec<Output>,
+    pub toplevels: Vec<Toplevel>,
+    pub wlr_toplevels: Vec<WlrToplevel>,
+    pub frame: Frame,
+}
+
+/// Layout of a shared-memory buffer the compositor will copy into.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct BufferSpec {
+    pub format: wl_shm::Format,
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+}
+
+/// The frame copy in flight.
+#[derive(Default)]
+pub(super) struct Frame {
+    /// The buffer to copy into, once the compositor has said.
+    pub buffer: Option<BufferSpec>,
+    /// Size and format when they come separately (image copy), the
+    /// stride being ours to pick.
+    pub size: Option<(u32, u32)>,
+    pub format: Option<wl_shm::Format>,
+    /// All buffer constraints have been sent.
+    pub buffer_done: bool,
+    /// The copy comes out upside down.
+    pub y_invert: bool,
+    /// Transform to undo, when the copy reports its own.
+    pub transform: Option<wl_output::Transform>,
+    pub status: FrameStatus,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) enum FrameStatus {
+    #[default]
+    Pending,
+    Ready,
+    Failed(String),
+}
+
+impl Client {
+    /// Connect to `$WAYLAND_DISPLAY` and learn the outputs, with their
+    /// xdg-output details when the compositor has them.
+    pub fn connect() -> Result<Self, CaptureError> {
+        let conn = Connection::connect_to_env()
+            .map_err(|e| CaptureError::Wayland(format!("failed to connect to compositor: {e}")))?;
+        let (globals, queue) = registry_queue_init::<State>(&conn)
+            .map_err(|e| CaptureError::Wayland(format!("failed to read globals: {e}")))?;
+        let mut client = Self { globals, queue, state: State::default() };
+
+        let qh = client.queue.handle();
+        let registry = client.globals.registry();
+        let wanted = wl_output::WlOutput::interface().name;
+        client.globals.contents().with_list(|list| {
+            for global in list.iter().filter(|g| g.interface == wanted) {
+                let index = client.state.outputs.len();
+                let wl = registry.bind(global.name, global.version.min(4), &qh, index);
+                client.state.outputs.push(Output::new(wl));
+            }
+        });
+        if client.has::<ZxdgOutputManagerV1>() {
+            let manager: ZxdgOutputManagerV1 = client.bind(1..=3)?;
+            for (index, output) in client.state.outputs.iter().enumerate() {
+                manager.get_xdg_output(&output.wl, &qh, index);
+            }
+            manager.destroy();
+        }
+        client.roundtrip()?;
+        Ok(client)
+    }
+
+    /// Whether the compositor advertises the global `I`.
+    pub fn has<I: Proxy>(&self) -> bool {
+        let name = I::interface().name;
+        self.globals.contents().with_list(|list| list.iter().any(|g| g.interface == name))
+    }
+
+    /// Bind the global `I` at a version in `versions`.
+    pub fn bind<I>(&self, versions: std::ops::RangeInclusive<u32>) -> Result<I, CaptureError>
+    where
+        I: Proxy + 'static,
+        State: Dispatch<I, ()>,
+    {
+        self.globals
+            .bind(&self.queue.handle(), versions, ())
+            .map_err(|e| CaptureError::Wayland(format!("{}: {e}", I::interface().name)))
+    }
+
+    pub fn handle(&self) -> QueueHandle<State> {
+        self.queue.handle()
+    }
+
+    pub fn roundtrip(&mut self) -> Result<(), CaptureError> {
+        self.queue
+            .roundtrip(&mut self.state)
+            .map(|_| ())
+            .map_err(|e| CaptureError::Wayland(format!("roundtrip: {e}")))
+    }
+
+    /// Dispatch events until `done` holds.
+    pub fn dispatch_until(&mut self, done: impl Fn(&State) -> bool) -> Result<(), CaptureError> {
+        while !done(&self.state) {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .map_err(|e| CaptureError::Wayland(format!("dispatch: {e}")))?;
+        }
+        Ok(())
+    }
+
+    /// Dispatch until the frame in flight is ready; failure is an error.
+    pub fn wait_ready(&mut self) -> Result<(), CaptureError> {
+        self.dispatch_until(|s| s.frame.status != FrameStatus::Pending)?;
+        match &self.state.frame.status {
+            FrameStatus::Failed(reason) => Err(CaptureError::Wayland(reason.clone())),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // Outputs plugged in mid-capture are ignored.
+    }
+}
+
+delegate_noop!(State: ignore wl_shm::WlShm);
+delegate_noop!(State: wl_shm_pool::WlShmPool);
+delegate_noop!(State: ignore wl_buffer::WlBuffer);
@@ -0,0 +1,152 @@
+//! ext-image-copy-capture: copying a single window.
+//!
+//! The staging image capture protocols copy the contents of a capture
+//! source into a buffer we hand over. Here the source is a window from the
+//! foreign toplevel list, so the copy is the window itself, whatever is on
+//! top of it.
+
+use super::client::{BufferSpec, Client, Frame, FrameStatus, State};
+use super::shm::{self, ShmBuffer, untransform};
+use super::{CaptureError, toplevel};
+use image::RgbaImage;
+use wayland_client::protocol::{wl_output, wl_shm::WlShm};
+use wayland_client::{Connection, Dispatch, QueueHandle, WEnum, delegate_noop};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1;
+use wayland_protocols::ext::image_capture_source::v1::client::{
+    ext_foreign_toplevel_image_capture_source_manager_v1 as toplevel_source,
+    ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+};
+use wayland_protocols::ext::image_copy_capture::v1::client::{
+    ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1},
+    ext_image_copy_capture_manager_v1::{ExtImageCopyCaptureManagerV1, Options},
+    ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+};
+use ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1;
+use toplevel_source::ExtForeignToplevelImageCaptureSourceManagerV1;
+
+/// Whether the compositor can copy individual windows.
+pub(super) fn available(client: &Client) -> bool {
+    client.has::<ExtImageCopyCaptureManagerV1>()
+        && client.has::<ExtForeignToplevelImageCaptureSourceManagerV1>()
+        && toplevel::available(client)
+}
+
+/// Copy the window behind `handle`, with the pointer painted in if it's
+/// over the window and `paint_cursor` is set.
+pub(super) fn capture_toplevel(
+    client: &mut Client,
+    handle: &ExtForeignToplevelHandleV1,
+    paint_cursor: bool,
+) -> Result<RgbaImage, CaptureError> {
+    let sources: ExtForeignToplevelImageCaptureSourceManagerV1 = client.bind(1..=1)?;
+    let manager: ExtImageCopyCaptureManagerV1 = client.bind(1..=1)?;
+    let shm: WlShm = client.bind(1..=1)?;
+    let qh = client.handle();
+
+    let source = sources.create_source(handle, &qh, ());
+    let options = if paint_cursor { Options::PaintCursors } else { Options::empty() };
+    let session = manager.create_session(&source, options, &qh, ());
+    client.state.frame = Frame::default();
+    let result = copy(client, &session, &shm);
+
+    session.destroy();
+    source.destroy();
+    manager.destroy();
+    sources.destroy();
+    result
+}
+
+fn copy(
+    client: &mut Client,
+    session: &ExtImageCopyCaptureSessionV1,
+    shm: &WlShm,
+) -> Result<RgbaImage, CaptureError> {
+    client.dispatch_until(|s| s.frame.buffer_done || s.frame.status != FrameStatus::Pending)?;
+    if let FrameStatus::Failed(reason) = &client.state.frame.status {
+        return Err(CaptureError::Wayland(reason.clone()));
+    }
+    let (Some((width, height)), Some(format)) = (client.state.frame.size, client.state.frame.format)
+    else {
+        return Err(CaptureError::Wayland(
+            "compositor offered no buffer format we can read".to_string(),
+        ));
+    };
+    let spec = BufferSpec { format, width, height, stride: width * 4 };
+
+    let buffer = ShmBuffer::new(client, shm, spec)?;
+    let frame = session.create_frame(&client.handle(), ());
+    frame.attach_buffer(&buffer.buffer);
+    frame.damage_buffer(0, 0, width as i32, height as i32);
+    frame.capture();
+    let result = client.wait_ready();
+    frame.destroy();
+    result?;
+
+    let image = buffer.read(false)?;
+    let transform = client.state.frame.transform.unwrap_or(wl_output::Transform::Normal);
+    Ok(untransform(image, transform))
+}
+
+impl Dispatch<ExtImageCopyCaptureSessionV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtImageCopyCaptureSessionV1,
+        event: ext_image_copy_capture_session_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_image_copy_capture_session_v1::Event;
+
+        let frame = &mut state.frame;
+        match event {
+            Event::BufferSize { width, height } => frame.size = Some((width, height)),
+            Event::ShmFormat { format: WEnum::Value(format) }
+                if frame.format.is_none() && shm::supported(format) =>
+            {
+                frame.format = Some(format);
+            }
+            Event::Done => frame.buffer_done = true,
+            Event::Stopped => {
+                frame.status = FrameStatus::Failed("the window went away".to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtImageCopyCaptureFrameV1,
+        event: ext_image_copy_capture_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_image_copy_capture_frame_v1::{Event, FailureReason};
+
+        let frame = &mut state.frame;
+        match event {
+            Event::Transform { transform: WEnum::Value(transform) } => {
+                frame.transform = Some(transform);
+            }
+            Event::Ready => frame.status = FrameStatus::Ready,
+            Event::Failed { reason } => {
+                let reason = match reason {
+                    WEnum::Value(FailureReason::BufferConstraints) => {
+                        "the window changed size during the copy"
+                    }
+                    WEnum::Value(FailureReason::Stopped) => "the window went away",
+                    _ => "compositor failed to copy the window",
+                };
+                frame.status = FrameStatus::Failed(reason.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: ExtForeignToplevelImageCaptureSourceManagerV1);
+delegate_noop!(State: ExtImageCaptureSourceV1);
+delegate_noop!(State: ExtImageCopyCaptureManagerV1);
@@ -0,0 +1,484 @@
+//! Window geometry from the compositor's own IPC.
+//!
+//! Wayland has no protocol for asking where a window is, but Sway and
+//! Hyprland both answer over a Unix socket. Knowing the rectangle is enough
+//! to cut a window out of a fullscreen capture, so this makes window
+//! captures work on those compositors with nothing but the portal.
+
+use super::toplevel::Matcher;
+use super::{CaptureError, Region, WindowSelector};
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long to wait for the compositor to answer.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A compositor we can ask, and the socket to ask on.
+pub(super) enum Compositor {
+    Sway(PathBuf),
+    Hyprland(PathBuf),
+}
+
+impl Compositor {
+    /// The compositor this session runs under, going by the variables each
+    /// one sets for its clients.
+    pub fn detect() -> Option<Self> {
+        if let Some(path) = std::env::var_os("SWAYSOCK") {
+            return Some(Self::Sway(path.into()));
+        }
+        let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+        // Hyprland 0.40 moved the socket from /tmp into the runtime dir.
+        let runtime = dirs::runtime_dir().map(|d| d.join("hypr").join(&signature));
+        let socket = [runtime, Some(PathBuf::from("/tmp/hypr").join(&signature))]
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join(".socket.sock"))
+            .find(|path| path.exists())?;
+        Some(Self::Hyprland(socket))
+    }
+
+    /// Where the focused window is, in global coordinates.
+    pub fn focused(&self) -> Result<Region, CaptureError> {
+        let window = match self {
+            Self::Sway(socket) => sway_windows(socket)?.into_iter().find(|w| w.focused),
+            Self::Hyprland(socket) => {
+                let window: HyprClient = hyprland_request(socket, "activewindow")?;
+                // With nothing focused the answer is an empty object.
+                window.at.is_some().then(|| window.into())
+            }
+        };
+        window
+            .map(|w| w.rect)
+            .ok_or_else(|| CaptureError::Wayland("no window is focused".to_string()))
+    }
+
+    /// Where the newest visible window matching `selector` is.
+    pub fn find(&self, selector: &WindowSelector) -> Result<Region, CaptureError> {
+        let matcher = Matcher::new(selector)?;
+        let windows = match self {
+            Self::Sway(socket) => sway_windows(socket)?,
+            Self::Hyprland(socket) => hyprland_windows(socket)?,
+        };
+        windows
+            .into_iter()
+            .rev()
+            .find(|w| {
+                let classes: Vec<&str> = w.classes.iter().map(String::as_str).collect();
+                matcher.matches(&classes, &w.title)
+            })
+            .map(|w| w.rect)
+            .ok_or_else(|| CaptureError::Other("no window matches the selector".to_string()))
+    }
+}
+
+/// A window on screen, as either compositor describes it.
+struct Window {
+    classes: Vec<String>,
+    title: String,
+    focused: bool,
+    rect: Region,
+}
+
+#[derive(Deserialize)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    id: i64,
+    name: Option<String>,
+    #[serde(default)]
+    focused: bool,
+    #[serde(default)]
+    visible: bool,
+    rect: Rect,
+    /// The window's content, relative to `rect`.
+    window_rect: Option<Rect>,
+    /// Set on windows only, never on workspaces or containers.
+    pid: Option<i64>,
+    app_id: Option<String>,
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+/// X11 properties of an Xwayland window.
+#[derive(Deserialize)]
+struct WindowProperties {
+    class: Option<String>,
+    instance: Option<String>,
+}
+
+/// The i3 IPC message type for `get_tree`.
+const SWAY_GET_TREE: u32 = 4;
+const SWAY_MAGIC: &[u8] = b"i3-ipc";
+
+/// Every visible window in Sway's tree, oldest first.
+fn sway_windows(socket: &Path) -> Result<Vec<Window>, CaptureError> {
+    let tree: SwayNode = sway_request(socket, SWAY_GET_TREE)?;
+    let mut nodes = Vec::new();
+    collect_sway_windows(tree, &mut nodes);
+    // Container ids only grow, so they order windows by age.
+    nodes.sort_by_key(|n| n.id);
+    Ok(nodes
+        .into_iter()
+        .map(|n| {
+            let (r, content) = (n.rect, n.window_rect);
+            let rect = match content {
+                Some(c) if c.width > 0 && c.height > 0 => {
+                    Region { x: r.x + c.x, y: r.y + c.y, width: c.width, height: c.height }
+                }
+                _ => Region { x: r.x, y: r.y, width: r.width, height: r.height },
+            };
+            let mut classes: Vec<String> = n.app_id.into_iter().collect();
+            if let Some(props) = n.window_properties {
+                classes.extend(props.instance);
+                classes.extend(props.class);
+            }
+            Window { classes, title: n.name.unwrap_or_default(), focused: n.focused, rect }
+        })
+        .collect())
+}
+
+fn collect_sway_windows(mut node: SwayNode, windows: &mut Vec<SwayNode>) {
+    let children: Vec<SwayNode> =
+        node.nodes.drain(..).chain(node.floating_nodes.drain(..)).collect();
+    for child in children {
+        collect_sway_windows(child, windows);
+    }
+    // Windows on hidden workspaces and in the scratchpad aren't visible.
+    if node.pid.is_some() && node.visible {
+        windows.push(node);
+    }
+}
+
+/// Send one i3 IPC message with an empty payload and decode the reply.
+fn sway_request<T: for<'de> Deserialize<'de>>(
+    socket: &Path,
+    kind: u32,
+) -> Result<T, CaptureError> {
+    let err = |e: std::io::Error| CaptureError::Wayland(format!("sway IPC failed: {e}"));
+    let mut stream = connect(socket).map_err(err)?;
+
+    let mut message = SWAY_MAGIC.to_vec();
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    stream.write_all(&message).map_err(err)?;
+
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header).map_err(err)?;
+    if &header[..6] != SWAY_MAGIC {
+        return Err(CaptureError::Wayland("sway IPC sent a malformed reply".to_string()));
+    }
+    let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload).map_err(err)?;
+    serde_json::from_slice(&payload)
+        .map_err(|e| CaptureError::Wayland(format!("unexpected sway IPC reply: {e}")))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HyprClient {
+    /// Top-left corner and size of the content; both absent when the
+    /// reply is empty.
+    at: Option<(i32, i32)>,
+    size: Option<(u32, u32)>,
+    #[serde(default)]
+    class: String,
+    #[serde(default)]
+    initial_class: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default = "yes")]
+    mapped: bool,
+    #[serde(default)]
+    hidden: bool,
+    workspace: Option<HyprWorkspace>,
+}
+
+#[derive(Deserialize)]
+struct HyprWorkspace {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HyprMonitor {
+    active_workspace: HyprWorkspace,
+    special_workspace: Option<HyprWorkspace>,
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl From<HyprClient> for Window {
+    fn from(c: HyprClient) -> Self {
+        let (x, y) = c.at.unwrap_or_default();
+        let (width, height) = c.size.unwrap_or_default();
+        let mut classes = vec![c.class];
+        if !c.initial_class.is_empty() && c.initial_class != classes[0] {
+            classes.push(c.initial_class);
+        }
+        Window { classes, title: c.title, focused: false, rect: Region { x, y, width, height } }
+    }
+}
+
+/// Every window on a workspace some monitor shows, oldest first.
+fn hyprland_windows(socket: &Path) -> Result<Vec<Window>, CaptureError> {
+    let monitors: Vec<HyprMonitor> = hyprland_request(socket, "monitors")?;
+    let shown: Vec<i64> = monitors
+        .iter()
+        .flat_map(|m| [Some(m.active_workspace.id), m.special_workspace.as_ref().map(|w| w.id)])
+        .flatten()
+        .filter(|&id| id != 0)
+        .collect();
+    let clients: Vec<HyprClient> = hyprland_request(socket, "clients")?;
+    Ok(clients
+        .into_iter()
+        .filter(|c| c.mapped && !c.hidden && c.at.is_some())
+        .filter(|c| c.workspace.as_ref().is_some_and(|w| shown.contains(&w.id)))
+        .map(Window::from)
+        .collect())
+}
+
+/// Run one Hyprland command with JSON output; Hyprland answers and closes
+/// the connection.
+fn hyprland_request<T: for<'de> Deserialize<'de>>(
+    socket: &Path,
+    command: &str,
+) -> Result<T, CaptureError> {
+    let err = |e: std::io::Error| CaptureError::Wayland(format!("Hyprland IPC failed: {e}"));
+    let mut stream = connect(socket).map_err(err)?;
+    stream.write_all(format!("j/{command}").as_bytes()).map_err(err)?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).map_err(err)?;
+    serde_json::from_slice(&reply)
+        .map_err(|e| CaptureError::Wayland(format!("unexpected Hyprland IPC reply: {e}")))
+}
+
+fn connect(socket: &Path) -> std::io::Result<UnixStream> {
+    let stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    /// Answer every connection to a socket in a fresh temporary directory
+    /// with `handle`. Keep the directory alive for as long as the socket
+    /// is used.
+    fn serve(handle: fn(&mut UnixStream)) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                handle(&mut stream.unwrap());
+            }
+        });
+        (dir, path)
+    }
+
+    fn rect(r: Region) -> (i32, i32, u32, u32) {
+        (r.x, r.y, r.width, r.height)
+    }
+
+    fn selector(class: Option<&str>, title: Option<&str>) -> WindowSelector {
+        WindowSelector { id: None, class: class.map(Into::into), title: title.map(Into::into) }
+    }
+
+    /// Windows out of id order: a tiled one with a border and title bar,
+    /// an Xwayland one in a tabbed container, a floating one, one on a
+    /// hidden workspace and one in the scratchpad.
+    const SWAY_TREE: &str = r#"{
+        "id": 1, "type": "root", "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+        "nodes": [
+            {"id": 2, "type": "output", "name": "__i3",
+             "rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+             "nodes": [
+                {"id": 3, "type": "workspace", "name": "__i3_scratch",
+                 "rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+                 "floating_nodes": [
+                    {"id": 40, "name": "scratch", "pid": 40, "app_id": "foot",
+                     "visible": false,
+                     "rect": {"x": 0, "y": 0, "width": 500, "height": 400}}]}]},
+            {"id": 5, "type": "output", "name": "DP-1",
+             "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+             "nodes": [
+                {"id": 6, "type": "workspace", "name": "1", "visible": true,
+                 "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+                 "nodes": [
+                    {"id": 12, "name": "vim", "pid": 12, "app_id": "foot",
+                     "visible": true, "focused": true,
+                     "rect": {"x": 960, "y": 0, "width": 960, "height": 1080},
+                     "window_rect": {"x": 2, "y": 24, "width": 956, "height": 1054}},
+                    {"id": 7, "type": "con", "name": null, "layout": "tabbed",
+                     "rect": {"x": 0, "y": 0, "width": 960, "height": 1080},
+                     "nodes": [
+                        {"id": 9, "name": "Mozilla Firefox", "pid": 9, "app_id": null,
+                         "window_properties": {"class": "firefox", "instance": "Navigator"},
+                         "visible": true,
+                         "rect": {"x": 0, "y": 0, "width": 960, "height": 1080},
+                         "window_rect": {"x": 0, "y": 0, "width": 0, "height": 0}}]}],
+                 "floating_nodes": [
+                    {"id": 8, "name": "htop", "pid": 8, "app_id": "foot", "visible": true,
+                     "rect": {"x": 100, "y": 100, "width": 640, "height": 480},
+                     "window_rect": {"x": 0, "y": 0, "width": 640, "height": 480}}]},
+                {"id": 20, "type": "workspace", "name": "2", "visible": false,
+                 "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+                 "nodes": [
+                    {"id": 21, "name": "hidden", "pid": 21, "app_id": "foot",
+                     "visible": false,
+                     "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080}}]}]}]
+    }"#;
+
+    /// Answer `get_tree`, with the header and the payload in separate
+    /// writes so the reader has to go by the length.
+    fn sway_server(stream: &mut UnixStream) {
+        let mut header = [0u8; 14];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(&header[..6], SWAY_MAGIC);
+        assert_eq!(header[6..10], 0u32.to_ne_bytes());
+        assert_eq!(header[10..], SWAY_GET_TREE.to_ne_bytes());
+
+        let mut reply = SWAY_MAGIC.to_vec();
+        reply.extend_from_slice(&(SWAY_TREE.len() as u32).to_ne_bytes());
+        reply.extend_from_slice(&SWAY_GET_TREE.to_ne_bytes());
+        stream.write_all(&reply).unwrap();
+        stream.flush().unwrap();
+        stream.write_all(SWAY_TREE.as_bytes()).unwrap();
+        // Sway keeps the connection open after a reply.
+        let _ = stream.read(&mut header);
+    }
+
+    #[test]
+    fn sway_request_framing() {
+        let (_dir, socket) = serve(sway_server);
+        let tree: serde_json::Value = sway_request(&socket, SWAY_GET_TREE).unwrap();
+        assert_eq!(tree["id"], 1);
+        assert_eq!(tree["nodes"][1]["name"], "DP-1");
+    }
+
+    #[test]
+    fn sway_reply_with_bad_magic() {
+        let (_dir, socket) = serve(|stream| {
+            let mut header = [0u8; 14];
+            stream.read_exact(&mut header).unwrap();
+            stream.write_all(b"i4-ipc\x02\0\0\0\x04\0\0\0{}").unwrap();
+        });
+        let result = sway_request::<serde_json::Value>(&socket, SWAY_GET_TREE);
+        assert!(matches!(result, Err(CaptureError::Wayland(e)) if e.contains("malformed")));
+    }
+
+    #[test]
+    fn sway_windows_visible_and_by_age() {
+        let (_dir, socket) = serve(sway_server);
+        let windows = sway_windows(&socket).unwrap();
+        let found: Vec<_> = windows.iter().map(|w| (w.title.as_str(), rect(w.rect))).collect();
+        assert_eq!(
+            found,
+            [
+                ("htop", (100, 100, 640, 480)),
+                // An empty window_rect leaves the container's rectangle.
+                ("Mozilla Firefox", (0, 0, 960, 1080)),
+                // The content sits inside the border and title bar.
+                ("vim", (962, 24, 956, 1054)),
+            ]
+        );
+        assert_eq!(windows[1].classes, ["Navigator", "firefox"]);
+    }
+
+    #[test]
+    fn sway_focused_and_find() {
+        let (_dir, socket) = serve(sway_server);
+        let sway = Compositor::Sway(socket);
+        assert_eq!(rect(sway.focused().unwrap()), (962, 24, 956, 1054));
+        // The newest visible match, skipping the scratchpad and hidden
+        // workspace.
+        assert_eq!(rect(sway.find(&selector(Some("foot"), None)).unwrap()).0, 962);
+        assert_eq!(rect(sway.find(&selector(Some("^Navigator$"), None)).unwrap()).0, 0);
+        assert_eq!(rect(sway.find(&selector(Some("foot"), Some("^ht"))).unwrap()).0, 100);
+        assert!(sway.find(&selector(None, Some("scratch"))).is_err());
+        assert!(sway.find(&selector(Some("firefox"), Some("vim"))).is_err());
+    }
+
+    /// Answer like Hyprland: one command per connection, then hang up.
+    /// Workspace 1 and the special workspace -98 are shown.
+    fn hyprland_server(stream: &mut UnixStream) {
+        let mut request = [0u8; 64];
+        let len = stream.read(&mut request).unwrap();
+        let reply = match &request[..len] {
+            b"j/activewindow" => "{}",
+            b"j/monitors" => {
+                r#"[{"id": 0, "name": "DP-1", "activeWorkspace": {"id": 1, "name": "1"},
+                     "specialWorkspace": {"id": 0, "name": ""}},
+                    {"id": 1, "name": "HDMI-A-1", "activeWorkspace": {"id": 3, "name": "3"},
+                     "specialWorkspace": {"id": -98, "name": "special:magic"}}]"#
+            }
+            b"j/clients" => {
+                r#"[{"at": [0, 0], "size": [960, 1080], "class": "foot", "initialClass": "foot",
+                     "title": "vim", "mapped": true, "hidden": false,
+                     "workspace": {"id": 1, "name": "1"}},
+                    {"at": [10, 10], "size": [100, 100], "class": "foot", "title": "elsewhere",
+                     "mapped": true, "hidden": false, "workspace": {"id": 2, "name": "2"}},
+                    {"at": [1920, 0], "size": [800, 600], "class": "org.mozilla.firefox",
+                     "initialClass": "firefox", "title": "Mozilla Firefox",
+                     "mapped": true, "hidden": false, "workspace": {"id": 3, "name": "3"}},
+                    {"at": [2000, 50], "size": [400, 300], "class": "foot", "title": "magic",
+                     "mapped": true, "hidden": false,
+                     "workspace": {"id": -98, "name": "special:magic"}},
+                    {"at": [0, 0], "size": [50, 50], "class": "foot", "title": "tab",
+                     "mapped": true, "hidden": true, "workspace": {"id": 1, "name": "1"}},
+                    {"at": [0, 0], "size": [50, 50], "class": "foot", "title": "unmapped",
+                     "mapped": false, "hidden": false, "workspace": {"id": 1, "name": "1"}}]"#
+            }
+            other => panic!("unexpected request {:?}", String::from_utf8_lossy(other)),
+        };
+        stream.write_all(reply.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn hyprland_nothing_focused() {
+        let (_dir, socket) = serve(hyprland_server);
+        let result = Compositor::Hyprland(socket).focused();
+        assert!(matches!(result, Err(CaptureError::Wayland(e)) if e == "no window is focused"));
+    }
+
+    #[test]
+    fn hyprland_windows_on_shown_workspaces() {
+        let (_dir, socket) = serve(hyprland_server);
+        let windows = hyprland_windows(&socket).unwrap();
+        let titles: Vec<_> = windows.iter().map(|w| w.title.as_str()).collect();
+        assert_eq!(titles, ["vim", "Mozilla Firefox", "magic"]);
+        assert_eq!(rect(windows[2].rect), (2000, 50, 400, 300));
+        assert_eq!(windows[0].classes, ["foot"]);
+        assert_eq!(windows[1].classes, ["org.mozilla.firefox", "firefox"]);
+    }
+
+    #[test]
+    fn hyprland_find() {
+        let (_dir, socket) = serve(hyprland_server);
+        let hyprland = Compositor::Hyprland(socket);
+        assert_eq!(rect(hyprland.find(&selector(Some("^firefox$"), None)).unwrap()).0, 1920);
+        assert_eq!(rect(hyprland.find(&selector(Some("foot"), None)).unwrap()).0, 2000);
+        assert_eq!(rect(hyprland.find(&selector(None, Some("^vim$"))).unwrap()).0, 0);
+        assert!(hyprland.find(&selector(None, Some("elsewhere"))).is_err());
+        let by_id = WindowSelector { id: Some(1), class: None, title: None };
+        assert!(hyprland.find(&by_id).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Outputs as the compositor describes them.
+//!
+//! `wl_output` reports the hardware side: mode, physical size, make and
+//! model, transform and an integer scale. `zxdg_output_v1`, where the
+//! compositor has it, adds where the output sits in the global space and
+//! how big it is there, which the integer scale gets wrong under
+//! fractional scaling.
+
+use super::client::State;
+use super::{Monitor, Region};
+use wayland_client::protocol::wl_output::{self, Transform};
+use wayland_client::{Connection, Dispatch, QueueHandle, WEnum, delegate_noop};
+use wayland_protocols::xdg::xdg_output::zv1::client::{
+    zxdg_output_manager_v1::ZxdgOutputManagerV1,
+    zxdg_output_v1::{self, ZxdgOutputV1},
+};
+
+/// A `wl_output` and its last reported state.
+pub(super) struct Output {
+    pub wl: wl_output::WlOutput,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub make: String,
+    pub model: String,
+    /// Top-left corner in the compositor's global space.
+    pub x: i32,
+    pub y: i32,
+    /// Current mode in hardware pixels, before the transform.
+    pub mode: (i32, i32),
+    /// Refresh rate of the current mode in mHz.
+    pub refresh: i32,
+    /// Physical size in millimetres; 0 when unknown.
+    pub size_mm: (i32, i32),
+    pub scale: i32,
+    pub transform: Transform,
+    /// Position and size in the global space, from xdg-output.
+    pub logical_position: Option<(i32, i32)>,
+    pub logical_size: Option<(i32, i32)>,
+}
+
+impl Output {
+    pub fn new(wl: wl_output::WlOutput) -> Self {
+        Self {
+            wl,
+            name: None,
+            description: None,
+            make: String::new(),
+            model: String::new(),
+            x: 0,
+            y: 0,
+            mode: (0, 0),
+            refresh: 0,
+            size_mm: (0, 0),
+            scale: 1,
+            transform: Transform::Normal,
+            logical_position: None,
+            logical_size: None,
+        }
+    }
+
+    /// The mode turned upright: the output's size in hardware pixels as it
+    /// is seen.
+    fn upright_mode(&self) -> (i32, i32) {
+        match self.transform {
+            Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+                (self.mode.1, self.mode.0)
+            }
+            _ => self.mode,
+        }
+    }
+
+    /// The area the output shows in global coordinates. Without xdg-output
+    /// that's the upright mode divided by the integer scale.
+    pub fn logical(&self) -> Region {
+        let (x, y) = self.logical_position.unwrap_or((self.x, self.y));
+        let (w, h) = self.logical_size.unwrap_or_else(|| {
+            let (w, h) = self.upright_mode();
+            let scale = self.scale.max(1);
+            (w / scale, h / scale)
+        });
+        Region { x, y, width: w.max(0) as u32, height: h.max(0) as u32 }
+    }
+
+    /// Describe the output the way monitors are listed on X11. `index`
+    /// names outputs that don't report a name.
+    pub fn to_monitor(&self, index: usize) -> Monitor {
+        let area = self.logical();
+        let known = |s: &str| (!s.is_empty() && s != "Unknown").then(|| s.to_string());
+        let rotation = match self.transform {
+            Transform::_90 | Transform::Flipped90 => 90,
+            Transform::_180 | Transform::Flipped180 => 180,
+            Transform::_270 | Transform::Flipped270 => 270,
+            _ => 0,
+        };
+        let (width_mm, height_mm) = self.size_mm;
+        Monitor {
+            name: self.name.clone().unwrap_or_else(|| format!("output-{index}")),
+            x: area.x as i16,
+            y: area.y as i16,
+            width: area.width as u16,
+            height: area.height as u16,
+            primary: false,
+            rotation,
+            refresh: (self.refresh > 0).then(|| self.refresh as f64 / 1000.0),
+            width_mm: (width_mm > 0).then_some(width_mm as u32),
+            height_mm: (height_mm > 0).then_some(height_mm as u32),
+            // Both the mode and the physical size are before the transform.
+            dpi: (width_mm > 0).then(|| self.mode.0 as f64 * 25.4 / width_mm as f64),
+            manufacturer: known(&self.make),
+            model: known(&self.model),
+            description: self.description.clone(),
+            scale: (area.width > 0).then(|| self.upright_mode().0 as f64 / area.width as f64),
+        }
+    }
+}
+
+/// The smallest region containing every output.
+pub(super) fn layout(outputs: &[Output]) -> Option<Region> {
+    outputs
+        .iter()
+        .map(|o| o.logical())
+        .filter(|r| r.width > 0 && r.height > 0)
+        .map(|r| (r.x, r.y, r.x + r.width as i32, r.y + r.height as i32))
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+        .map(|(x, y, right, bottom)| Region {
+            x,
+            y,
+            width: (right - x) as u32,
+            height: (bottom - y) as u32,
+        })
+}
+
+impl Dispatch<wl_output::WlOutput, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let output = &mut state.outputs[*index];
+        match event {
+            wl_output::Event::Geometry {
+                x,
+                y,
+                physical_width,
+                physical_height,
+                make,
+                model,
+                transform,
+                ..
+            } => {
+                output.x = x;
+                output.y = y;
+                output.size_mm = (physical_width, physical_height);
+                output.make = make;
+                output.model = model;
+                if let WEnum::Value(transform) = transform {
+                    output.transform = transform;
+                }
+            }
+            wl_output::Event::Mode { flags: WEnum::Value(flags), width, height, refresh }
+                if flags.contains(wl_output::Mode::Current) =>
+            {
+                output.mode = (width, height);
+                output.refresh = refresh;
+            }
+            wl_output::Event::Scale { factor } => output.scale = factor,
+            wl_output::Event::Name { name } => output.name = Some(name),
+            wl_output::Event::Description { description } => {
+                output.description = Some(description);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let output = &mut state.outputs[*index];
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                output.logical_position = Some((x, y));
+            }
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                output.logical_size = Some((width, height));
+            }
+            // wl_output v4 says the same; keep whichever came first.
+            zxdg_output_v1::Event::Name { name } => {
+                output.name.get_or_insert(name);
+            }
+            zxdg_output_v1::Event::Description { description } => {
+                output.description.get_or_insert(description);
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: ZxdgOutputManagerV1);
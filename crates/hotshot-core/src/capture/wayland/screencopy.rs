@@ -0,0 +1,196 @@
+//! wlroots screencopy: outputs copied straight from the compositor.
+//!
+//! Sway, Hyprland and the other wlroots compositors advertise
+//! `zwlr_screencopy_manager_v1`, which copies an output, or a rectangle of
+//! one, into a buffer we hand it: no permission dialog and no temporary
+//! file. A capture spanning several outputs copies each one and lays the
+//! copies out on one canvas.
+
+use super::client::{BufferSpec, Client, Frame, FrameStatus, State};
+use super::shm::{self, ShmBuffer, untransform};
+use super::{CaptureError, Region, output};
+use image::{Rgba, RgbaImage};
+use wayland_client::protocol::wl_shm::WlShm;
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum, delegate_noop};
+use wayland_protocols_wlr::screencopy::v1::client::{
+    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+};
+
+/// Whether the compositor speaks screencopy.
+pub(super) fn available(client: &Client) -> bool {
+    client.has::<ZwlrScreencopyManagerV1>()
+}
+
+/// Capture `area` in global coordinates, or every output when `None`.
+/// Returns the image and the area it shows. Outputs are copied at their
+/// full resolution, so on a scaled output the image is larger than the
+/// area.
+pub(super) fn capture(
+    client: &mut Client,
+    area: Option<Region>,
+    overlay_cursor: bool,
+) -> Result<(RgbaImage, Region), CaptureError> {
+    let area = match area {
+        Some(area) => area,
+        None => output::layout(&client.state.outputs)
+            .ok_or_else(|| CaptureError::Wayland("no outputs to capture".to_string()))?,
+    };
+    let layout: Vec<(usize, Region)> = client
+        .state
+        .outputs
+        .iter()
+        .map(|o| o.logical())
+        .enumerate()
+        .filter(|(_, r)| r.width > 0 && r.height > 0)
+        .collect();
+
+    let manager: ZwlrScreencopyManagerV1 = client.bind(1..=3)?;
+    let shm: WlShm = client.bind(1..=1)?;
+    let mut pieces = Vec::new();
+    for (index, rect) in layout {
+        let Some(part) = rect.intersect(&area) else {
+            continue;
+        };
+        let copied = copy_output(client, &manager, &shm, index, rect, part, overlay_cursor);
+        match copied {
+            Ok(image) => pieces.push((part, image)),
+            Err(e) => {
+                manager.destroy();
+                return Err(e);
+            }
+        }
+    }
+    manager.destroy();
+
+    if pieces.is_empty() {
+        return Err(CaptureError::Wayland("region is outside screen bounds".to_string()));
+    }
+    Ok((stitch(area, pieces), area))
+}
+
+/// Copy `part` of the output at `index`, whose global area is `rect`.
+fn copy_output(
+    client: &mut Client,
+    manager: &ZwlrScreencopyManagerV1,
+    shm: &WlShm,
+    index: usize,
+    rect: Region,
+    part: Region,
+    overlay_cursor: bool,
+) -> Result<RgbaImage, CaptureError> {
+    let qh = client.handle();
+    let output = &client.state.outputs[index];
+    let transform = output.transform;
+    let whole = (part.width, part.height) == (rect.width, rect.height);
+    let frame = if whole {
+        manager.capture_output(overlay_cursor as i32, &output.wl, &qh, ())
+    } else {
+        manager.capture_output_region(
+            overlay_cursor as i32,
+            &output.wl,
+            part.x - rect.x,
+            part.y - rect.y,
+            part.width as i32,
+            part.height as i32,
+            &qh,
+            (),
+        )
+    };
+    client.state.frame = Frame::default();
+    let result = copy_frame(client, &frame, shm);
+    frame.destroy();
+    result.map(|image| untransform(image, transform))
+}
+
+fn copy_frame(
+    client: &mut Client,
+    frame: &ZwlrScreencopyFrameV1,
+    shm: &WlShm,
+) -> Result<RgbaImage, CaptureError> {
+    // Version 3 lists every buffer type and then says it's done; before
+    // that the shm buffer event is all there is.
+    let lists_all = frame.version() >= 3;
+    client.dispatch_until(|s| {
+        s.frame.status != FrameStatus::Pending
+            || if lists_all { s.frame.buffer_done } else { s.frame.buffer.is_some() }
+    })?;
+    if let FrameStatus::Failed(reason) = &client.state.frame.status {
+        return Err(CaptureError::Wayland(reason.clone()));
+    }
+    let spec = client.state.frame.buffer.ok_or_else(|| {
+        CaptureError::Wayland("compositor offered no buffer format we can read".to_string())
+    })?;
+
+    let buffer = ShmBuffer::new(client, shm, spec)?;
+    frame.copy(&buffer.buffer);
+    client.wait_ready()?;
+    buffer.read(client.state.frame.y_invert)
+}
+
+/// Lay the copies out on one canvas at the highest pixel density among
+/// them, scaling lower-density copies up to match. Parts of `area` no
+/// output shows stay black.
+fn stitch(area: Region, mut pieces: Vec<(Region, RgbaImage)>) -> RgbaImage {
+    if let [(r, _)] = pieces.as_slice()
+        && (r.x, r.y, r.width, r.height) == (area.x, area.y, area.width, area.height)
+    {
+        return pieces.remove(0).1;
+    }
+
+    let density = pieces
+        .iter()
+        .map(|(r, image)| image.width() as f64 / r.width as f64)
+        .fold(1.0, f64::max);
+    let px = |v: i64| (v as f64 * density).round() as i64;
+    let mut canvas = RgbaImage::from_pixel(
+        px(area.width as i64) as u32,
+        px(area.height as i64) as u32,
+        Rgba([0, 0, 0, 255]),
+    );
+    for (r, image) in pieces {
+        let size = (px(r.width as i64) as u32, px(r.height as i64) as u32);
+        let image = if image.dimensions() == size {
+            image
+        } else {
+            image::imageops::resize(&image, size.0, size.1, image::imageops::FilterType::Lanczos3)
+        };
+        let (x, y) = (px((r.x - area.x) as i64), px((r.y - area.y) as i64));
+        image::imageops::replace(&mut canvas, &image, x, y);
+    }
+    canvas
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_screencopy_frame_v1::{Event, Flags};
+
+        let frame = &mut state.frame;
+        match event {
+            Event::Buffer { format: WEnum::Value(format), width, height, stride }
+                if shm::supported(format) =>
+            {
+                frame.buffer = Some(BufferSpec { format, width, height, stride });
+            }
+            Event::Flags { flags: WEnum::Value(flags) } => {
+                frame.y_invert = flags.contains(Flags::YInvert);
+            }
+            Event::BufferDone => frame.buffer_done = true,
+            Event::Ready { .. } => frame.status = FrameStatus::Ready,
+            Event::Failed => {
+                frame.status =
+                    FrameStatus::Failed("compositor failed to copy the output".to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: ZwlrScreencopyManagerV1);
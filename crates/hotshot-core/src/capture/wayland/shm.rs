@@ -0,0 +1,131 @@
+//! Shared-memory buffers for the compositor to copy frames into.
+//!
+//! The buffer lives in an anonymous memfd, so nothing touches the
+//! filesystem; once the copy is done we read it back and turn it into an
+//! upright RGBA image.
+
+use super::CaptureError;
+use super::client::{BufferSpec, Client};
+use image::RgbaImage;
+use std::fs::File;
+use std::os::fd::{AsFd, FromRawFd};
+use std::os::unix::fs::FileExt;
+use wayland_client::protocol::{wl_buffer, wl_output, wl_shm, wl_shm_pool};
+
+pub(super) struct ShmBuffer {
+    file: File,
+    pool: wl_shm_pool::WlShmPool,
+    pub buffer: wl_buffer::WlBuffer,
+    pub spec: BufferSpec,
+}
+
+impl ShmBuffer {
+    pub fn new(
+        client: &Client,
+        shm: &wl_shm::WlShm,
+        spec: BufferSpec,
+    ) -> Result<Self, CaptureError> {
+        let size = spec.stride as u64 * spec.height as u64;
+        if size == 0 || size > i32::MAX as u64 {
+            return Err(CaptureError::Wayland(format!(
+                "unusable buffer size {}x{}",
+                spec.width, spec.height
+            )));
+        }
+
+        // SAFETY: memfd_create takes a NUL-terminated name and returns a new
+        // fd we take sole ownership of.
+        let fd = unsafe { libc::memfd_create(c"hotshot-capture".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            let e = std::io::Error::last_os_error();
+            return Err(CaptureError::Wayland(format!("memfd_create: {e}")));
+        }
+        // SAFETY: fd is valid and owned by nothing else.
+        let file = unsafe { File::from_raw_fd(fd) };
+        file.set_len(size)
+            .map_err(|e| CaptureError::Wayland(format!("failed to size buffer: {e}")))?;
+
+        let qh = client.handle();
+        let pool = shm.create_pool(file.as_fd(), size as i32, &qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            spec.width as i32,
+            spec.height as i32,
+            spec.stride as i32,
+            spec.format,
+            &qh,
+            (),
+        );
+        Ok(Self { file, pool, buffer, spec })
+    }
+
+    /// The buffer's contents as an RGBA image, flipped upright if
+    /// `y_invert` is set.
+    pub fn read(&self, y_invert: bool) -> Result<RgbaImage, CaptureError> {
+        let mut data = vec![0u8; self.spec.stride as usize * self.spec.height as usize];
+        self.file
+            .read_exact_at(&mut data, 0)
+            .map_err(|e| CaptureError::Wayland(format!("failed to read buffer: {e}")))?;
+        let image = to_rgba(&data, &self.spec)?;
+        Ok(if y_invert { image::imageops::flip_vertical(&image) } else { image })
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+        self.pool.destroy();
+    }
+}
+
+/// Whether [`to_rgba`] understands `format`.
+pub(super) fn supported(format: wl_shm::Format) -> bool {
+    matches!(
+        format,
+        wl_shm::Format::Argb8888
+            | wl_shm::Format::Xrgb8888
+            | wl_shm::Format::Abgr8888
+            | wl_shm::Format::Xbgr8888
+    )
+}
+
+/// Convert a 32-bit little-endian shm buffer to RGBA.
+fn to_rgba(data: &[u8], spec: &BufferSpec) -> Result<RgbaImage, CaptureError> {
+    // Byte positions of red, green and blue, and whether alpha is real.
+    let (r, g, b, alpha) = match spec.format {
+        wl_shm::Format::Argb8888 => (2, 1, 0, true),
+        wl_shm::Format::Xrgb8888 => (2, 1, 0, false),
+        wl_shm::Format::Abgr8888 => (0, 1, 2, true),
+        wl_shm::Format::Xbgr8888 => (0, 1, 2, false),
+        other => {
+            return Err(CaptureError::Wayland(format!("unsupported buffer format {other:?}")));
+        }
+    };
+    let mut image = RgbaImage::new(spec.width, spec.height);
+    for (y, row) in image.rows_mut().enumerate() {
+        let line = &data[y * spec.stride as usize..];
+        for (px, src) in row.zip(line.chunks_exact(4)) {
+            px.0 = [src[r], src[g], src[b], if alpha { src[3] } else { 255 }];
+        }
+    }
+    Ok(image)
+}
+
+/// Undo an output transform: the copy holds the output's pixels as they
+/// are sent to the panel, and this turns them back the way they look in
+/// the compositor's global space.
+pub(super) fn untransform(image: RgbaImage, transform: wl_output::Transform) -> RgbaImage {
+    use image::imageops::{flip_horizontal, rotate90, rotate180, rotate270};
+    use wl_output::Transform;
+
+    match transform {
+        Transform::_90 => rotate90(&image),
+        Transform::_180 => rotate180(&image),
+        Transform::_270 => rotate270(&image),
+        Transform::Flipped => flip_horizontal(&image),
+        Transform::Flipped90 => flip_horizontal(&rotate90(&image)),
+        Transform::Flipped180 => flip_horizontal(&rotate180(&image)),
+        Transform::Flipped270 => flip_horizontal(&rotate270(&image)),
+        _ => image,
+    }
+}
@@ -0,0 +1,230 @@
+//! Top-level windows as the compositor lists them.
+//!
+//! `ext_foreign_toplevel_list_v1` hands out the window handles image copy
+//! capture takes, with each window's app id and title. It doesn't say which
+//! window has focus, so that comes from the wlroots foreign toplevel
+//! manager and is matched back to a handle by app id and title.
+
+use super::client::{Client, State};
+use super::{CaptureError, WindowSelector};
+use wayland_client::{Connection, Dispatch, QueueHandle, event_created_child};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+/// A window from the ext foreign toplevel list.
+pub(super) struct Toplevel {
+    pub handle: ExtForeignToplevelHandleV1,
+    pub app_id: String,
+    pub title: String,
+    pub closed: bool,
+}
+
+/// A window as the wlroots manager reports it; only used to find focus.
+pub(super) struct WlrToplevel {
+    handle: ZwlrForeignToplevelHandleV1,
+    app_id: String,
+    title: String,
+    activated: bool,
+}
+
+/// Whether the compositor lists windows in a form image copy can use.
+pub(super) fn available(client: &Client) -> bool {
+    client.has::<ExtForeignToplevelListV1>()
+}
+
+/// Fetch the window list, oldest window first.
+pub(super) fn list(client: &mut Client) -> Result<Vec<Toplevel>, CaptureError> {
+    let list: ExtForeignToplevelListV1 = client.bind(1..=1)?;
+    client.roundtrip()?;
+    list.stop();
+    let mut toplevels = std::mem::take(&mut client.state.toplevels);
+    toplevels.retain(|t| !t.closed);
+    Ok(toplevels)
+}
+
+/// A window selector's patterns, compiled. Window ids are an X11 notion,
+/// so only class and title can be matched.
+pub(super) struct Matcher {
+    class: Option<regex::Regex>,
+    title: Option<regex::Regex>,
+}
+
+impl Matcher {
+    pub fn new(selector: &WindowSelector) -> Result<Self, CaptureError> {
+        if selector.id.is_some() {
+            return Err(CaptureError::Wayland(
+                "window ids are X11-only; select by --class or --title on Wayland".to_string(),
+            ));
+        }
+        let compile = |pattern: &Option<String>| {
+            pattern
+                .as_deref()
+                .map(regex::Regex::new)
+                .transpose()
+                .map_err(|e| CaptureError::Other(format!("invalid regex: {e}")))
+        };
+        Ok(Self { class: compile(&selector.class)?, title: compile(&selector.title)? })
+    }
+
+    /// Whether a window matches; the class pattern may match any of
+    /// `classes`.
+    pub fn matches(&self, classes: &[&str], title: &str) -> bool {
+        self.class.as_ref().is_none_or(|re| classes.iter().any(|c| re.is_match(c)))
+            && self.title.as_ref().is_none_or(|re| re.is_match(title))
+    }
+}
+
+/// The newest window matching `selector`, with the app id as its class.
+pub(super) fn find<'a>(
+    toplevels: &'a [Toplevel],
+    selector: &WindowSelector,
+) -> Result<&'a Toplevel, CaptureError> {
+    let matcher = Matcher::new(selector)?;
+    toplevels
+        .iter()
+        .rev()
+        .find(|t| matcher.matches(&[&t.app_id], &t.title))
+        .ok_or_else(|| CaptureError::Other("no window matches the selector".to_string()))
+}
+
+/// The focused window, matched from the wlroots manager's activated
+/// window to a list entry with the same app id and title.
+pub(super) fn focused<'a>(
+    client: &mut Client,
+    toplevels: &'a [Toplevel],
+) -> Result<&'a Toplevel, CaptureError> {
+    if !client.has::<ZwlrForeignToplevelManagerV1>() {
+        return Err(CaptureError::Wayland(
+            "this compositor doesn't say which window is focused \
+             (no zwlr_foreign_toplevel_manager_v1); use --class or --title"
+                .to_string(),
+        ));
+    }
+    let manager: ZwlrForeignToplevelManagerV1 = client.bind(1..=3)?;
+    client.roundtrip()?;
+    manager.stop();
+
+    let active = client
+        .state
+        .wlr_toplevels
+        .iter()
+        .find(|t| t.activated)
+        .ok_or_else(|| CaptureError::Wayland("no window is focused".to_string()))?;
+    toplevels
+        .iter()
+        .rev()
+        .find(|t| t.app_id == active.app_id && t.title == active.title)
+        .ok_or_else(|| {
+            CaptureError::Wayland(format!(
+                "focused window '{}' isn't in the capturable window list",
+                active.title
+            ))
+        })
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            state.toplevels.push(Toplevel {
+                handle: toplevel,
+                app_id: String::new(),
+                title: String::new(),
+                closed: false,
+            });
+        }
+    }
+
+    event_created_child!(State, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_foreign_toplevel_handle_v1::Event;
+
+        let Some(toplevel) = state.toplevels.iter_mut().find(|t| &t.handle == handle) else {
+            return;
+        };
+        match event {
+            Event::AppId { app_id } => toplevel.app_id = app_id,
+            Event::Title { title } => toplevel.title = title,
+            Event::Closed => toplevel.closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            state.wlr_toplevels.push(WlrToplevel {
+                handle: toplevel,
+                app_id: String::new(),
+                title: String::new(),
+                activated: false,
+            });
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::{Event, State as WindowState};
+
+        let Some(toplevel) = state.wlr_toplevels.iter_mut().find(|t| &t.handle == handle) else {
+            return;
+        };
+        match event {
+            Event::AppId { app_id } => toplevel.app_id = app_id,
+            Event::Title { title } => toplevel.title = title,
+            // An array of native-endian u32 states.
+            Event::State { state } => {
+                let activated = WindowState::Activated as u32;
+                toplevel.activated = state
+                    .chunks_exact(4)
+                    .any(|s| u32::from_ne_bytes([s[0], s[1], s[2], s[3]]) == activated);
+            }
+            Event::Closed => toplevel.activated = false,
+            _ => {}
+        }
+    }
+}
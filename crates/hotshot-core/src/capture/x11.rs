@@ -1,800 +1,335 @@
-use super::{CaptureError, CaptureMode, Monitor, Region};
+mod composite;
+mod countdown;
+mod cursor;
+mod keys;
+mod measure;
+mod monitor_info;
+mod overlay;
+mod pick;
+mod pixels;
+mod record;
+mod region;
+mod scroll;
+mod shm;
+mod snap;
+mod windows;
+
+use super::{
+    Capture, CaptureError, CaptureMode, CaptureOptions, CursorPosition, Measurement, Monitor,
+    Region, ScrollSpec, WindowInfo, WindowSelector,
+};
 use image::RgbaImage;
-use x11rb::connection::{Connection, RequestConnection};
-use x11rb::protocol::randr;
-use x11rb::protocol::render::{self, Pictformat};
+pub use keys::keysym_from_name;
+use overlay::{ESCAPE_KEYCODE, Extras, Overlay, draw_overlay};
+use pixels::PixelFormat;
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
 use x11rb::protocol::xproto::*;
 use x11rb::rust_connection::RustConnection;
 
-pub fn capture(mode: &CaptureMode, display_bounds: Option<Region>) -> Result<RgbaImage, CaptureError> {
-    match mode {
+pub fn capture(
+    mode: &CaptureMode,
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Capture, CaptureError> {
+    // The stitched image doesn't line up with the screen, so no cursor, and
+    // the region is the area that was scrolled through.
+    if let CaptureMode::Scroll(spec) = mode {
+        let (image, area) = scroll::capture(spec, display_bounds, opts)?;
+        return Ok(Capture { image, cursor: None, region: Some(area), physical_size: None });
+    }
+
+    // Snapshot the pointer up front: the interactive overlay swaps it for a
+    // crosshair, and we want the cursor as it was when the capture started.
+    // Without it the screenshot is still worth having.
+    let cursor = if opts.include_cursor {
+        let (conn, _) = connect()?;
+        cursor::get_cursor_image(&conn)
+            .inspect_err(|e| eprintln!("warning: capturing without the cursor: {e}"))
+            .ok()
+    } else {
+        None
+    };
+
+    let (mut image, origin) = match mode {
         CaptureMode::Fullscreen => {
             if let Some(bounds) = display_bounds {
-                capture_region(bounds)
+                capture_region(bounds)?
             } else {
-                capture_fullscreen()
+                capture_fullscreen()?
             }
         }
-        CaptureMode::Region(region) => capture_region(*region),
-        CaptureMode::RegionInteractive => capture_region_interactive(display_bounds),
-        CaptureMode::ActiveWindow => capture_active_window(),
-    }
-}
-
-pub fn list_monitors() -> Result<Vec<Monitor>, CaptureError> {
-    let (conn, screen_num) = connect()?;
-    let screen = &conn.setup().roots[screen_num];
-    let root = screen.root;
+        CaptureMode::Region(region) => capture_region(*region)?,
+        CaptureMode::RegionInteractive => region::select(display_bounds, opts)?,
+        CaptureMode::ActiveWindow => capture_active_window(opts)?,
+        CaptureMode::WindowInteractive => capture_window_interactive(display_bounds, opts)?,
+        CaptureMode::Window(selector) => capture_selected_window(selector, opts)?,
+        CaptureMode::Scroll(_) => unreachable!("handled above"),
+    };
 
-    let monitors = randr::get_monitors(&conn, root, true)
-        .map_err(|e| CaptureError::X11(format!("randr get_monitors: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("randr get_monitors reply: {e}")))?;
-
-    let mut result = Vec::new();
-    for info in &monitors.monitors {
-        let name = conn
-            .get_atom_name(info.name)
-            .map_err(|e| CaptureError::X11(format!("get_atom_name: {e}")))?
-            .reply()
-            .map_err(|e| CaptureError::X11(format!("get_atom_name reply: {e}")))?;
-        let name = String::from_utf8_lossy(&name.name).to_string();
-        result.push(Monitor {
-            name,
-            x: info.x,
-            y: info.y,
-            width: info.width,
-            height: info.height,
-        });
-    }
+    let cursor = cursor
+        .and_then(|c| c.draw_onto(&mut image, origin))
+        .map(|(x, y)| CursorPosition { x, y });
+    let region = Region {
+        x: origin.0,
+        y: origin.1,
+        width: image.width(),
+        height: image.height(),
+    };
 
-    Ok(result)
+    Ok(Capture { image, cursor, region: Some(region), physical_size: None })
 }
 
-fn connect() -> Result<(RustConnection, usize), CaptureError> {
-    x11rb::connect(None).map_err(|e| CaptureError::X11(format!("failed to connect: {e}")))
+/// Wait for `delay` while showing an on-screen countdown over `bounds`.
+pub fn countdown(delay: std::time::Duration, bounds: Option<Region>) -> Result<(), CaptureError> {
+    countdown::run(delay, bounds)
 }
 
-fn capture_fullscreen() -> Result<RgbaImage, CaptureError> {
-    let (conn, screen_num) = connect()?;
-    let screen = &conn.setup().roots[screen_num];
-    let width = screen.width_in_pixels;
-    let height = screen.height_in_pixels;
-
-    capture_window_region(&conn, screen.root, 0, 0, width, height)
+/// The screen area a recording of `mode` covers, picked like a screenshot.
+pub fn record_area(
+    mode: &CaptureMode,
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Region, CaptureError> {
+    record::area(mode, display_bounds, opts)
 }
 
-fn capture_region(region: Region) -> Result<RgbaImage, CaptureError> {
-    let (conn, screen_num) = connect()?;
-    let screen = &conn.setup().roots[screen_num];
-
-    capture_window_region(
-        &conn,
-        screen.root,
-        region.x as i16,
-        region.y as i16,
-        region.width as u16,
-        region.height as u16,
-    )
+/// Grab up to `max_frames` frames of `area`, handing each to `sink` with its
+/// on-screen duration. Returns the total duration.
+pub fn record(
+    area: Region,
+    rec: &crate::recording::RecordOptions,
+    max_frames: u32,
+    include_cursor: bool,
+    control: &crate::recording::RecordControl,
+    sink: impl FnMut(RgbaImage, std::time::Duration) -> Result<(), CaptureError>,
+) -> Result<std::time::Duration, CaptureError> {
+    record::record(area, rec, max_frames, include_cursor, control, sink)
 }
 
-// ---------------------------------------------------------------------------
-// Native X11 interactive region selector (replaces slop dependency)
-// ---------------------------------------------------------------------------
-
-/// RAII cleanup for X11 resources allocated during overlay.
-struct OverlayResources<'a> {
-    conn: &'a RustConnection,
-    window: u32,
-    screen_picture: u32,
-    back_pixmap: u32,
-    back_picture: u32,
-    flip_gc: u32,
-    dim_picture: u32,
-    dim_pixmap: u32,
-    border_picture: u32,
-    border_pixmap: u32,
-    cursor: u32,
-    cursor_font: u32,
-    grabbed: bool,
+/// Pick a colour on the frozen screen: the clicked point in root
+/// coordinates and its colour, averaged over a `sample`-pixel square.
+pub fn pick_color(
+    display_bounds: Option<Region>,
+    sample: u32,
+    opts: &CaptureOptions,
+) -> Result<((i32, i32), [u8; 3]), CaptureError> {
+    pick::pick(display_bounds, sample, opts)
 }
 
-impl<'a> Drop for OverlayResources<'a> {
-    fn drop(&mut self) {
-        let _ = render::free_picture(self.conn, self.border_picture);
-        let _ = self.conn.free_pixmap(self.border_pixmap);
-        let _ = render::free_picture(self.conn, self.dim_picture);
-        let _ = self.conn.free_pixmap(self.dim_pixmap);
-        let _ = self.conn.free_gc(self.flip_gc);
-        let _ = render::free_picture(self.conn, self.back_picture);
-        let _ = self.conn.free_pixmap(self.back_pixmap);
-        let _ = render::free_picture(self.conn, self.screen_picture);
-        let _ = self.conn.unmap_window(self.window);
-        let _ = self.conn.destroy_window(self.window);
-        let _ = self.conn.free_cursor(self.cursor);
-        let _ = self.conn.close_font(self.cursor_font);
-        if self.grabbed {
-            let _ = self.conn.ungrab_pointer(Time::CURRENT_TIME);
-            let _ = self.conn.ungrab_keyboard(Time::CURRENT_TIME);
-        }
-        let _ = self.conn.flush();
-        // Note: screen_pixmap is NOT freed here — caller extracts from it after drop.
-    }
+/// Measure distances on the frozen screen until the user finishes.
+pub fn measure(
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Vec<Measurement>, CaptureError> {
+    measure::measure(display_bounds, opts)
 }
 
-/// Find a 32-bit ARGB visual and the matching XRender Pictformat.
-fn find_argb_visual_and_format(
-    conn: &RustConnection,
-    screen: &Screen,
-) -> Result<(Visualid, u8, Pictformat), CaptureError> {
-    let formats = render::query_pict_formats(conn)
-        .map_err(|e| CaptureError::X11(format!("query_pict_formats: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("query_pict_formats reply: {e}")))?;
-
-    // Walk render screens → depths → visuals to find a 32-bit ARGB visual.
-    for pscreen in &formats.screens {
-        for pdepth in &pscreen.depths {
-            if pdepth.depth != 32 {
-                continue;
-            }
-            for pvisual in &pdepth.visuals {
-                // Verify the visual belongs to one of the screen's allowed depths.
-                for sdepth in &screen.allowed_depths {
-                    if sdepth.depth != 32 {
-                        continue;
-                    }
-                    for v in &sdepth.visuals {
-                        if v.visual_id == pvisual.visual {
-                            return Ok((pvisual.visual, 32, pvisual.format));
-                        }
-                    }
-                }
-            }
-        }
-    }
-    Err(CaptureError::X11(
-        "no 32-bit ARGB visual found".to_string(),
-    ))
+pub fn list_monitors() -> Result<Vec<Monitor>, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let root = conn.setup().roots[screen_num].root;
+    monitors(&conn, root)
 }
 
-/// Find the XRender Pictformat that matches a given visual.
-fn find_pictformat_for_visual(
-    conn: &RustConnection,
-    visual: Visualid,
-) -> Result<Pictformat, CaptureError> {
-    let formats = render::query_pict_formats(conn)
-        .map_err(|e| CaptureError::X11(format!("query_pict_formats: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("query_pict_formats reply: {e}")))?;
+fn monitors(conn: &impl Connection, root: Window) -> Result<Vec<Monitor>, CaptureError> {
+    monitor_info::list(conn, root)
+}
 
-    for pscreen in &formats.screens {
-        for pdepth in &pscreen.depths {
-            for pvisual in &pdepth.visuals {
-                if pvisual.visual == visual {
-                    return Ok(pvisual.format);
-                }
-            }
-        }
-    }
-    Err(CaptureError::X11(format!(
-        "no pictformat for visual {visual}"
-    )))
+/// `Xft.dpi` from the root window's resource database, if set.
+pub fn xft_dpi() -> Result<Option<f64>, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let root = conn.setup().roots[screen_num].root;
+    let resources =
+        windows::get_bytes(&conn, root, AtomEnum::RESOURCE_MANAGER.into(), AtomEnum::STRING)?;
+    Ok(String::from_utf8_lossy(&resources)
+        .lines()
+        .find_map(|line| line.strip_prefix("Xft.dpi:"))
+        .and_then(|dpi| dpi.trim().parse().ok())
+        .filter(|&dpi: &f64| dpi > 0.0))
 }
 
-/// Capture a region of the root window into a server-side Pixmap.
-///
-/// When `bounds` is `None`, captures the entire root window.
-/// When `bounds` is `Some(region)`, captures only that region.
-///
-/// Uses `get_image` (reads through the compositor) + chunked `put_image`
-/// because `copy_area` from the root window on composited desktops returns
-/// blank/black content (the compositor owns the rendered pixels).
-fn capture_screen_to_pixmap(
-    conn: &RustConnection,
-    screen: &Screen,
-    bounds: Option<Region>,
-) -> Result<u32, CaptureError> {
-    let root = screen.root;
-    let depth = screen.root_depth;
-
-    let (src_x, src_y, w, h) = match bounds {
-        Some(b) => (b.x as i16, b.y as i16, b.width as u16, b.height as u16),
-        None => (0i16, 0i16, screen.width_in_pixels, screen.height_in_pixels),
-    };
+pub fn list_windows() -> Result<Vec<WindowInfo>, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let root = conn.setup().roots[screen_num].root;
+
+    let client_list = windows::intern_atom(&conn, b"_NET_CLIENT_LIST_STACKING")?;
+    let net_wm_name = windows::intern_atom(&conn, b"_NET_WM_NAME")?;
+    let net_wm_pid = windows::intern_atom(&conn, b"_NET_WM_PID")?;
+    let net_wm_desktop = windows::intern_atom(&conn, b"_NET_WM_DESKTOP")?;
+    let utf8_string = windows::intern_atom(&conn, b"UTF8_STRING")?;
+
+    let ids = windows::get_u32s(&conn, root, client_list, AtomEnum::WINDOW)?;
+    let mut result = Vec::with_capacity(ids.len());
+    for id in ids {
+        // Windows can disappear between listing and querying them; skip
+        // any that go partway through.
+        let info = || -> Result<WindowInfo, CaptureError> {
+            let geometry = windows::window_rect(&conn, root, id)?;
+
+            let wm_class =
+                windows::get_bytes(&conn, id, AtomEnum::WM_CLASS.into(), AtomEnum::STRING)?;
+            let mut parts = wm_class
+                .split(|&b| b == 0)
+                .map(|p| String::from_utf8_lossy(p).into_owned());
+            let instance = parts.next().unwrap_or_default();
+            let class = parts.next().unwrap_or_default();
+
+            let mut title = windows::get_bytes(&conn, id, net_wm_name, utf8_string)?;
+            if title.is_empty() {
+                title = windows::get_bytes(&conn, id, AtomEnum::WM_NAME.into(), AtomEnum::ANY)?;
+            }
 
-    // Read composited screen content via get_image.
-    let img = conn
-        .get_image(ImageFormat::Z_PIXMAP, root, src_x, src_y, w, h, !0)
-        .map_err(|e| CaptureError::X11(format!("get_image root: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("get_image root reply: {e}")))?;
-
-    let pixmap = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    conn.create_pixmap(depth, pixmap, root, w, h)
-        .map_err(|e| CaptureError::X11(format!("create_pixmap: {e}")))?;
-
-    let gc = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    conn.create_gc(gc, pixmap, &CreateGCAux::default())
-        .map_err(|e| CaptureError::X11(format!("create_gc: {e}")))?;
-
-    // Upload pixel data in chunks (X11 has a max request size).
-    let bytes_per_pixel = 4u32; // Z_PIXMAP at 24/32-bit depth
-    let row_bytes = w as u32 * bytes_per_pixel;
-    let max_req = conn.maximum_request_bytes();
-    // Reserve some bytes for the PutImage header (~28 bytes).
-    let max_data = (max_req - 32) as u32;
-    let rows_per_chunk = (max_data / row_bytes).max(1).min(h as u32);
-
-    let data = &img.data;
-    let mut y_offset: u16 = 0;
-    while y_offset < h {
-        let remaining = h - y_offset;
-        let chunk_h = remaining.min(rows_per_chunk as u16);
-        let start = (y_offset as u32 * row_bytes) as usize;
-        let end = start + (chunk_h as u32 * row_bytes) as usize;
-        conn.put_image(
-            ImageFormat::Z_PIXMAP,
-            pixmap,
-            gc,
-            w,
-            chunk_h,
-            0,
-            y_offset as i16,
-            0,
-            depth,
-            &data[start..end],
-        )
-        .map_err(|e| CaptureError::X11(format!("put_image: {e}")))?;
-        y_offset += chunk_h;
+            let pid = windows::get_u32s(&conn, id, net_wm_pid, AtomEnum::CARDINAL)?
+                .first()
+                .copied();
+            // 0xFFFFFFFF means "on all desktops".
+            let desktop = windows::get_u32s(&conn, id, net_wm_desktop, AtomEnum::CARDINAL)?
+                .first()
+                .copied()
+                .filter(|&d| d != u32::MAX);
+
+            Ok(WindowInfo {
+                id,
+                instance,
+                class,
+                title: String::from_utf8_lossy(&title).into_owned(),
+                pid,
+                desktop,
+                geometry,
+            })
+        };
+        if let Ok(info) = info() {
+            result.push(info);
+        }
     }
 
-    conn.free_gc(gc)
-        .map_err(|e| CaptureError::X11(format!("free_gc: {e}")))?;
-    conn.flush()
-        .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
-
-    Ok(pixmap)
+    Ok(result)
 }
 
-/// Compute normalised selection rectangle from drag start/current positions.
-fn compute_selection(x0: i16, y0: i16, x1: i16, y1: i16, sw: u16, sh: u16) -> (i16, i16, u16, u16) {
-    let lx = x0.min(x1).max(0);
-    let ly = y0.min(y1).max(0);
-    let rx = x0.max(x1).min(sw as i16);
-    let ry = y0.max(y1).min(sh as i16);
-    let w = (rx - lx).max(0) as u16;
-    let h = (ry - ly).max(0) as u16;
-    (lx, ly, w, h)
+fn connect() -> Result<(RustConnection, usize), CaptureError> {
+    x11rb::connect(None).map_err(|e| CaptureError::X11(format!("failed to connect: {e}")))
 }
 
-/// Draw the overlay with double buffering: render to back_picture (off-screen),
-/// then copy_area the finished frame to the window in one shot.
-fn draw_overlay(
-    conn: &RustConnection,
-    window: u32,
-    back_pixmap: u32,
-    back_picture: u32,
-    flip_gc: u32,
-    screen_picture: u32,
-    dim_picture: u32,
-    sw: u16,
-    sh: u16,
-    sel: Option<(i16, i16, u16, u16)>,
-) -> Result<(), CaptureError> {
-    // 1) Composite full screenshot onto back buffer
-    render::composite(
-        conn,
-        render::PictOp::SRC,
-        screen_picture,
-        0u32,
-        back_picture,
-        0, 0,
-        0, 0,
-        0, 0,
-        sw, sh,
-    )
-    .map_err(|e| CaptureError::X11(format!("composite screenshot: {e}")))?;
-
-    // 2) Dim the entire back buffer (50% black over everything)
-    render::composite(
-        conn,
-        render::PictOp::OVER,
-        dim_picture,
-        0u32,
-        back_picture,
-        0, 0,
-        0, 0,
-        0, 0,
-        sw, sh,
-    )
-    .map_err(|e| CaptureError::X11(format!("composite dim: {e}")))?;
-
-    if let Some((sx, sy, sw_sel, sh_sel)) = sel {
-        if sw_sel > 0 && sh_sel > 0 {
-            // 3) Cut out: composite original screenshot over the selected region
-            render::composite(
-                conn,
-                render::PictOp::SRC,
-                screen_picture,
-                0u32,
-                back_picture,
-                sx, sy,
-                0, 0,
-                sx, sy,
-                sw_sel, sh_sel,
-            )
-            .map_err(|e| CaptureError::X11(format!("composite cutout: {e}")))?;
-
-            // 4) White border (2px)
-            let bw: i16 = 2;
-            let border_rects = [
-                // top
-                Rectangle {
-                    x: (sx - bw).max(0),
-                    y: (sy - bw).max(0),
-                    width: sw_sel + (2 * bw) as u16,
-                    height: bw as u16,
-                },
-                // bottom
-                Rectangle {
-                    x: (sx - bw).max(0),
-                    y: sy + sh_sel as i16,
-                    width: sw_sel + (2 * bw) as u16,
-                    height: bw as u16,
-                },
-                // left
-                Rectangle {
-                    x: (sx - bw).max(0),
-                    y: sy,
-                    width: bw as u16,
-                    height: sh_sel,
-                },
-                // right
-                Rectangle {
-                    x: sx + sw_sel as i16,
-                    y: sy,
-                    width: bw as u16,
-                    height: sh_sel,
-                },
-            ];
-            render::fill_rectangles(
-                conn,
-                render::PictOp::OVER,
-                back_picture,
-                render::Color {
-                    red: 0xffff,
-                    green: 0xffff,
-                    blue: 0xffff,
-                    alpha: 0xffff,
-                },
-                &border_rects,
-            )
-            .map_err(|e| CaptureError::X11(format!("fill_rectangles border: {e}")))?;
-        }
-    }
+/// Captured pixels plus the root-window position of their top-left corner.
+type Captured = (RgbaImage, (i32, i32));
+
+fn capture_fullscreen() -> Result<Captured, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let screen = &conn.setup().roots[screen_num];
+    let width = screen.width_in_pixels;
+    let height = screen.height_in_pixels;
 
-    // 5) Flip: copy_area from back buffer pixmap to window (single
-    //    core-protocol blit — no intermediate states visible)
-    conn.copy_area(back_pixmap, window, flip_gc, 0, 0, 0, 0, sw, sh)
-        .map_err(|e| CaptureError::X11(format!("copy_area flip: {e}")))?;
+    let image = capture_window_region(&conn, screen, 0, 0, width, height)?;
+    Ok((image, (0, 0)))
+}
 
-    conn.flush()
-        .map_err(|e| CaptureError::X11(format!("flush draw: {e}")))?;
+fn capture_region(region: Region) -> Result<Captured, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let screen = &conn.setup().roots[screen_num];
 
-    Ok(())
+    let image = capture_window_region(
+        &conn,
+        screen,
+        region.x as i16,
+        region.y as i16,
+        region.width as u16,
+        region.height as u16,
+    )?;
+    Ok((image, (region.x, region.y)))
 }
 
-/// Extract a region from a server-side Pixmap as an RgbaImage.
-fn extract_region_from_pixmap(
-    conn: &RustConnection,
-    pixmap: u32,
+/// Read a Z_PIXMAP image of `drawable` (laid out as `format`) as RGBA,
+/// through MIT-SHM when the server supports it and with a plain `get_image`
+/// otherwise (remote displays, servers without the extension, or if the SHM
+/// request fails).
+fn get_image(
+    conn: &impl Connection,
+    drawable: u32,
+    format: &PixelFormat,
     x: i16,
     y: i16,
     width: u16,
     height: u16,
 ) -> Result<RgbaImage, CaptureError> {
-    let reply = conn
-        .get_image(ImageFormat::Z_PIXMAP, pixmap, x, y, width, height, !0)
-        .map_err(|e| CaptureError::X11(format!("get_image from pixmap: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("get_image pixmap reply: {e}")))?;
-
-    let mut data = reply.data;
-    // X11 returns BGRX for 24-bit pixmaps — convert to RGBA with opaque alpha.
-    for chunk in data.chunks_exact_mut(4) {
-        chunk.swap(0, 2);
-        chunk[3] = 255;
+    if let Some(seg) = shm::ShmSegment::new(conn, format.image_size(width, height))
+        && let Ok(data) = seg.get_image(drawable, x, y, width, height)
+    {
+        return format.to_rgba(data, width, height);
     }
 
-    RgbaImage::from_raw(width as u32, height as u32, data)
-        .ok_or_else(|| CaptureError::X11("failed to create image from pixmap data".to_string()))
+    let reply = conn
+        .get_image(ImageFormat::Z_PIXMAP, drawable, x, y, width, height, !0)
+        .map_err(|e| CaptureError::X11(format!("get_image failed: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("get_image reply failed: {e}")))?;
+    format.to_rgba(&reply.data, width, height)
 }
 
-fn capture_region_interactive(display_bounds: Option<Region>) -> Result<RgbaImage, CaptureError> {
+/// Click-to-pick window selector: highlights the client window under the
+/// pointer on the frozen screen and captures it on left click.
+fn capture_window_interactive(
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Captured, CaptureError> {
     let (conn, screen_num) = connect()?;
     let screen = &conn.setup().roots[screen_num].clone();
 
-    // When display_bounds is set, constrain the overlay to that display.
-    // ox/oy are the overlay origin in root coordinates; sw/sh are the overlay dimensions.
-    let (ox, oy, sw, sh) = match display_bounds {
-        Some(b) => (b.x as i16, b.y as i16, b.width as u16, b.height as u16),
-        None => (0i16, 0i16, screen.width_in_pixels, screen.height_in_pixels),
-    };
-
-    // ---- XRender init ----
-    render::query_version(&conn, 0, 11)
-        .map_err(|e| CaptureError::X11(format!("render query_version: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("render query_version reply: {e}")))?;
-
-    let root_pictformat = find_pictformat_for_visual(&conn, screen.root_visual)?;
-    // Find a 32-bit ARGB pictformat for solid-fill sources (needed for alpha blending).
-    let argb_format = find_argb_visual_and_format(&conn, screen)
-        .map(|(_, _, fmt)| fmt)?;
-
-    // ---- Capture screen (only the target display if constrained) ----
-    let screen_pixmap = capture_screen_to_pixmap(&conn, screen, display_bounds)?;
-
-    let screen_picture = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    render::create_picture(
-        &conn,
-        screen_picture,
-        screen_pixmap,
-        root_pictformat,
-        &render::CreatePictureAux::new(),
-    )
-    .map_err(|e| CaptureError::X11(format!("create_picture screen: {e}")))?;
-
-    // ---- Create overlay window at root depth (avoids alpha/compositor issues) ----
-    let window = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    let win_aux = CreateWindowAux::new()
-        .override_redirect(1)
-        .background_pixel(screen.black_pixel)
-        .border_pixel(0)
-        .event_mask(
-            EventMask::EXPOSURE
-                | EventMask::BUTTON_PRESS
-                | EventMask::BUTTON_RELEASE
-                | EventMask::POINTER_MOTION
-                | EventMask::KEY_PRESS,
-        );
-    conn.create_window(
-        screen.root_depth,
-        window,
-        screen.root,
-        ox,
-        oy,
-        sw,
-        sh,
-        0,
-        WindowClass::INPUT_OUTPUT,
-        screen.root_visual,
-        &win_aux,
-    )
-    .map_err(|e| CaptureError::X11(format!("create_window: {e}")))?;
-
-    conn.map_window(window)
-        .map_err(|e| CaptureError::X11(format!("map_window: {e}")))?;
-    conn.flush()
-        .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
-
-    // ---- Back buffer (off-screen pixmap + XRender picture) ----
-    let back_pixmap = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    conn.create_pixmap(screen.root_depth, back_pixmap, screen.root, sw, sh)
-        .map_err(|e| CaptureError::X11(format!("create_pixmap back: {e}")))?;
-    let back_picture = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    render::create_picture(
-        &conn,
-        back_picture,
-        back_pixmap,
-        root_pictformat,
-        &render::CreatePictureAux::new(),
-    )
-    .map_err(|e| CaptureError::X11(format!("create_picture back: {e}")))?;
-
-    // GC for copy_area flip from back buffer to window
-    let flip_gc = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    conn.create_gc(flip_gc, window, &CreateGCAux::default())
-        .map_err(|e| CaptureError::X11(format!("create_gc flip: {e}")))?;
-
-    // ---- Solid-fill sources (32-bit ARGB for alpha blending) ----
-    // These need a drawable compatible with 32-bit depth, use screen.root as parent.
-    let dim_pixmap = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    conn.create_pixmap(32, dim_pixmap, screen.root, 1, 1)
-        .map_err(|e| CaptureError::X11(format!("create_pixmap dim: {e}")))?;
-    let dim_picture = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    render::create_picture(
-        &conn,
-        dim_picture,
-        dim_pixmap,
-        argb_format,
-        &render::CreatePictureAux::new().repeat(render::Repeat::NORMAL),
-    )
-    .map_err(|e| CaptureError::X11(format!("create_picture dim: {e}")))?;
-    render::fill_rectangles(
-        &conn,
-        render::PictOp::SRC,
-        dim_picture,
-        render::Color { red: 0, green: 0, blue: 0, alpha: 0x8000 },
-        &[Rectangle { x: 0, y: 0, width: 1, height: 1 }],
-    )
-    .map_err(|e| CaptureError::X11(format!("fill dim: {e}")))?;
-
-    let border_pixmap = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    conn.create_pixmap(32, border_pixmap, screen.root, 1, 1)
-        .map_err(|e| CaptureError::X11(format!("create_pixmap border: {e}")))?;
-    let border_picture = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    render::create_picture(
-        &conn,
-        border_picture,
-        border_pixmap,
-        argb_format,
-        &render::CreatePictureAux::new().repeat(render::Repeat::NORMAL),
-    )
-    .map_err(|e| CaptureError::X11(format!("create_picture border: {e}")))?;
-    render::fill_rectangles(
-        &conn,
-        render::PictOp::SRC,
-        border_picture,
-        render::Color { red: 0xffff, green: 0xffff, blue: 0xffff, alpha: 0xffff },
-        &[Rectangle { x: 0, y: 0, width: 1, height: 1 }],
-    )
-    .map_err(|e| CaptureError::X11(format!("fill border: {e}")))?;
-
-    // ---- Crosshair cursor ----
-    let cursor_font = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    conn.open_font(cursor_font, b"cursor")
-        .map_err(|e| CaptureError::X11(format!("open_font cursor: {e}")))?;
-    let cursor = conn
-        .generate_id()
-        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
-    // Glyph 34 = crosshair in the cursor font, 35 = its mask
-    conn.create_glyph_cursor(
-        cursor,
-        cursor_font,
-        cursor_font,
-        34,
-        35,
-        0xffff, 0xffff, 0xffff, // foreground: white
-        0, 0, 0,                 // background: black
-    )
-    .map_err(|e| CaptureError::X11(format!("create_glyph_cursor: {e}")))?;
-
-    // Set the crosshair cursor on the overlay window itself, so it only
-    // appears when the mouse is over this window.
-    conn.change_window_attributes(window, &ChangeWindowAttributesAux::new().cursor(cursor))
-        .map_err(|e| CaptureError::X11(format!("set cursor on window: {e}")))?;
-
-    // ---- Grab pointer and keyboard ----
-    // When targeting a single display, skip grabs entirely so the user can
-    // interact normally on other monitors.  The overlay window's event mask
-    // delivers press/release/motion/key events when the pointer is over it.
-    let grabbed = display_bounds.is_none();
-    if grabbed {
-        conn.grab_pointer(
-            true,
-            window,
-            (EventMask::BUTTON_PRESS
-                | EventMask::BUTTON_RELEASE
-                | EventMask::POINTER_MOTION)
-                .into(),
-            GrabMode::ASYNC,
-            GrabMode::ASYNC,
-            window,
-            cursor,
-            Time::CURRENT_TIME,
-        )
-        .map_err(|e| CaptureError::X11(format!("grab_pointer: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("grab_pointer reply: {e}")))?;
-
-        conn.grab_keyboard(true, window, Time::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)
-            .map_err(|e| CaptureError::X11(format!("grab_keyboard: {e}")))?
-            .reply()
-            .map_err(|e| CaptureError::X11(format!("grab_keyboard reply: {e}")))?;
-    }
-
-    let resources = OverlayResources {
-        conn: &conn,
-        window,
-        screen_picture,
-        back_pixmap,
-        back_picture,
-        flip_gc,
-        dim_picture,
-        dim_pixmap,
-        border_picture,
-        border_pixmap,
-        cursor,
-        cursor_font,
-        grabbed,
-    };
-
-    // ---- Initial draw (fully dimmed) ----
-    draw_overlay(
-        &conn,
-        window,
-        back_pixmap,
-        back_picture,
-        flip_gc,
-        screen_picture,
-        dim_picture,
-        sw,
-        sh,
-        None,
-    )?;
-
-    // ---- Event loop ----
-    let mut drag_start: Option<(i16, i16)> = None;
-    let mut current_pos: (i16, i16) = (0, 0);
+    // Snapshot the stacking order before the overlay covers everything.
+    let toplevels = windows::visible_toplevels(&conn, screen.root, opts)?;
 
-    const ESCAPE_KEYCODE: u8 = 9;
+    let mut overlay = Overlay::new(&conn, screen, display_bounds)?;
+    let mut hovered: Option<Window> = None;
 
     loop {
-        let event = conn
-            .wait_for_event()
-            .map_err(|e| CaptureError::X11(format!("wait_for_event: {e}")))?;
-
-        match event {
-            x11rb::protocol::Event::Expose(_) => {
-                let sel = drag_start.map(|(sx, sy)| compute_selection(sx, sy, current_pos.0, current_pos.1, sw, sh));
-                draw_overlay(
-                    &conn,
-                    window,
-                    back_pixmap,
-                    back_picture,
-                    flip_gc,
-                    screen_picture,
-                    dim_picture,
-                    sw,
-                    sh,
-                    sel,
-                )?;
+        match overlay.next_event()? {
+            Event::Expose(_) => {
+                let sel = hovered
+                    .and_then(|id| toplevels.iter().find(|w| w.id == id))
+                    .and_then(|w| overlay.clip(w.rect));
+                draw_overlay(&overlay, sel, Extras::default())?;
             }
-            x11rb::protocol::Event::ButtonPress(ev) => {
-                if ev.detail == 1 {
-                    // Left mouse button
-                    drag_start = Some((ev.event_x, ev.event_y));
-                    current_pos = (ev.event_x, ev.event_y);
+            Event::MotionNotify(ev) => {
+                let hit = windows::toplevel_at(&toplevels, ev.root_x as i32, ev.root_y as i32);
+                if hit.map(|w| w.id) != hovered {
+                    hovered = hit.map(|w| w.id);
+                    draw_overlay(&overlay, hit.and_then(|w| overlay.clip(w.rect)), Extras::default())?;
                 }
             }
-            x11rb::protocol::Event::MotionNotify(ev) => {
-                if drag_start.is_some() {
-                    current_pos = (ev.event_x, ev.event_y);
-
-                    // Coalesce pending motion events
-                    while let Some(queued) = conn
-                        .poll_for_event()
-                        .map_err(|e| CaptureError::X11(format!("poll_for_event: {e}")))?
-                    {
-                        match queued {
-                            x11rb::protocol::Event::MotionNotify(me) => {
-                                current_pos = (me.event_x, me.event_y);
-                            }
-                            other => {
-                                // We ate a non-motion event; need to handle it.
-                                // Re-process by storing and breaking out.
-                                // Since x11rb doesn't have "put back", handle inline:
-                                match other {
-                                    x11rb::protocol::Event::ButtonRelease(ev) if ev.detail == 1 => {
-                                        if let Some((sx, sy)) = drag_start {
-                                            let (rx, ry, rw, rh) =
-                                                compute_selection(sx, sy, current_pos.0, current_pos.1, sw, sh);
-                                            if rw > 0 && rh > 0 {
-                                                let img = extract_region_from_pixmap(
-                                                    &conn, screen_pixmap, rx, ry, rw, rh,
-                                                )?;
-                                                drop(resources);
-                                                conn.free_pixmap(screen_pixmap)
-                                                    .map_err(|e| CaptureError::X11(format!("free pixmap: {e}")))?;
-                                                conn.flush()
-                                                    .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
-                                                return Ok(img);
-                                            }
-                                        }
-                                        drag_start = None;
-                                    }
-                                    x11rb::protocol::Event::KeyPress(ev) if ev.detail == ESCAPE_KEYCODE => {
-                                        drop(resources);
-                                        let _ = conn.free_pixmap(screen_pixmap);
-                                        let _ = conn.flush();
-                                        return Err(CaptureError::SelectionCancelled);
-                                    }
-                                    _ => {}
-                                }
-                                break;
-                            }
-                        }
-                    }
-
-                    let (sx, sy) = drag_start.unwrap();
-                    let sel = compute_selection(sx, sy, current_pos.0, current_pos.1, sw, sh);
-                    draw_overlay(
-                        &conn,
-                        window,
-                        back_pixmap,
-                        back_picture,
-                        flip_gc,
-                        screen_picture,
-                        dim_picture,
-                        sw,
-                        sh,
-                        Some(sel),
-                    )?;
+            Event::ButtonPress(ev) if ev.detail == 1 => {
+                let hit = windows::toplevel_at(&toplevels, ev.root_x as i32, ev.root_y as i32);
+                // Prefer the window's own pixmap so overlapping windows don't leak in.
+                if let Some(w) = hit
+                    && let Some(captured) =
+                        composite::capture_window(&conn, screen_num, screen.root, w.id, w.rect)?
+                {
+                    return Ok(captured);
                 }
-            }
-            x11rb::protocol::Event::ButtonRelease(ev) => {
-                if ev.detail == 1 {
-                    if let Some((sx, sy)) = drag_start {
-                        let (rx, ry, rw, rh) =
-                            compute_selection(sx, sy, ev.event_x, ev.event_y, sw, sh);
-                        if rw > 0 && rh > 0 {
-                            let img = extract_region_from_pixmap(
-                                &conn, screen_pixmap, rx, ry, rw, rh,
-                            )?;
-                            drop(resources);
-                            conn.free_pixmap(screen_pixmap)
-                                .map_err(|e| CaptureError::X11(format!("free pixmap: {e}")))?;
-                            conn.flush()
-                                .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
-                            return Ok(img);
-                        }
-                    }
-                    drag_start = None;
+                if let Some((x, y, w, h)) = hit.and_then(|w| overlay.clip(w.rect)) {
+                    let img = overlay.extract(x, y, w, h)?;
+                    return Ok((img, (overlay.ox as i32 + x as i32, overlay.oy as i32 + y as i32)));
                 }
             }
-            x11rb::protocol::Event::KeyPress(ev) => {
-                if ev.detail == ESCAPE_KEYCODE {
-                    drop(resources);
-                    let _ = conn.free_pixmap(screen_pixmap);
-                    let _ = conn.flush();
-                    return Err(CaptureError::SelectionCancelled);
-                }
+            Event::KeyPress(ev) if ev.detail == ESCAPE_KEYCODE => {
+                return Err(CaptureError::SelectionCancelled);
             }
             _ => {}
         }
     }
 }
 
-fn capture_active_window() -> Result<RgbaImage, CaptureError> {
+fn capture_active_window(opts: &CaptureOptions) -> Result<Captured, CaptureError> {
     let (conn, screen_num) = connect()?;
-    let screen = &conn.setup().roots[screen_num];
+    let root = conn.setup().roots[screen_num].root;
+    let window_id = active_window(&conn, root)?;
+    capture_client_window(&conn, screen_num, window_id, opts)
+}
 
-    // Get _NET_ACTIVE_WINDOW
-    let active_atom = conn
-        .intern_atom(false, b"_NET_ACTIVE_WINDOW")
-        .map_err(|e| CaptureError::X11(format!("intern_atom failed: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("intern_atom reply failed: {e}")))?
-        .atom;
+/// The window in `_NET_ACTIVE_WINDOW`.
+fn active_window(conn: &impl Connection, root: Window) -> Result<Window, CaptureError> {
+    let active_atom = windows::intern_atom(conn, b"_NET_ACTIVE_WINDOW")?;
 
     let reply = conn
-        .get_property(false, screen.root, active_atom, AtomEnum::WINDOW, 0, 1)
+        .get_property(false, root, active_atom, AtomEnum::WINDOW, 0, 1)
         .map_err(|e| CaptureError::X11(format!("get_property failed: {e}")))?
         .reply()
         .map_err(|e| CaptureError::X11(format!("get_property reply failed: {e}")))?;
@@ -807,54 +342,89 @@ fn capture_active_window() -> Result<RgbaImage, CaptureError> {
     if window_id == 0 {
         return Err(CaptureError::X11("no active window found".to_string()));
     }
+    Ok(window_id)
+}
 
-    // Get window geometry (including decorations via translate)
-    let geo = conn
-        .get_geometry(window_id)
-        .map_err(|e| CaptureError::X11(format!("get_geometry failed: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("get_geometry reply failed: {e}")))?;
+/// Geometry of the active window in root coordinates.
+pub fn active_window_rect() -> Result<Region, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let root = conn.setup().roots[screen_num].root;
+    let window = active_window(&conn, root)?;
+    windows::window_rect(&conn, root, window)
+}
 
-    // Translate coordinates to root window
-    let translated = conn
-        .translate_coordinates(window_id, screen.root, 0, 0)
-        .map_err(|e| CaptureError::X11(format!("translate_coordinates failed: {e}")))?
+/// Current pointer position in root coordinates.
+pub fn pointer_position() -> Result<(i32, i32), CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let root = conn.setup().roots[screen_num].root;
+    let pointer = conn
+        .query_pointer(root)
+        .map_err(|e| CaptureError::X11(format!("query_pointer: {e}")))?
         .reply()
-        .map_err(|e| CaptureError::X11(format!("translate reply failed: {e}")))?;
+        .map_err(|e| CaptureError::X11(format!("query_pointer reply: {e}")))?;
+    Ok((pointer.root_x as i32, pointer.root_y as i32))
+}
 
-    capture_window_region(
-        &conn,
-        screen.root,
-        translated.dst_x,
-        translated.dst_y,
-        geo.width,
-        geo.height,
-    )
+/// Capture the window matching `selector` without changing focus.
+fn capture_selected_window(
+    selector: &WindowSelector,
+    opts: &CaptureOptions,
+) -> Result<Captured, CaptureError> {
+    let windows = list_windows()?;
+    let window = super::find_window(&windows, selector)?;
+    let (conn, screen_num) = connect()?;
+    capture_client_window(&conn, screen_num, window.id, opts)
+}
+
+/// Capture a client window: from its Composite pixmap when possible,
+/// otherwise by reading its rectangle off the root window.
+fn capture_client_window(
+    conn: &RustConnection,
+    screen_num: usize,
+    window_id: Window,
+    opts: &CaptureOptions,
+) -> Result<Captured, CaptureError> {
+    let screen = &conn.setup().roots[screen_num];
+
+    let rect = windows::capture_rect(conn, screen.root, window_id, opts)?;
+    if let Some(captured) = composite::capture_window(conn, screen_num, screen.root, window_id, rect)? {
+        return Ok(captured);
+    }
+
+    // Frames of maximized or half-offscreen windows can extend past the root,
+    // which get_image rejects.
+    let rect = clip_to_screen(rect, screen)
+        .ok_or_else(|| CaptureError::X11("window is off-screen".to_string()))?;
+    let image = capture_window_region(
+        conn,
+        screen,
+        rect.x as i16,
+        rect.y as i16,
+        rect.width as u16,
+        rect.height as u16,
+    )?;
+    Ok((image, (rect.x, rect.y)))
+}
+
+/// Intersect a root-space rectangle with the screen.
+fn clip_to_screen(r: Region, screen: &Screen) -> Option<Region> {
+    r.intersect(&Region {
+        x: 0,
+        y: 0,
+        width: screen.width_in_pixels as u32,
+        height: screen.height_in_pixels as u32,
+    })
 }
 
+/// Read part of the root window.
 fn capture_window_region(
     conn: &impl Connection,
-    window: u32,
+    screen: &Screen,
     x: i16,
     y: i16,
     width: u16,
     height: u16,
 ) -> Result<RgbaImage, CaptureError> {
-    let reply = conn
-        .get_image(ImageFormat::Z_PIXMAP, window, x, y, width, height, !0)
-        .map_err(|e| CaptureError::X11(format!("get_image failed: {e}")))?
-        .reply()
-        .map_err(|e| CaptureError::X11(format!("get_image reply failed: {e}")))?;
-
-    let mut data = reply.data;
-
-    // X11 returns BGRX for 24-bit windows (4th byte is padding, not alpha).
-    // Convert to RGBA and set alpha to fully opaque.
-    for chunk in data.chunks_exact_mut(4) {
-        chunk.swap(0, 2); // BGR_ → RGB_
-        chunk[3] = 255;   // set alpha to opaque
-    }
-
-    RgbaImage::from_raw(width as u32, height as u32, data)
-        .ok_or_else(|| CaptureError::X11("failed to create image from pixel data".to_string()))
+    let format = PixelFormat::root(conn, screen)?;
+    get_image(conn, screen.root, &format, x, y, width, height)
 }
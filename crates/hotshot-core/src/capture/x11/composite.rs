@@ -0,0 +1,105 @@
+//! Window capture from the Composite backing pixmap.
+//!
+//! Reading a window through the root picks up whatever overlaps it and
+//! loses anything off-screen. When a compositing manager is running, every
+//! top-level is redirected into its own pixmap, which `NameWindowPixmap`
+//! hands to us with the window's full, unobscured contents (and its real
+//! alpha channel for ARGB windows).
+
+use super::pixels::PixelFormat;
+use super::windows::toplevel_ancestor;
+use super::{CaptureError, Captured, Region};
+use x11rb::connection::Connection;
+use x11rb::protocol::composite::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::*;
+
+/// Whether Composite >= 0.2 is available and a compositing manager owns
+/// `_NET_WM_CM_S<screen>`, i.e. top-levels are backed by their own pixmaps.
+///
+/// Without a compositing manager windows aren't redirected, and redirecting
+/// one ourselves only yields content the application redraws later.
+fn usable(conn: &impl Connection, screen_num: usize) -> bool {
+    if !matches!(conn.extension_information(composite::X11_EXTENSION_NAME), Ok(Some(_))) {
+        return false;
+    }
+    match conn.composite_query_version(0, 4).ok().and_then(|c| c.reply().ok()) {
+        Some(v) if (v.major_version, v.minor_version) >= (0, 2) => {}
+        _ => return false,
+    }
+    let name = format!("_NET_WM_CM_S{screen_num}");
+    let Ok(atom) = super::windows::intern_atom(conn, name.as_bytes()) else {
+        return false;
+    };
+    match conn.get_selection_owner(atom).ok().and_then(|c| c.reply().ok()) {
+        Some(reply) => reply.owner != x11rb::NONE,
+        None => false,
+    }
+}
+
+/// Capture `rect` (root coordinates) of the top-level containing `client`
+/// from its backing pixmap.
+///
+/// Returns `Ok(None)` when Composite can't be used for this window (no
+/// extension, no compositing manager, or the window isn't redirected, e.g.
+/// an unredirected fullscreen game); callers fall back to the root path.
+/// On success, also returns the root position of the image's top-left corner.
+pub(super) fn capture_window(
+    conn: &impl Connection,
+    screen_num: usize,
+    root: Window,
+    client: Window,
+    rect: Region,
+) -> Result<Option<Captured>, CaptureError> {
+    if !usable(conn, screen_num) {
+        return Ok(None);
+    }
+
+    // Only redirected windows (the frames, for a reparenting WM) have a
+    // pixmap; it covers the whole window including its border.
+    let frame = toplevel_ancestor(conn, root, client)?;
+    let geo = conn
+        .get_geometry(frame)
+        .map_err(|e| CaptureError::X11(format!("get_geometry frame: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("get_geometry frame reply: {e}")))?;
+    let attrs = conn
+        .get_window_attributes(frame)
+        .map_err(|e| CaptureError::X11(format!("get_window_attributes frame: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("get_window_attributes frame reply: {e}")))?;
+    let bw = geo.border_width as i32;
+    let pix_w = geo.width as i32 + 2 * bw;
+    let pix_h = geo.height as i32 + 2 * bw;
+
+    // Crop rectangle in pixmap coordinates, clipped to the pixmap.
+    let lx = (rect.x - geo.x as i32).max(0);
+    let ly = (rect.y - geo.y as i32).max(0);
+    let rx = (rect.x - geo.x as i32 + rect.width as i32).min(pix_w);
+    let ry = (rect.y - geo.y as i32 + rect.height as i32).min(pix_h);
+    if rx <= lx || ry <= ly {
+        return Ok(None);
+    }
+    let (w, h) = ((rx - lx) as u16, (ry - ly) as u16);
+
+    let pixmap = conn
+        .generate_id()
+        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+    let named = conn
+        .composite_name_window_pixmap(frame, pixmap)
+        .map_err(|e| CaptureError::X11(format!("name_window_pixmap: {e}")))?
+        .check();
+    if named.is_err() {
+        return Ok(None);
+    }
+
+    // The pixmap shares the frame's visual; ARGB frames keep their alpha.
+    let format = PixelFormat::new(conn, geo.depth, attrs.visual, attrs.colormap)
+        .map(PixelFormat::with_alpha);
+    let image = format.and_then(|format| {
+        super::get_image(conn, pixmap, &format, lx as i16, ly as i16, w, h)
+    });
+    let _ = conn.free_pixmap(pixmap);
+    let image = image?;
+
+    Ok(Some((image, (geo.x as i32 + lx, geo.y as i32 + ly))))
+}
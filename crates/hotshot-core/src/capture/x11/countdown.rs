@@ -0,0 +1,165 @@
+//! On-screen countdown shown while a delayed capture waits.
+//!
+//! A small override-redirect window in the bottom-right corner of the
+//! target area shows the remaining seconds. Digits are drawn as seven-segment
+//! rectangles so we don't depend on which core fonts the server has. The
+//! window is destroyed (and the compositor given time to repaint) before the
+//! capture starts, so it never appears in the image.
+
+use super::{CaptureError, Region, connect};
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+
+const SEG_LEN: u16 = 24;
+const SEG_THICK: u16 = 6;
+const DIGIT_W: u16 = SEG_LEN + 2 * SEG_THICK;
+const DIGIT_H: u16 = 2 * SEG_LEN + 3 * SEG_THICK;
+const DIGIT_GAP: u16 = 10;
+const PADDING: u16 = 16;
+const MARGIN: i16 = 32;
+
+/// How often we wake up to check for the next second / expose events.
+const TICK: Duration = Duration::from_millis(50);
+/// Time for a compositor to repaint the area under the removed window.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Segments a..g (top, top-right, bottom-right, bottom, bottom-left,
+/// top-left, middle) lit for each digit.
+const DIGITS: [u8; 10] = [
+    0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110,
+    0b1101101, 0b1111101, 0b0000111, 0b1111111, 0b1101111,
+];
+
+/// Show a countdown for `delay` over `bounds` (or the whole screen), then
+/// remove it.
+pub(super) fn run(delay: Duration, bounds: Option<Region>) -> Result<(), CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let screen = &conn.setup().roots[screen_num];
+
+    let (bx, by, bw, bh) = match bounds {
+        Some(b) => (b.x as i16, b.y as i16, b.width as u16, b.height as u16),
+        None => (0, 0, screen.width_in_pixels, screen.height_in_pixels),
+    };
+    let max_digits = digits(delay.as_secs_f64().ceil() as u64).len() as u16;
+    let width = 2 * PADDING + max_digits * DIGIT_W + (max_digits - 1) * DIGIT_GAP;
+    let height = 2 * PADDING + DIGIT_H;
+    let x = bx + bw as i16 - width as i16 - MARGIN;
+    let y = by + bh as i16 - height as i16 - MARGIN;
+
+    let window = conn
+        .generate_id()
+        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+    conn.create_window(
+        screen.root_depth,
+        window,
+        screen.root,
+        x,
+        y,
+        width,
+        height,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        screen.root_visual,
+        &CreateWindowAux::new()
+            .override_redirect(1)
+            .background_pixel(screen.black_pixel)
+            .event_mask(EventMask::EXPOSURE),
+    )
+    .map_err(|e| CaptureError::X11(format!("create_window countdown: {e}")))?;
+
+    let gc = conn
+        .generate_id()
+        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+    conn.create_gc(gc, window, &CreateGCAux::new().foreground(screen.white_pixel))
+        .map_err(|e| CaptureError::X11(format!("create_gc countdown: {e}")))?;
+
+    conn.map_window(window)
+        .map_err(|e| CaptureError::X11(format!("map_window countdown: {e}")))?;
+
+    let deadline = Instant::now() + delay;
+    let mut shown: Option<u64> = None;
+    let result = loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break Ok(());
+        }
+        let remaining = (deadline - now).as_secs_f64().ceil() as u64;
+
+        let mut exposed = false;
+        while let Some(event) = conn
+            .poll_for_event()
+            .map_err(|e| CaptureError::X11(format!("poll_for_event: {e}")))?
+        {
+            exposed |= matches!(event, Event::Expose(_));
+        }
+
+        if shown != Some(remaining) || exposed {
+            if let Err(e) = draw(&conn, window, gc, width, height, remaining) {
+                break Err(e);
+            }
+            shown = Some(remaining);
+        }
+        std::thread::sleep(TICK.min(deadline - now));
+    };
+
+    let _ = conn.free_gc(gc);
+    let _ = conn.destroy_window(window);
+    // Round-trip so the server has processed the destroy before we capture.
+    let _ = conn.get_input_focus().map(|c| c.reply());
+    std::thread::sleep(SETTLE);
+    result
+}
+
+fn digits(n: u64) -> Vec<u8> {
+    n.to_string().bytes().map(|b| b - b'0').collect()
+}
+
+fn draw(
+    conn: &impl Connection,
+    window: Window,
+    gc: Gcontext,
+    width: u16,
+    height: u16,
+    value: u64,
+) -> Result<(), CaptureError> {
+    conn.clear_area(false, window, 0, 0, width, height)
+        .map_err(|e| CaptureError::X11(format!("clear_area countdown: {e}")))?;
+
+    // Right-align so the number doesn't jump as it shrinks.
+    let ds = digits(value);
+    let mut rects = Vec::new();
+    let mut dx = width as i16 - PADDING as i16 - DIGIT_W as i16;
+    for &d in ds.iter().rev() {
+        rects.extend(segments(DIGITS[d as usize], dx, PADDING as i16));
+        dx -= (DIGIT_W + DIGIT_GAP) as i16;
+    }
+
+    conn.poly_fill_rectangle(window, gc, &rects)
+        .map_err(|e| CaptureError::X11(format!("poly_fill_rectangle countdown: {e}")))?;
+    conn.flush()
+        .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
+    Ok(())
+}
+
+/// Rectangles for the lit segments of one digit with its top-left at (x, y).
+fn segments(mask: u8, x: i16, y: i16) -> Vec<Rectangle> {
+    let (l, t) = (SEG_LEN as i16, SEG_THICK as i16);
+    let horizontal = |sy: i16| Rectangle { x: x + t, y: y + sy, width: SEG_LEN, height: SEG_THICK };
+    let vertical = |sx: i16, sy: i16| Rectangle { x: x + sx, y: y + sy, width: SEG_THICK, height: SEG_LEN };
+    let all = [
+        horizontal(0),             // a
+        vertical(t + l, t),        // b
+        vertical(t + l, 2 * t + l), // c
+        horizontal(2 * (t + l)),   // d
+        vertical(0, 2 * t + l),    // e
+        vertical(0, t),            // f
+        horizontal(t + l),         // g
+    ];
+    all.into_iter()
+        .enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .map(|(_, r)| r)
+        .collect()
+}
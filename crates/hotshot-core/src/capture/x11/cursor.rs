@@ -0,0 +1,99 @@
+//! Pointer image via XFixes, for drawing the cursor into captures.
+//!
+//! The X server never includes the cursor in `get_image` output (it's a
+//! hardware/software sprite layered on top), so we fetch the current cursor
+//! image and blend it ourselves.
+
+use super::CaptureError;
+use image::RgbaImage;
+use x11rb::connection::Connection;
+use x11rb::protocol::xfixes::{self, ConnectionExt as _};
+
+/// The cursor sprite and where it sits on the root window.
+pub(super) struct CursorImage {
+    /// Pointer (hotspot) position in root coordinates.
+    pub x: i32,
+    pub y: i32,
+    xhot: i32,
+    yhot: i32,
+    width: u32,
+    height: u32,
+    /// Premultiplied ARGB, one `u32` per pixel.
+    pixels: Vec<u32>,
+}
+
+pub(super) fn get_cursor_image(conn: &impl Connection) -> Result<CursorImage, CaptureError> {
+    if !matches!(conn.extension_information(xfixes::X11_EXTENSION_NAME), Ok(Some(_))) {
+        return Err(CaptureError::X11("XFixes extension not available".to_string()));
+    }
+    // XFixes requires the client to announce its version before any other request.
+    conn.xfixes_query_version(4, 0)
+        .map_err(|e| CaptureError::X11(format!("xfixes query_version: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("xfixes query_version reply: {e}")))?;
+
+    let reply = conn
+        .xfixes_get_cursor_image()
+        .map_err(|e| CaptureError::X11(format!("xfixes get_cursor_image: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("xfixes get_cursor_image reply: {e}")))?;
+    // `draw_onto` indexes the pixels by the declared size.
+    if reply.cursor_image.len() < reply.width as usize * reply.height as usize {
+        return Err(CaptureError::X11("xfixes cursor image is shorter than its size".to_string()));
+    }
+
+    Ok(CursorImage {
+        x: reply.x as i32,
+        y: reply.y as i32,
+        xhot: reply.xhot as i32,
+        yhot: reply.yhot as i32,
+        width: reply.width as u32,
+        height: reply.height as u32,
+        pixels: reply.cursor_image,
+    })
+}
+
+impl CursorImage {
+    /// Blend the cursor onto `image`, whose top-left corner is at `origin` in
+    /// root coordinates.
+    ///
+    /// Returns the hotspot in image coordinates, or `None` if the pointer is
+    /// outside the image (in which case nothing is drawn).
+    pub(super) fn draw_onto(&self, image: &mut RgbaImage, origin: (i32, i32)) -> Option<(i32, i32)> {
+        let hx = self.x - origin.0;
+        let hy = self.y - origin.1;
+        if hx < 0 || hy < 0 || hx >= image.width() as i32 || hy >= image.height() as i32 {
+            return None;
+        }
+
+        let left = hx - self.xhot;
+        let top = hy - self.yhot;
+        for cy in 0..self.height {
+            let iy = top + cy as i32;
+            if iy < 0 || iy >= image.height() as i32 {
+                continue;
+            }
+            for cx in 0..self.width {
+                let ix = left + cx as i32;
+                if ix < 0 || ix >= image.width() as i32 {
+                    continue;
+                }
+                let argb = self.pixels[(cy * self.width + cx) as usize];
+                let a = argb >> 24;
+                if a == 0 {
+                    continue;
+                }
+                let src = [(argb >> 16) & 0xff, (argb >> 8) & 0xff, argb & 0xff];
+                let px = image.get_pixel_mut(ix as u32, iy as u32);
+                // Premultiplied "over": dst = src + dst * (1 - a)
+                for (c, s) in src.iter().enumerate() {
+                    let d = px.0[c] as u32;
+                    px.0[c] = (s + d * (255 - a) / 255).min(255) as u8;
+                }
+                px.0[3] = (a + px.0[3] as u32 * (255 - a) / 255).min(255) as u8;
+            }
+        }
+
+        Some((hx, hy))
+    }
+}
@@ -0,0 +1,130 @@
+//! Keyboard bindings for the interactive selectors.
+//!
+//! Bindings are configured by X keysym name (`Return`, `BackSpace`, `space`,
+//! `h`, ...) and matched against the unshifted keysym of each pressed
+//! keycode, so `Shift+Left` still reads as `Left`.
+
+use super::CaptureError;
+use crate::config::SelectorConfig;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+
+/// What a key does in the region selector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Action {
+    Confirm,
+    Cancel,
+    Restart,
+    /// Held while dragging to move the selection.
+    Move,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Keycode → keysym table for the server plus the configured bindings.
+pub(super) struct KeyBindings {
+    min_keycode: u8,
+    per_keycode: usize,
+    keysyms: Vec<Keysym>,
+    bindings: Vec<(Keysym, Action)>,
+}
+
+impl KeyBindings {
+    pub(super) fn load(conn: &impl Connection, config: &SelectorConfig) -> Result<Self, CaptureError> {
+        let mut bindings = Vec::new();
+        let groups = [
+            (&config.confirm, Action::Confirm),
+            (&config.cancel, Action::Cancel),
+            (&config.restart, Action::Restart),
+            (&config.move_selection, Action::Move),
+            (&config.left, Action::Left),
+            (&config.right, Action::Right),
+            (&config.up, Action::Up),
+            (&config.down, Action::Down),
+        ];
+        for (names, action) in groups {
+            for name in names {
+                let keysym = keysym_from_name(name).ok_or_else(|| {
+                    CaptureError::Other(format!("unknown key name in [selector]: {name}"))
+                })?;
+                bindings.push((keysym, action));
+            }
+        }
+
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn
+            .get_keyboard_mapping(min, max - min + 1)
+            .map_err(|e| CaptureError::X11(format!("get_keyboard_mapping: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("get_keyboard_mapping reply: {e}")))?;
+
+        Ok(Self {
+            min_keycode: min,
+            per_keycode: mapping.keysyms_per_keycode as usize,
+            keysyms: mapping.keysyms,
+            bindings,
+        })
+    }
+
+    /// The action bound to `keycode`, if any.
+    pub(super) fn action(&self, keycode: u8) -> Option<Action> {
+        let keysym = self.keysym(keycode)?;
+        self.bindings
+            .iter()
+            .find(|&&(k, _)| k == keysym)
+            .map(|&(_, action)| action)
+    }
+
+    /// The first non-empty keysym of `keycode` (the unshifted one).
+    fn keysym(&self, keycode: u8) -> Option<Keysym> {
+        let start = (keycode.checked_sub(self.min_keycode)? as usize) * self.per_keycode;
+        self.keysyms
+            .get(start..start + self.per_keycode)?
+            .iter()
+            .copied()
+            .find(|&k| k != 0)
+    }
+}
+
+/// Look up a keysym by its X name. Single printable characters map to
+/// themselves (letters case-insensitively).
+pub fn keysym_from_name(name: &str) -> Option<Keysym> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next())
+        && c.is_ascii_graphic()
+    {
+        return Some(c.to_ascii_lowercase() as Keysym);
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u32>().ok())
+        && (1..=35).contains(&n)
+    {
+        return Some(0xffbd + n);
+    }
+    let keysym = match name {
+        "space" => 0x0020,
+        "BackSpace" => 0xff08,
+        "Tab" => 0xff09,
+        "Return" => 0xff0d,
+        "Escape" => 0xff1b,
+        "Delete" => 0xffff,
+        "Home" => 0xff50,
+        "Left" => 0xff51,
+        "Up" => 0xff52,
+        "Right" => 0xff53,
+        "Down" => 0xff54,
+        "Page_Up" | "Prior" => 0xff55,
+        "Page_Down" | "Next" => 0xff56,
+        "End" => 0xff57,
+        "Insert" => 0xff63,
+        "KP_Enter" => 0xff8d,
+        "KP_Left" => 0xff96,
+        "KP_Up" => 0xff97,
+        "KP_Right" => 0xff98,
+        "KP_Down" => 0xff99,
+        _ => return None,
+    };
+    Some(keysym)
+}
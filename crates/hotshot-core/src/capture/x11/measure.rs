@@ -0,0 +1,176 @@
+//! On-screen ruler on the frozen-screen overlay.
+//!
+//! Hovering shows how far the area of uniform colour under the pointer
+//! stretches horizontally and vertically; clicking records those two spans.
+//! Dragging measures between two points instead, with both ends snapping to
+//! nearby colour edges (hold Ctrl to place them freely). Every measurement
+//! stays on screen; right-click or the restart key removes the last one,
+//! and the confirm or cancel key or a middle-click finishes.
+
+use super::keys::{Action, KeyBindings};
+use super::overlay::{Extras, Overlay, Ruler, draw_overlay};
+use super::{CaptureError, CaptureOptions, Measurement, Region, connect};
+use image::RgbaImage;
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+
+/// Per-channel difference within which neighbouring pixels count as the
+/// same colour.
+const COLOR_TOLERANCE: u8 = 4;
+/// How far a dragged end looks for a colour edge to snap to.
+const SNAP_RADIUS: i16 = 8;
+
+pub(super) fn measure(
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Vec<Measurement>, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let screen = &conn.setup().roots[screen_num].clone();
+    let keys = KeyBindings::load(&conn, &opts.selector)?;
+    let mut overlay = Overlay::new(&conn, screen, display_bounds)?;
+    let pixels = overlay.extract(0, 0, overlay.sw, overlay.sh)?;
+
+    let mut done: Vec<Ruler> = Vec::new();
+    let mut anchor: Option<(i16, i16)> = None;
+    let mut pointer: Option<(i16, i16)> = None;
+    let mut free = false;
+
+    loop {
+        let redraw = match overlay.next_event()? {
+            Event::Expose(_) => true,
+            Event::MotionNotify(ev) => {
+                pointer = Some((ev.event_x, ev.event_y));
+                free = ev.state.contains(KeyButMask::CONTROL);
+                true
+            }
+            Event::ButtonPress(ev) if ev.detail == 1 => {
+                let p = (ev.event_x, ev.event_y);
+                free = ev.state.contains(KeyButMask::CONTROL);
+                anchor = Some(if free { p } else { snap(&pixels, p) });
+                pointer = Some(p);
+                true
+            }
+            Event::ButtonRelease(ev) if ev.detail == 1 => {
+                let p = (ev.event_x, ev.event_y);
+                free = ev.state.contains(KeyButMask::CONTROL);
+                match anchor.take() {
+                    // A click rather than a drag records the spans.
+                    Some(a) if a == p || snap(&pixels, p) == a => {
+                        done.extend(spans(&pixels, p));
+                    }
+                    Some(a) => {
+                        let to = if free { p } else { snap(&pixels, p) };
+                        done.push(Ruler { from: a, to });
+                    }
+                    None => {}
+                }
+                true
+            }
+            Event::ButtonPress(ev) if ev.detail == 3 => {
+                anchor = None;
+                done.pop();
+                true
+            }
+            // Works even when another window has taken the keyboard.
+            Event::ButtonPress(ev) if ev.detail == 2 => break,
+            Event::KeyPress(ev) => match keys.action(ev.detail) {
+                Some(Action::Confirm | Action::Cancel) => break,
+                Some(Action::Restart) => {
+                    anchor = None;
+                    done.pop();
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        };
+
+        if redraw {
+            let mut rulers = done.clone();
+            match (anchor, pointer) {
+                (Some(a), Some(p)) => {
+                    let to = if free { p } else { snap(&pixels, p) };
+                    rulers.push(Ruler { from: a, to });
+                }
+                (None, Some(p)) if !free => rulers.extend(spans(&pixels, p)),
+                _ => {}
+            }
+            let extras = Extras {
+                loupe: pointer.filter(|_| opts.selector.magnifier),
+                rulers: &rulers,
+                ..Extras::default()
+            };
+            draw_overlay(&overlay, None, extras)?;
+        }
+    }
+
+    let (ox, oy) = (overlay.ox as i32, overlay.oy as i32);
+    Ok(done
+        .iter()
+        .map(|r| Measurement {
+            x1: ox + r.from.0 as i32,
+            y1: oy + r.from.1 as i32,
+            x2: ox + r.to.0 as i32,
+            y2: oy + r.to.1 as i32,
+        })
+        .collect())
+}
+
+fn same_color(pixels: &RgbaImage, a: (i16, i16), b: (i16, i16)) -> bool {
+    let pa = pixels.get_pixel(a.0 as u32, a.1 as u32);
+    let pb = pixels.get_pixel(b.0 as u32, b.1 as u32);
+    pa.0.iter().zip(pb.0.iter()).all(|(x, y)| x.abs_diff(*y) <= COLOR_TOLERANCE)
+}
+
+fn in_bounds(pixels: &RgbaImage, (x, y): (i16, i16)) -> bool {
+    x >= 0 && y >= 0 && (x as u32) < pixels.width() && (y as u32) < pixels.height()
+}
+
+/// Walk from `p` by `step` while the colour stays the same; returns the
+/// last pixel of the run.
+fn run_end(pixels: &RgbaImage, p: (i16, i16), step: (i16, i16)) -> (i16, i16) {
+    let mut q = p;
+    loop {
+        let next = (q.0 + step.0, q.1 + step.1);
+        if !in_bounds(pixels, next) || !same_color(pixels, q, next) {
+            return q;
+        }
+        q = next;
+    }
+}
+
+/// The horizontal and vertical extent of the uniform colour under `p`,
+/// edge to edge.
+fn spans(pixels: &RgbaImage, p: (i16, i16)) -> [Ruler; 2] {
+    if !in_bounds(pixels, p) {
+        return [Ruler { from: p, to: p }; 2];
+    }
+    let (left, right) = (run_end(pixels, p, (-1, 0)), run_end(pixels, p, (1, 0)));
+    let (top, bottom) = (run_end(pixels, p, (0, -1)), run_end(pixels, p, (0, 1)));
+    [
+        Ruler { from: left, to: (right.0 + 1, right.1) },
+        Ruler { from: top, to: (bottom.0, bottom.1 + 1) },
+    ]
+}
+
+/// Move `p` onto the nearest colour edge within [`SNAP_RADIUS`], separately
+/// along each axis. An edge sits between two differing pixels; the point
+/// snaps to the first pixel after it.
+fn snap(pixels: &RgbaImage, p: (i16, i16)) -> (i16, i16) {
+    if !in_bounds(pixels, p) {
+        return p;
+    }
+    let nearest = |step: (i16, i16)| {
+        (0..=SNAP_RADIUS)
+            .flat_map(|d| [d, -d])
+            .map(|d| (p.0 + step.0 * d, p.1 + step.1 * d))
+            .find(|&q| {
+                let prev = (q.0 - step.0, q.1 - step.1);
+                in_bounds(pixels, q) && in_bounds(pixels, prev) && !same_color(pixels, prev, q)
+            })
+    };
+    let x = nearest((1, 0)).map_or(p.0, |q| q.0);
+    let y = nearest((0, 1)).map_or(p.1, |q| q.1);
+    (x, y)
+}
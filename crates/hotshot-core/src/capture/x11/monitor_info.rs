@@ -0,0 +1,228 @@
+//! Monitor discovery.
+//!
+//! RandR 1.5 monitors give us names, geometry, the primary flag and the
+//! physical size. Each monitor's first output adds rotation and refresh rate
+//! (from its CRTC and mode) and the manufacturer and model from its EDID, which
+//! stay the same when a docking station shuffles connector names. Servers
+//! without RandR 1.5 fall back to Xinerama, and then to the root window.
+
+use super::{CaptureError, Monitor, windows};
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::xinerama::ConnectionExt as _;
+use x11rb::protocol::xproto::*;
+
+/// EDID display descriptor tag for the monitor name.
+const EDID_NAME_TAG: u8 = 0xfc;
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+pub(super) fn list(conn: &impl Connection, root: Window) -> Result<Vec<Monitor>, CaptureError> {
+    if randr_1_5(conn) {
+        let monitors = randr_monitors(conn, root)?;
+        if !monitors.is_empty() {
+            return Ok(monitors);
+        }
+    }
+    if let Some(monitors) = xinerama_monitors(conn) {
+        return Ok(monitors);
+    }
+    Ok(vec![root_monitor(conn, root)])
+}
+
+fn randr_1_5(conn: &impl Connection) -> bool {
+    conn.randr_query_version(1, 5)
+        .ok()
+        .and_then(|c| c.reply().ok())
+        .is_some_and(|v| (v.major_version, v.minor_version) >= (1, 5))
+}
+
+fn randr_monitors(conn: &impl Connection, root: Window) -> Result<Vec<Monitor>, CaptureError> {
+    let monitors = conn
+        .randr_get_monitors(root, true)
+        .map_err(|e| CaptureError::X11(format!("randr get_monitors: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("randr get_monitors reply: {e}")))?;
+    let resources = conn
+        .randr_get_screen_resources_current(root)
+        .map_err(|e| CaptureError::X11(format!("randr get_screen_resources: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("randr get_screen_resources reply: {e}")))?;
+    let edid = windows::intern_atom(conn, b"EDID")?;
+
+    let mut result = Vec::new();
+    for info in &monitors.monitors {
+        let name = conn
+            .get_atom_name(info.name)
+            .map_err(|e| CaptureError::X11(format!("get_atom_name: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("get_atom_name reply: {e}")))?;
+        let mut monitor = Monitor {
+            name: String::from_utf8_lossy(&name.name).to_string(),
+            x: info.x,
+            y: info.y,
+            width: info.width,
+            height: info.height,
+            primary: info.primary,
+            width_mm: Some(info.width_in_millimeters).filter(|&mm| mm > 0),
+            height_mm: Some(info.height_in_millimeters).filter(|&mm| mm > 0),
+            ..Default::default()
+        };
+        // Outputs can be unplugged between requests; keep what we have.
+        if let Some(&output) = info.outputs.first() {
+            let _ = add_output_details(conn, &resources, output, edid, &mut monitor);
+        }
+        monitor.dpi = monitor.compute_dpi();
+        result.push(monitor);
+    }
+
+    Ok(result)
+}
+
+/// Rotation, refresh rate and EDID identity from the output behind a monitor.
+fn add_output_details(
+    conn: &impl Connection,
+    resources: &randr::GetScreenResourcesCurrentReply,
+    output: randr::Output,
+    edid: Atom,
+    monitor: &mut Monitor,
+) -> Result<(), CaptureError> {
+    let timestamp = resources.config_timestamp;
+    let info = conn
+        .randr_get_output_info(output, timestamp)
+        .map_err(|e| CaptureError::X11(format!("randr get_output_info: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("randr get_output_info reply: {e}")))?;
+
+    if info.crtc != x11rb::NONE {
+        let crtc = conn
+            .randr_get_crtc_info(info.crtc, timestamp)
+            .map_err(|e| CaptureError::X11(format!("randr get_crtc_info: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("randr get_crtc_info reply: {e}")))?;
+        monitor.rotation = rotation_degrees(crtc.rotation);
+        monitor.refresh = resources
+            .modes
+            .iter()
+            .find(|m| m.id == crtc.mode)
+            .and_then(refresh_rate);
+    }
+
+    let property = conn
+        .randr_get_output_property(output, edid, AtomEnum::ANY, 0, 64, false, false)
+        .map_err(|e| CaptureError::X11(format!("randr get_output_property: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("randr get_output_property reply: {e}")))?;
+    if let Some((manufacturer, model)) = parse_edid(&property.data) {
+        monitor.manufacturer = Some(manufacturer);
+        monitor.model = Some(model);
+    }
+
+    Ok(())
+}
+
+/// Clockwise rotation in degrees from a RandR rotation mask.
+fn rotation_degrees(rotation: randr::Rotation) -> u16 {
+    let bits = u16::from(rotation);
+    if bits & u16::from(randr::Rotation::ROTATE90) != 0 {
+        90
+    } else if bits & u16::from(randr::Rotation::ROTATE180) != 0 {
+        180
+    } else if bits & u16::from(randr::Rotation::ROTATE270) != 0 {
+        270
+    } else {
+        0
+    }
+}
+
+/// Vertical refresh in Hz: pixel clock over the total (blanking included)
+/// pixels per frame.
+fn refresh_rate(mode: &randr::ModeInfo) -> Option<f64> {
+    let mut vtotal = mode.vtotal as f64;
+    if mode.mode_flags.contains(randr::ModeFlag::DOUBLE_SCAN) {
+        vtotal *= 2.0;
+    }
+    if mode.mode_flags.contains(randr::ModeFlag::INTERLACE) {
+        vtotal /= 2.0;
+    }
+    let pixels = mode.htotal as f64 * vtotal;
+    (pixels > 0.0).then(|| mode.dot_clock as f64 / pixels)
+}
+
+/// The PNP manufacturer id and model name from an EDID base block. Panels
+/// without a name descriptor get their product code instead.
+fn parse_edid(edid: &[u8]) -> Option<(String, String)> {
+    if edid.len() < 128 || edid[..8] != EDID_HEADER {
+        return None;
+    }
+
+    // Three 5-bit letters, 'A' = 1, big-endian.
+    let id = u16::from_be_bytes([edid[8], edid[9]]);
+    let manufacturer: String = [10, 5, 0]
+        .iter()
+        .map(|shift| (b'A' - 1 + ((id >> shift) & 0x1f) as u8) as char)
+        .collect();
+    if !manufacturer.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+
+    // Four 18-byte descriptors; display descriptors start with three zeros.
+    let name = edid[54..126]
+        .chunks(18)
+        .find(|d| d[..3] == [0, 0, 0] && d[3] == EDID_NAME_TAG)
+        .map(|d| {
+            let text = d[5..].split(|&b| b == b'\n').next().unwrap_or_default();
+            String::from_utf8_lossy(text).trim().to_string()
+        })
+        .filter(|name| !name.is_empty());
+    let model = name.unwrap_or_else(|| {
+        format!("{:04X}", u16::from_le_bytes([edid[10], edid[11]]))
+    });
+
+    Some((manufacturer, model))
+}
+
+/// Screens from Xinerama, when it's active. These carry geometry only.
+fn xinerama_monitors(conn: &impl Connection) -> Option<Vec<Monitor>> {
+    let active = conn.xinerama_is_active().ok()?.reply().ok()?;
+    if active.state == 0 {
+        return None;
+    }
+    let screens = conn.xinerama_query_screens().ok()?.reply().ok()?;
+    let monitors: Vec<Monitor> = screens
+        .screen_info
+        .iter()
+        .enumerate()
+        .map(|(i, s)| Monitor {
+            name: format!("XINERAMA-{i}"),
+            x: s.x_org,
+            y: s.y_org,
+            width: s.width,
+            height: s.height,
+            // Xinerama lists the primary head first.
+            primary: i == 0,
+            ..Default::default()
+        })
+        .collect();
+    (!monitors.is_empty()).then_some(monitors)
+}
+
+/// The whole root window as a single monitor.
+fn root_monitor(conn: &impl Connection, root: Window) -> Monitor {
+    let screen = conn
+        .setup()
+        .roots
+        .iter()
+        .find(|s| s.root == root)
+        .unwrap_or(&conn.setup().roots[0]);
+    let mut monitor = Monitor {
+        name: "default".to_string(),
+        width: screen.width_in_pixels,
+        height: screen.height_in_pixels,
+        primary: true,
+        width_mm: Some(screen.width_in_millimeters as u32).filter(|&mm| mm > 0),
+        height_mm: Some(screen.height_in_millimeters as u32).filter(|&mm| mm > 0),
+        ..Default::default()
+    };
+    monitor.dpi = monitor.compute_dpi();
+    monitor
+}
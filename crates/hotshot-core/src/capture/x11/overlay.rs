@@ -0,0 +1,1090 @@
+//! Frozen-screen overlay shared by the interactive X11 selectors.
+//!
+//! The screen is copied into a server-side pixmap once, then an
+//! override-redirect window shows a dimmed version of it. Selectors redraw
+//! it (double-buffered) as the pointer moves and extract the final pixels
+//! from the frozen copy, so nothing drawn on the overlay ends up in the
+//! image.
+
+use super::pixels::PixelFormat;
+use super::{CaptureError, Region};
+use image::RgbaImage;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::Event;
+use x11rb::protocol::render::{self, Pictformat};
+use x11rb::protocol::xproto::*;
+use x11rb::rust_connection::RustConnection;
+
+pub(super) const ESCAPE_KEYCODE: u8 = 9;
+
+/// Side length of the square resize handles.
+pub(super) const HANDLE_SIZE: u16 = 8;
+
+/// Magnifier: screen pixels per side (odd, so one sits under the crosshair)
+/// and how many overlay pixels each one becomes. The zoom is a power of two
+/// so its reciprocal is exact in XRender's 16.16 fixed point.
+const LOUPE_CELLS: u16 = 15;
+const LOUPE_ZOOM: u16 = 8;
+/// Distance between the pointer and the magnifier.
+const LOUPE_OFFSET: i16 = 24;
+/// Padding around label text.
+const LABEL_PAD: u16 = 4;
+
+/// What to draw over the dimmed screen besides the selection itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Extras<'a> {
+    /// Resize handles on the selection's corners and edges.
+    pub handles: bool,
+    /// W×H and X,Y label next to the selection.
+    pub label: bool,
+    /// Magnifier for the pixels around this point (overlay coordinates).
+    pub loupe: Option<(i16, i16)>,
+    /// Measuring lines, each labelled with its length.
+    pub rulers: &'a [Ruler],
+}
+
+/// A line between two points in overlay coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Ruler {
+    pub from: (i16, i16),
+    pub to: (i16, i16),
+}
+
+/// Core font used for labels.
+struct Text {
+    font: Font,
+    gc: Gcontext,
+    char_width: u16,
+    ascent: i16,
+    descent: i16,
+}
+
+impl Text {
+    fn height(&self) -> u16 {
+        (self.ascent + self.descent) as u16
+    }
+
+    fn width(&self, s: &[u8]) -> u16 {
+        self.char_width * s.len() as u16
+    }
+}
+
+/// The overlay window and all X11 resources backing it.
+///
+/// Everything (including the frozen screen pixmap) is released on drop.
+pub(super) struct Overlay<'a> {
+    pub conn: &'a RustConnection,
+    /// Overlay origin in root coordinates.
+    pub ox: i16,
+    pub oy: i16,
+    /// Overlay dimensions.
+    pub sw: u16,
+    pub sh: u16,
+    pub window: u32,
+    pub screen_pixmap: u32,
+    pub screen_picture: u32,
+    pub back_pixmap: u32,
+    pub back_picture: u32,
+    pub flip_gc: u32,
+    pub dim_picture: u32,
+    dim_pixmap: u32,
+    pub border_picture: u32,
+    border_pixmap: u32,
+    /// Second picture of the frozen screen, transformed to draw the magnifier.
+    loupe_picture: u32,
+    text: Option<Text>,
+    /// Layout of the frozen screen's pixels.
+    format: PixelFormat,
+    cursor: u32,
+    cursor_font: u32,
+    grabbed: bool,
+    /// Without grabs, the window that had the keyboard before the overlay
+    /// took it; it gets it back on drop.
+    focus: Option<Window>,
+    /// A non-motion event read while coalescing motion, delivered next.
+    pending: Option<Event>,
+}
+
+impl Drop for Overlay<'_> {
+    fn drop(&mut self) {
+        if let Some(text) = &self.text {
+            let _ = self.conn.free_gc(text.gc);
+            let _ = self.conn.close_font(text.font);
+        }
+        let _ = render::free_picture(self.conn, self.loupe_picture);
+        let _ = render::free_picture(self.conn, self.border_picture);
+        let _ = self.conn.free_pixmap(self.border_pixmap);
+        let _ = render::free_picture(self.conn, self.dim_picture);
+        let _ = self.conn.free_pixmap(self.dim_pixmap);
+        let _ = self.conn.free_gc(self.flip_gc);
+        let _ = render::free_picture(self.conn, self.back_picture);
+        let _ = self.conn.free_pixmap(self.back_pixmap);
+        let _ = render::free_picture(self.conn, self.screen_picture);
+        let _ = self.conn.free_pixmap(self.screen_pixmap);
+        if let Some(focus) = self.focus {
+            let _ = self.conn.set_input_focus(InputFocus::PARENT, focus, Time::CURRENT_TIME);
+        }
+        let _ = self.conn.unmap_window(self.window);
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.free_cursor(self.cursor);
+        let _ = self.conn.close_font(self.cursor_font);
+        if self.grabbed {
+            let _ = self.conn.ungrab_pointer(Time::CURRENT_TIME);
+            let _ = self.conn.ungrab_keyboard(Time::CURRENT_TIME);
+        }
+        let _ = self.conn.flush();
+    }
+}
+
+impl<'a> Overlay<'a> {
+    /// Freeze the screen (or just `display_bounds`) and map the overlay on top.
+    pub(super) fn new(
+        conn: &'a RustConnection,
+        screen: &Screen,
+        display_bounds: Option<Region>,
+    ) -> Result<Self, CaptureError> {
+        // When display_bounds is set, constrain the overlay to that display.
+        // ox/oy are the overlay origin in root coordinates; sw/sh are the overlay dimensions.
+        let (ox, oy, sw, sh) = match display_bounds {
+            Some(b) => (b.x as i16, b.y as i16, b.width as u16, b.height as u16),
+            None => (0i16, 0i16, screen.width_in_pixels, screen.height_in_pixels),
+        };
+
+        // ---- XRender init ----
+        render::query_version(conn, 0, 11)
+            .map_err(|e| CaptureError::X11(format!("render query_version: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("render query_version reply: {e}")))?;
+
+        let root_pictformat = find_pictformat_for_visual(conn, screen.root_visual)?;
+        // Find a 32-bit ARGB pictformat for solid-fill sources (needed for alpha blending).
+        let argb_format = find_argb_visual_and_format(conn, screen)
+            .map(|(_, _, fmt)| fmt)?;
+
+        // ---- Capture screen (only the target display if constrained) ----
+        let format = PixelFormat::root(conn, screen)?;
+        let screen_pixmap = capture_screen_to_pixmap(conn, screen, &format, display_bounds)?;
+
+        let screen_picture = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        render::create_picture(
+            conn,
+            screen_picture,
+            screen_pixmap,
+            root_pictformat,
+            &render::CreatePictureAux::new(),
+        )
+        .map_err(|e| CaptureError::X11(format!("create_picture screen: {e}")))?;
+
+        // ---- Create overlay window at root depth (avoids alpha/compositor issues) ----
+        let window = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        let win_aux = CreateWindowAux::new()
+            .override_redirect(1)
+            .background_pixel(screen.black_pixel)
+            .border_pixel(0)
+            .event_mask(
+                EventMask::EXPOSURE
+                    | EventMask::BUTTON_PRESS
+                    | EventMask::BUTTON_RELEASE
+                    | EventMask::POINTER_MOTION
+                    | EventMask::ENTER_WINDOW
+                    | EventMask::KEY_PRESS
+                    | EventMask::KEY_RELEASE,
+            );
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            ox,
+            oy,
+            sw,
+            sh,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &win_aux,
+        )
+        .map_err(|e| CaptureError::X11(format!("create_window: {e}")))?;
+
+        conn.map_window(window)
+            .map_err(|e| CaptureError::X11(format!("map_window: {e}")))?;
+        conn.flush()
+            .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
+
+        // ---- Back buffer (off-screen pixmap + XRender picture) ----
+        let back_pixmap = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        conn.create_pixmap(screen.root_depth, back_pixmap, screen.root, sw, sh)
+            .map_err(|e| CaptureError::X11(format!("create_pixmap back: {e}")))?;
+        let back_picture = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        render::create_picture(
+            conn,
+            back_picture,
+            back_pixmap,
+            root_pictformat,
+            &render::CreatePictureAux::new(),
+        )
+        .map_err(|e| CaptureError::X11(format!("create_picture back: {e}")))?;
+
+        // GC for copy_area flip from back buffer to window
+        let flip_gc = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        conn.create_gc(flip_gc, window, &CreateGCAux::default())
+            .map_err(|e| CaptureError::X11(format!("create_gc flip: {e}")))?;
+
+        // ---- Solid-fill sources (32-bit ARGB for alpha blending) ----
+        // These need a drawable compatible with 32-bit depth, use screen.root as parent.
+        let dim_pixmap = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        conn.create_pixmap(32, dim_pixmap, screen.root, 1, 1)
+            .map_err(|e| CaptureError::X11(format!("create_pixmap dim: {e}")))?;
+        let dim_picture = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        render::create_picture(
+            conn,
+            dim_picture,
+            dim_pixmap,
+            argb_format,
+            &render::CreatePictureAux::new().repeat(render::Repeat::NORMAL),
+        )
+        .map_err(|e| CaptureError::X11(format!("create_picture dim: {e}")))?;
+        render::fill_rectangles(
+            conn,
+            render::PictOp::SRC,
+            dim_picture,
+            render::Color { red: 0, green: 0, blue: 0, alpha: 0x8000 },
+            &[Rectangle { x: 0, y: 0, width: 1, height: 1 }],
+        )
+        .map_err(|e| CaptureError::X11(format!("fill dim: {e}")))?;
+
+        let border_pixmap = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        conn.create_pixmap(32, border_pixmap, screen.root, 1, 1)
+            .map_err(|e| CaptureError::X11(format!("create_pixmap border: {e}")))?;
+        let border_picture = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        render::create_picture(
+            conn,
+            border_picture,
+            border_pixmap,
+            argb_format,
+            &render::CreatePictureAux::new().repeat(render::Repeat::NORMAL),
+        )
+        .map_err(|e| CaptureError::X11(format!("create_picture border: {e}")))?;
+        render::fill_rectangles(
+            conn,
+            render::PictOp::SRC,
+            border_picture,
+            render::Color { red: 0xffff, green: 0xffff, blue: 0xffff, alpha: 0xffff },
+            &[Rectangle { x: 0, y: 0, width: 1, height: 1 }],
+        )
+        .map_err(|e| CaptureError::X11(format!("fill border: {e}")))?;
+
+        let loupe_picture = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        render::create_picture(
+            conn,
+            loupe_picture,
+            screen_pixmap,
+            root_pictformat,
+            &render::CreatePictureAux::new(),
+        )
+        .map_err(|e| CaptureError::X11(format!("create_picture loupe: {e}")))?;
+
+        let text = open_label_font(conn, back_pixmap, screen)?;
+
+        // ---- Crosshair cursor ----
+        let cursor_font = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        conn.open_font(cursor_font, b"cursor")
+            .map_err(|e| CaptureError::X11(format!("open_font cursor: {e}")))?;
+        let cursor = conn
+            .generate_id()
+            .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+        // Glyph 34 = crosshair in the cursor font, 35 = its mask
+        conn.create_glyph_cursor(
+            cursor,
+            cursor_font,
+            cursor_font,
+            34,
+            35,
+            0xffff, 0xffff, 0xffff, // foreground: white
+            0, 0, 0,                 // background: black
+        )
+        .map_err(|e| CaptureError::X11(format!("create_glyph_cursor: {e}")))?;
+
+        // Set the crosshair cursor on the overlay window itself, so it only
+        // appears when the mouse is over this window.
+        conn.change_window_attributes(window, &ChangeWindowAttributesAux::new().cursor(cursor))
+            .map_err(|e| CaptureError::X11(format!("set cursor on window: {e}")))?;
+
+        // ---- Grab pointer and keyboard ----
+        // When targeting a single display, skip grabs entirely so the user can
+        // interact normally on other monitors.  The overlay window's event mask
+        // delivers press/release/motion events when the pointer is over it, and
+        // key events while it has the input focus.
+        let grabbed = display_bounds.is_none();
+        let mut focus = None;
+        if grabbed {
+            conn.grab_pointer(
+                true,
+                window,
+                EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                window,
+                cursor,
+                Time::CURRENT_TIME,
+            )
+            .map_err(|e| CaptureError::X11(format!("grab_pointer: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("grab_pointer reply: {e}")))?;
+
+            conn.grab_keyboard(true, window, Time::CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)
+                .map_err(|e| CaptureError::X11(format!("grab_keyboard: {e}")))?
+                .reply()
+                .map_err(|e| CaptureError::X11(format!("grab_keyboard reply: {e}")))?;
+        } else {
+            let previous = conn
+                .get_input_focus()
+                .map_err(|e| CaptureError::X11(format!("get_input_focus: {e}")))?
+                .reply()
+                .map_err(|e| CaptureError::X11(format!("get_input_focus reply: {e}")))?;
+            conn.set_input_focus(InputFocus::PARENT, window, Time::CURRENT_TIME)
+                .map_err(|e| CaptureError::X11(format!("set_input_focus: {e}")))?;
+            focus = Some(previous.focus);
+        }
+
+        let overlay = Self {
+            conn,
+            ox,
+            oy,
+            sw,
+            sh,
+            window,
+            screen_pixmap,
+            screen_picture,
+            back_pixmap,
+            back_picture,
+            flip_gc,
+            dim_picture,
+            dim_pixmap,
+            border_picture,
+            border_pixmap,
+            loupe_picture,
+            text,
+            format,
+            cursor,
+            cursor_font,
+            grabbed,
+            focus,
+            pending: None,
+        };
+
+        // ---- Initial draw (fully dimmed) ----
+        draw_overlay(&overlay, None, Extras::default())?;
+
+        Ok(overlay)
+    }
+
+    /// Wait for the next event, collapsing runs of queued `MotionNotify`
+    /// events into the latest one.
+    ///
+    /// Auto-repeat arrives as a release/press pair with the same timestamp;
+    /// the release is dropped so a held key only reports repeated presses.
+    pub(super) fn next_event(&mut self) -> Result<Event, CaptureError> {
+        loop {
+            match self.read_event()? {
+                // Back from another monitor, where a click may have moved the
+                // focus: take the keyboard again.
+                Event::EnterNotify(_) if self.focus.is_some() => {
+                    self.conn
+                        .set_input_focus(InputFocus::PARENT, self.window, Time::CURRENT_TIME)
+                        .map_err(|e| CaptureError::X11(format!("set_input_focus: {e}")))?;
+                }
+                event => return Ok(event),
+            }
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Event, CaptureError> {
+        if let Some(event) = self.pending.take() {
+            return Ok(event);
+        }
+        let mut event = self
+            .conn
+            .wait_for_event()
+            .map_err(|e| CaptureError::X11(format!("wait_for_event: {e}")))?;
+        if let Event::KeyRelease(release) = &event {
+            let next = self
+                .conn
+                .poll_for_event()
+                .map_err(|e| CaptureError::X11(format!("poll_for_event: {e}")))?;
+            match next {
+                Some(Event::KeyPress(press))
+                    if press.detail == release.detail && press.time == release.time =>
+                {
+                    return Ok(Event::KeyPress(press));
+                }
+                other => self.pending = other,
+            }
+        }
+        if matches!(event, Event::MotionNotify(_)) {
+            while let Some(queued) = self
+                .conn
+                .poll_for_event()
+                .map_err(|e| CaptureError::X11(format!("poll_for_event: {e}")))?
+            {
+                if matches!(queued, Event::MotionNotify(_)) {
+                    event = queued;
+                } else {
+                    // x11rb has no "put back"; deliver it on the next call.
+                    self.pending = Some(queued);
+                    break;
+                }
+            }
+        }
+        Ok(event)
+    }
+
+    /// Convert a root-space rectangle to overlay coordinates, clipped to the
+    /// overlay. Returns `None` if nothing of it is visible.
+    pub(super) fn clip(&self, r: Region) -> Option<(i16, i16, u16, u16)> {
+        let lx = (r.x - self.ox as i32).max(0);
+        let ly = (r.y - self.oy as i32).max(0);
+        let rx = (r.x - self.ox as i32 + r.width as i32).min(self.sw as i32);
+        let ry = (r.y - self.oy as i32 + r.height as i32).min(self.sh as i32);
+        if rx <= lx || ry <= ly {
+            return None;
+        }
+        Some((lx as i16, ly as i16, (rx - lx) as u16, (ry - ly) as u16))
+    }
+
+    /// Extract part of the frozen screen, in overlay coordinates.
+    pub(super) fn extract(
+        &self,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<RgbaImage, CaptureError> {
+        super::get_image(self.conn, self.screen_pixmap, &self.format, x, y, width, height)
+    }
+}
+
+/// Open the "fixed" core font for labels. Labels are skipped (`None`) on
+/// servers that don't have it.
+fn open_label_font(
+    conn: &RustConnection,
+    drawable: Drawable,
+    screen: &Screen,
+) -> Result<Option<Text>, CaptureError> {
+    let font = conn
+        .generate_id()
+        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+    let opened = conn
+        .open_font(font, b"fixed")
+        .map_err(|e| CaptureError::X11(format!("open_font fixed: {e}")))?
+        .check();
+    if opened.is_err() {
+        return Ok(None);
+    }
+    let info = conn
+        .query_font(font)
+        .map_err(|e| CaptureError::X11(format!("query_font: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("query_font reply: {e}")))?;
+
+    let gc = conn
+        .generate_id()
+        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+    conn.create_gc(
+        gc,
+        drawable,
+        &CreateGCAux::new().font(font).foreground(screen.white_pixel),
+    )
+    .map_err(|e| CaptureError::X11(format!("create_gc text: {e}")))?;
+
+    Ok(Some(Text {
+        font,
+        gc,
+        char_width: info.max_bounds.character_width as u16,
+        ascent: info.font_ascent,
+        descent: info.font_descent,
+    }))
+}
+
+/// Find a 32-bit ARGB visual and the matching XRender Pictformat.
+fn find_argb_visual_and_format(
+    conn: &RustConnection,
+    screen: &Screen,
+) -> Result<(Visualid, u8, Pictformat), CaptureError> {
+    let formats = render::query_pict_formats(conn)
+        .map_err(|e| CaptureError::X11(format!("query_pict_formats: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("query_pict_formats reply: {e}")))?;
+
+    // Walk render screens → depths → visuals to find a 32-bit ARGB visual.
+    for pscreen in &formats.screens {
+        for pdepth in &pscreen.depths {
+            if pdepth.depth != 32 {
+                continue;
+            }
+            for pvisual in &pdepth.visuals {
+                // Verify the visual belongs to one of the screen's allowed depths.
+                for sdepth in &screen.allowed_depths {
+                    if sdepth.depth != 32 {
+                        continue;
+                    }
+                    for v in &sdepth.visuals {
+                        if v.visual_id == pvisual.visual {
+                            return Ok((pvisual.visual, 32, pvisual.format));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Err(CaptureError::X11(
+        "no 32-bit ARGB visual found".to_string(),
+    ))
+}
+
+/// Find the XRender Pictformat that matches a given visual.
+fn find_pictformat_for_visual(
+    conn: &RustConnection,
+    visual: Visualid,
+) -> Result<Pictformat, CaptureError> {
+    let formats = render::query_pict_formats(conn)
+        .map_err(|e| CaptureError::X11(format!("query_pict_formats: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("query_pict_formats reply: {e}")))?;
+
+    for pscreen in &formats.screens {
+        for pdepth in &pscreen.depths {
+            for pvisual in &pdepth.visuals {
+                if pvisual.visual == visual {
+                    return Ok(pvisual.format);
+                }
+            }
+        }
+    }
+    Err(CaptureError::X11(format!(
+        "no pictformat for visual {visual}"
+    )))
+}
+
+/// Capture a region of the root window into a server-side Pixmap.
+///
+/// When `bounds` is `None`, captures the entire root window.
+/// When `bounds` is `Some(region)`, captures only that region.
+///
+/// Uses `get_image` (reads through the compositor) + `put_image`
+/// because `copy_area` from the root window on composited desktops returns
+/// blank/black content (the compositor owns the rendered pixels). Both go
+/// through an MIT-SHM segment when possible, otherwise the pixels make a
+/// round trip over the socket with a chunked `put_image`.
+fn capture_screen_to_pixmap(
+    conn: &RustConnection,
+    screen: &Screen,
+    format: &PixelFormat,
+    bounds: Option<Region>,
+) -> Result<u32, CaptureError> {
+    let root = screen.root;
+    let depth = screen.root_depth;
+
+    let (src_x, src_y, w, h) = match bounds {
+        Some(b) => (b.x as i16, b.y as i16, b.width as u16, b.height as u16),
+        None => (0i16, 0i16, screen.width_in_pixels, screen.height_in_pixels),
+    };
+
+    let pixmap = conn
+        .generate_id()
+        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+    conn.create_pixmap(depth, pixmap, root, w, h)
+        .map_err(|e| CaptureError::X11(format!("create_pixmap: {e}")))?;
+
+    let gc = conn
+        .generate_id()
+        .map_err(|e| CaptureError::X11(format!("generate_id: {e}")))?;
+    conn.create_gc(gc, pixmap, &CreateGCAux::default())
+        .map_err(|e| CaptureError::X11(format!("create_gc: {e}")))?;
+
+    let row_bytes = format.row_bytes(w) as u32;
+
+    // Fast path: read into and upload from a shared segment.
+    let via_shm = match super::shm::ShmSegment::new(conn, format.image_size(w, h)) {
+        Some(seg) => seg
+            .get_image(root, src_x, src_y, w, h)
+            .and_then(|_| seg.put_image(pixmap, gc, w, h, depth))
+            .is_ok(),
+        None => false,
+    };
+
+    if !via_shm {
+        // Read composited screen content via get_image.
+        let img = conn
+            .get_image(ImageFormat::Z_PIXMAP, root, src_x, src_y, w, h, !0)
+            .map_err(|e| CaptureError::X11(format!("get_image root: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("get_image root reply: {e}")))?;
+
+        // Upload pixel data in chunks (X11 has a max request size).
+        let max_req = conn.maximum_request_bytes();
+        // Reserve some bytes for the PutImage header (~28 bytes).
+        let max_data = (max_req - 32) as u32;
+        let rows_per_chunk = (max_data / row_bytes).max(1).min(h as u32);
+
+        let data = &img.data;
+        let mut y_offset: u16 = 0;
+        while y_offset < h {
+            let remaining = h - y_offset;
+            let chunk_h = remaining.min(rows_per_chunk as u16);
+            let start = (y_offset as u32 * row_bytes) as usize;
+            let end = start + (chunk_h as u32 * row_bytes) as usize;
+            conn.put_image(
+                ImageFormat::Z_PIXMAP,
+                pixmap,
+                gc,
+                w,
+                chunk_h,
+                0,
+                y_offset as i16,
+                0,
+                depth,
+                &data[start..end],
+            )
+            .map_err(|e| CaptureError::X11(format!("put_image: {e}")))?;
+            y_offset += chunk_h;
+        }
+    }
+
+    conn.free_gc(gc)
+        .map_err(|e| CaptureError::X11(format!("free_gc: {e}")))?;
+    conn.flush()
+        .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
+
+    Ok(pixmap)
+}
+
+/// Top-left corners of the eight resize handles around `sel`, clockwise
+/// from the top-left corner: NW, N, NE, E, SE, S, SW, W.
+pub(super) fn handle_positions((x, y, w, h): (i16, i16, u16, u16)) -> [(i16, i16); 8] {
+    let half = (HANDLE_SIZE / 2) as i16;
+    let (l, t) = (x - half, y - half);
+    let (cx, cy) = (l + (w / 2) as i16, t + (h / 2) as i16);
+    let (r, b) = (l + w as i16, t + h as i16);
+    [(l, t), (cx, t), (r, t), (r, cy), (r, b), (cx, b), (l, b), (l, cy)]
+}
+
+/// Draw the overlay with double buffering: render to back_picture (off-screen),
+/// then copy_area the finished frame to the window in one shot.
+///
+/// Everything, including the label and magnifier, is rendered from the
+/// frozen screen pixmap and only ever reaches the window, never the capture.
+pub(super) fn draw_overlay(
+    ov: &Overlay,
+    sel: Option<(i16, i16, u16, u16)>,
+    extras: Extras,
+) -> Result<(), CaptureError> {
+    let &Overlay {
+        conn,
+        window,
+        back_pixmap,
+        back_picture,
+        flip_gc,
+        screen_picture,
+        dim_picture,
+        sw,
+        sh,
+        ..
+    } = ov;
+
+    // 1) Composite full screenshot onto back buffer
+    render::composite(
+        conn,
+        render::PictOp::SRC,
+        screen_picture,
+        0u32,
+        back_picture,
+        0, 0,
+        0, 0,
+        0, 0,
+        sw, sh,
+    )
+    .map_err(|e| CaptureError::X11(format!("composite screenshot: {e}")))?;
+
+    // 2) Dim the entire back buffer (50% black over everything)
+    render::composite(
+        conn,
+        render::PictOp::OVER,
+        dim_picture,
+        0u32,
+        back_picture,
+        0, 0,
+        0, 0,
+        0, 0,
+        sw, sh,
+    )
+    .map_err(|e| CaptureError::X11(format!("composite dim: {e}")))?;
+
+    if let Some((sx, sy, sw_sel, sh_sel)) = sel
+        && sw_sel > 0
+        && sh_sel > 0
+    {
+        // 3) Cut out: composite original screenshot over the selected region
+        render::composite(
+            conn,
+            render::PictOp::SRC,
+            screen_picture,
+            0u32,
+            back_picture,
+            sx, sy,
+            0, 0,
+            sx, sy,
+            sw_sel, sh_sel,
+        )
+        .map_err(|e| CaptureError::X11(format!("composite cutout: {e}")))?;
+
+        // 4) White border (2px)
+        let bw: i16 = 2;
+        let border_rects = [
+            // top
+            Rectangle {
+                x: (sx - bw).max(0),
+                y: (sy - bw).max(0),
+                width: sw_sel + (2 * bw) as u16,
+                height: bw as u16,
+            },
+            // bottom
+            Rectangle {
+                x: (sx - bw).max(0),
+                y: sy + sh_sel as i16,
+                width: sw_sel + (2 * bw) as u16,
+                height: bw as u16,
+            },
+            // left
+            Rectangle {
+                x: (sx - bw).max(0),
+                y: sy,
+                width: bw as u16,
+                height: sh_sel,
+            },
+            // right
+            Rectangle {
+                x: sx + sw_sel as i16,
+                y: sy,
+                width: bw as u16,
+                height: sh_sel,
+            },
+        ];
+        render::fill_rectangles(
+            conn,
+            render::PictOp::OVER,
+            back_picture,
+            render::Color {
+                red: 0xffff,
+                green: 0xffff,
+                blue: 0xffff,
+                alpha: 0xffff,
+            },
+            &border_rects,
+        )
+        .map_err(|e| CaptureError::X11(format!("fill_rectangles border: {e}")))?;
+
+        // 4b) Resize handles: white squares with a dark outline
+        if extras.handles {
+            let outer: Vec<Rectangle> = handle_positions((sx, sy, sw_sel, sh_sel))
+                .iter()
+                .map(|&(x, y)| Rectangle { x, y, width: HANDLE_SIZE, height: HANDLE_SIZE })
+                .collect();
+            let inner: Vec<Rectangle> = outer
+                .iter()
+                .map(|r| Rectangle {
+                    x: r.x + 1,
+                    y: r.y + 1,
+                    width: r.width - 2,
+                    height: r.height - 2,
+                })
+                .collect();
+            render::fill_rectangles(
+                conn,
+                render::PictOp::OVER,
+                back_picture,
+                render::Color { red: 0x2000, green: 0x2000, blue: 0x2000, alpha: 0xffff },
+                &outer,
+            )
+            .map_err(|e| CaptureError::X11(format!("fill_rectangles handles: {e}")))?;
+            render::fill_rectangles(
+                conn,
+                render::PictOp::OVER,
+                back_picture,
+                render::Color { red: 0xffff, green: 0xffff, blue: 0xffff, alpha: 0xffff },
+                &inner,
+            )
+            .map_err(|e| CaptureError::X11(format!("fill_rectangles handles: {e}")))?;
+        }
+
+        if extras.label
+            && let Some(text) = &ov.text
+        {
+            draw_label(ov, text, (sx, sy, sw_sel, sh_sel))?;
+        }
+    }
+
+    for ruler in extras.rulers {
+        draw_ruler(ov, *ruler)?;
+    }
+
+    if let Some(point) = extras.loupe {
+        draw_loupe(ov, point)?;
+    }
+
+    // 5) Flip: copy_area from back buffer pixmap to window (single
+    //    core-protocol blit — no intermediate states visible)
+    conn.copy_area(back_pixmap, window, flip_gc, 0, 0, 0, 0, sw, sh)
+        .map_err(|e| CaptureError::X11(format!("copy_area flip: {e}")))?;
+
+    conn.flush()
+        .map_err(|e| CaptureError::X11(format!("flush draw: {e}")))?;
+
+    Ok(())
+}
+
+/// A translucent dark box with one line of white text, `pos` being its
+/// top-left corner.
+fn draw_text_box(ov: &Overlay, text: &Text, pos: (i16, i16), s: &[u8]) -> Result<(), CaptureError> {
+    let pad = LABEL_PAD as i16;
+    render::fill_rectangles(
+        ov.conn,
+        render::PictOp::OVER,
+        ov.back_picture,
+        render::Color { red: 0, green: 0, blue: 0, alpha: 0xc000 },
+        &[Rectangle {
+            x: pos.0,
+            y: pos.1,
+            width: text.width(s) + 2 * LABEL_PAD,
+            height: text.height() + 2 * LABEL_PAD,
+        }],
+    )
+    .map_err(|e| CaptureError::X11(format!("fill_rectangles label: {e}")))?;
+
+    // A single TEXTITEM8: length, delta, then the string.
+    let mut item = vec![s.len() as u8, 0];
+    item.extend_from_slice(s);
+    ov.conn
+        .poly_text8(ov.back_pixmap, text.gc, pos.0 + pad, pos.1 + pad + text.ascent, &item)
+        .map_err(|e| CaptureError::X11(format!("poly_text8: {e}")))?;
+    Ok(())
+}
+
+/// "W×H  X,Y" below the selection, or above it when there's no room, or
+/// inside it as a last resort. X,Y are root coordinates.
+fn draw_label(ov: &Overlay, text: &Text, (x, y, w, h): (i16, i16, u16, u16)) -> Result<(), CaptureError> {
+    // The "fixed" font is Latin-1 encoded: 0xd7 is the multiplication sign.
+    let mut s = w.to_string().into_bytes();
+    s.push(0xd7);
+    s.extend(format!("{h}  {},{}", ov.ox as i32 + x as i32, ov.oy as i32 + y as i32).bytes());
+
+    let box_w = (text.width(&s) + 2 * LABEL_PAD) as i16;
+    let box_h = (text.height() + 2 * LABEL_PAD) as i16;
+    let gap = 6;
+    let below = y + h as i16 + gap;
+    let above = y - gap - box_h;
+    let by = if below + box_h <= ov.sh as i16 {
+        below
+    } else if above >= 0 {
+        above
+    } else {
+        y + gap
+    };
+    let bx = x.min(ov.sw as i16 - box_w).max(0);
+    draw_text_box(ov, text, (bx, by), &s)
+}
+
+/// A white line over a darker, wider one so it shows on any background,
+/// with square end marks and a label near its middle: the length, plus the
+/// angle for lines that aren't horizontal or vertical.
+fn draw_ruler(ov: &Overlay, Ruler { from, to }: Ruler) -> Result<(), CaptureError> {
+    for (source, width) in [(ov.dim_picture, 3.0), (ov.border_picture, 1.0)] {
+        render::triangles(
+            ov.conn,
+            render::PictOp::OVER,
+            source,
+            ov.back_picture,
+            0u32,
+            0,
+            0,
+            &line_triangles(from, to, width),
+        )
+        .map_err(|e| CaptureError::X11(format!("triangles ruler: {e}")))?;
+    }
+    let ends: Vec<Rectangle> = [from, to]
+        .iter()
+        .map(|&(x, y)| Rectangle { x: x - 2, y: y - 2, width: 5, height: 5 })
+        .collect();
+    render::fill_rectangles(
+        ov.conn,
+        render::PictOp::OVER,
+        ov.back_picture,
+        render::Color { red: 0xffff, green: 0xffff, blue: 0xffff, alpha: 0xffff },
+        &ends,
+    )
+    .map_err(|e| CaptureError::X11(format!("fill_rectangles ruler ends: {e}")))?;
+
+    let Some(text) = &ov.text else {
+        return Ok(());
+    };
+    let (dx, dy) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+    let mut s = format!("{:.0}px", dx.hypot(dy)).into_bytes();
+    if dx != 0.0 && dy != 0.0 {
+        // Latin-1 0xb0 is the degree sign in the "fixed" font.
+        s.extend(format!("  {:.1}", (-dy).atan2(dx).to_degrees()).bytes());
+        s.push(0xb0);
+    }
+    let box_w = (text.width(&s) + 2 * LABEL_PAD) as i16;
+    let box_h = (text.height() + 2 * LABEL_PAD) as i16;
+    let mid = ((from.0 + to.0) / 2, (from.1 + to.1) / 2);
+    let x = (mid.0 + 8).min(ov.sw as i16 - box_w).max(0);
+    let y = (mid.1 + 8).min(ov.sh as i16 - box_h).max(0);
+    draw_text_box(ov, text, (x, y), &s)
+}
+
+/// Two triangles covering a `width`-pixel line through pixel centres.
+fn line_triangles(from: (i16, i16), to: (i16, i16), width: f64) -> [render::Triangle; 2] {
+    let (x0, y0) = (from.0 as f64 + 0.5, from.1 as f64 + 0.5);
+    let (x1, y1) = (to.0 as f64 + 0.5, to.1 as f64 + 0.5);
+    let len = (x1 - x0).hypot(y1 - y0).max(1.0);
+    // Half-width offset perpendicular to the line.
+    let (nx, ny) = (-(y1 - y0) / len * width / 2.0, (x1 - x0) / len * width / 2.0);
+    let fixed = |x: f64, y: f64| render::Pointfix {
+        x: (x * 65536.0) as i32,
+        y: (y * 65536.0) as i32,
+    };
+    let (a, b) = (fixed(x0 + nx, y0 + ny), fixed(x0 - nx, y0 - ny));
+    let (c, d) = (fixed(x1 + nx, y1 + ny), fixed(x1 - nx, y1 - ny));
+    [
+        render::Triangle { p1: a, p2: b, p3: c },
+        render::Triangle { p1: b, p2: c, p3: d },
+    ]
+}
+
+/// A zoomed view of the pixels around `(px, py)` with a pixel grid, the
+/// centre pixel outlined and its colour printed underneath.
+fn draw_loupe(ov: &Overlay, (px, py): (i16, i16)) -> Result<(), CaptureError> {
+    let conn = ov.conn;
+    let size = LOUPE_CELLS * LOUPE_ZOOM;
+    let strip = ov.text.as_ref().map_or(0, |t| t.height() + 2 * LABEL_PAD);
+
+    // Below-right of the pointer, flipped to whichever side has room.
+    let (sz, total_h) = (size as i16, (size + strip) as i16);
+    let mut lx = px + LOUPE_OFFSET;
+    if lx + sz > ov.sw as i16 {
+        lx = px - LOUPE_OFFSET - sz;
+    }
+    let mut ly = py + LOUPE_OFFSET;
+    if ly + total_h > ov.sh as i16 {
+        ly = py - LOUPE_OFFSET - total_h;
+    }
+    let (lx, ly) = (lx.max(0), ly.max(0));
+
+    // Scale the frozen screen by LOUPE_ZOOM around the pointer: overlay
+    // pixel (i, j) of the loupe samples screen pixel (i, j) / zoom + origin.
+    let one = 1 << 16;
+    let half = (LOUPE_CELLS / 2) as i32;
+    let transform = render::Transform {
+        matrix11: one / LOUPE_ZOOM as i32,
+        matrix12: 0,
+        matrix13: (px as i32 - half) << 16,
+        matrix21: 0,
+        matrix22: one / LOUPE_ZOOM as i32,
+        matrix23: (py as i32 - half) << 16,
+        matrix31: 0,
+        matrix32: 0,
+        matrix33: one,
+    };
+    render::set_picture_transform(conn, ov.loupe_picture, transform)
+        .map_err(|e| CaptureError::X11(format!("set_picture_transform: {e}")))?;
+    render::composite(
+        conn,
+        render::PictOp::SRC,
+        ov.loupe_picture,
+        0u32,
+        ov.back_picture,
+        0, 0,
+        0, 0,
+        lx, ly,
+        size, size,
+    )
+    .map_err(|e| CaptureError::X11(format!("composite loupe: {e}")))?;
+
+    // Grid lines between the magnified pixels.
+    let zoom = LOUPE_ZOOM as i16;
+    let mut grid = Vec::with_capacity(2 * LOUPE_CELLS as usize);
+    for k in 1..LOUPE_CELLS as i16 {
+        grid.push(Rectangle { x: lx + k * zoom, y: ly, width: 1, height: size });
+        grid.push(Rectangle { x: lx, y: ly + k * zoom, width: size, height: 1 });
+    }
+    render::fill_rectangles(
+        conn,
+        render::PictOp::OVER,
+        ov.back_picture,
+        render::Color { red: 0, green: 0, blue: 0, alpha: 0x4000 },
+        &grid,
+    )
+    .map_err(|e| CaptureError::X11(format!("fill_rectangles grid: {e}")))?;
+
+    // Outline the centre pixel and the loupe itself.
+    let c = half as i16 * zoom;
+    let outline = |x: i16, y: i16, w: u16, h: u16| {
+        [
+            Rectangle { x, y, width: w, height: 1 },
+            Rectangle { x, y: y + h as i16 - 1, width: w, height: 1 },
+            Rectangle { x, y, width: 1, height: h },
+            Rectangle { x: x + w as i16 - 1, y, width: 1, height: h },
+        ]
+    };
+    let mut lines = outline(lx + c, ly + c, LOUPE_ZOOM + 1, LOUPE_ZOOM + 1).to_vec();
+    lines.extend(outline(lx - 1, ly - 1, size + 2, size + 2));
+    render::fill_rectangles(
+        conn,
+        render::PictOp::OVER,
+        ov.back_picture,
+        render::Color { red: 0xffff, green: 0xffff, blue: 0xffff, alpha: 0xffff },
+        &lines,
+    )
+    .map_err(|e| CaptureError::X11(format!("fill_rectangles loupe outline: {e}")))?;
+
+    // Colour under the crosshair, read back from the frozen screen.
+    if let Some(text) = &ov.text
+        && px >= 0
+        && py >= 0
+        && px < ov.sw as i16
+        && py < ov.sh as i16
+    {
+        let pixel = conn
+            .get_image(ImageFormat::Z_PIXMAP, ov.screen_pixmap, px, py, 1, 1, !0)
+            .map_err(|e| CaptureError::X11(format!("get_image pixel: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("get_image pixel reply: {e}")))?;
+        if let Ok(pixel) = ov.format.to_rgba(&pixel.data, 1, 1) {
+            let [r, g, b, _] = pixel.get_pixel(0, 0).0;
+            let hex = format!("#{r:02X}{g:02X}{b:02X}");
+            draw_text_box(ov, text, (lx, ly + sz), hex.as_bytes())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Colour picker on the frozen-screen overlay.
+//!
+//! The magnifier follows the pointer; a left click (or the confirm key)
+//! picks the pixel under it, averaged over a `sample`-pixel square when
+//! that's more than one. Arrow keys move the pointer a pixel at a time.
+
+use super::keys::{Action, KeyBindings};
+use super::overlay::{Extras, Overlay, draw_overlay};
+use super::{CaptureError, CaptureOptions, Region, connect};
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::ConnectionExt as _;
+
+/// Pick a colour. Returns the point picked in root coordinates and the
+/// (averaged) colour there.
+pub(super) fn pick(
+    display_bounds: Option<Region>,
+    sample: u32,
+    opts: &CaptureOptions,
+) -> Result<((i32, i32), [u8; 3]), CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let screen = &conn.setup().roots[screen_num].clone();
+    let keys = KeyBindings::load(&conn, &opts.selector)?;
+    let mut overlay = Overlay::new(&conn, screen, display_bounds)?;
+
+    let pointer = conn
+        .query_pointer(overlay.window)
+        .map_err(|e| CaptureError::X11(format!("query_pointer: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("query_pointer reply: {e}")))?;
+    let mut point = (pointer.win_x, pointer.win_y);
+
+    loop {
+        let redraw = match overlay.next_event()? {
+            Event::Expose(_) => true,
+            Event::MotionNotify(ev) => {
+                point = (ev.event_x, ev.event_y);
+                true
+            }
+            Event::ButtonPress(ev) if ev.detail == 1 => {
+                point = (ev.event_x, ev.event_y);
+                return pick_at(&overlay, point, sample);
+            }
+            Event::KeyPress(ev) => match keys.action(ev.detail) {
+                Some(Action::Cancel) => return Err(CaptureError::SelectionCancelled),
+                Some(Action::Confirm) => return pick_at(&overlay, point, sample),
+                Some(Action::Left) => nudge(&conn, -1, 0)?,
+                Some(Action::Right) => nudge(&conn, 1, 0)?,
+                Some(Action::Up) => nudge(&conn, 0, -1)?,
+                Some(Action::Down) => nudge(&conn, 0, 1)?,
+                _ => false,
+            },
+            _ => false,
+        };
+
+        if redraw {
+            let extras = Extras { loupe: Some(point), ..Extras::default() };
+            draw_overlay(&overlay, sample_rect(&overlay, point, sample), extras)?;
+        }
+    }
+}
+
+/// The `sample`-pixel square centred on `point`, clipped to the overlay,
+/// in overlay coordinates. Shown undimmed while picking.
+fn sample_rect(ov: &Overlay, (x, y): (i16, i16), sample: u32) -> Option<(i16, i16, u16, u16)> {
+    let half = (sample / 2) as i32;
+    ov.clip(Region {
+        x: ov.ox as i32 + x as i32 - half,
+        y: ov.oy as i32 + y as i32 - half,
+        width: sample.max(1),
+        height: sample.max(1),
+    })
+}
+
+fn pick_at(
+    ov: &Overlay,
+    point: (i16, i16),
+    sample: u32,
+) -> Result<((i32, i32), [u8; 3]), CaptureError> {
+    let (x, y, w, h) = sample_rect(ov, point, sample)
+        .ok_or_else(|| CaptureError::X11("picked point is off-screen".to_string()))?;
+    let pixels = ov.extract(x, y, w, h)?;
+    let n = pixels.pixels().len() as u64;
+    let mut sum = [0u64; 3];
+    for px in pixels.pixels() {
+        for c in 0..3 {
+            sum[c] += px[c] as u64;
+        }
+    }
+    let avg = sum.map(|s| ((s + n / 2) / n) as u8);
+    Ok(((ov.ox as i32 + point.0 as i32, ov.oy as i32 + point.1 as i32), avg))
+}
+
+/// Move the pointer by one step; the resulting motion event redraws.
+fn nudge(conn: &impl Connection, dx: i16, dy: i16) -> Result<bool, CaptureError> {
+    conn.warp_pointer(x11rb::NONE, x11rb::NONE, 0, 0, 0, 0, dx, dy)
+        .map_err(|e| CaptureError::X11(format!("warp_pointer: {e}")))?;
+    conn.flush()
+        .map_err(|e| CaptureError::X11(format!("flush: {e}")))?;
+    Ok(false)
+}
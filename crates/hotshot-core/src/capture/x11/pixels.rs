@@ -0,0 +1,390 @@
+//! Decoding Z_PIXMAP image data into RGBA.
+//!
+//! What `get_image` returns depends on the server and the drawable: the
+//! pixmap format for its depth sets the bits per pixel and scanline padding,
+//! the connection setup sets the byte order, and the visual says where red,
+//! green and blue live in a pixel (10-bit channels on depth 30, 5/6/5 on
+//! depth 16). Indexed visuals such as 8-bit PseudoColor look their pixels up
+//! in the colormap instead.
+
+use super::CaptureError;
+use image::RgbaImage;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+
+/// Largest colormap we'll read for an indexed visual.
+const MAX_PALETTE_BITS: u8 = 12;
+
+/// Layout of Z_PIXMAP data for one depth and visual.
+pub(super) struct PixelFormat {
+    depth: u8,
+    bits_per_pixel: u8,
+    /// Rows are padded to a multiple of this many bits.
+    scanline_pad: u8,
+    /// Most significant byte first (image byte order).
+    msb_first: bool,
+    /// Leftmost pixel in the most significant bit, for 1 bit per pixel.
+    bit_msb_first: bool,
+    colors: Colors,
+}
+
+enum Colors {
+    /// TrueColor and DirectColor: each channel is a bit field of the pixel.
+    Masks {
+        red: Channel,
+        green: Channel,
+        blue: Channel,
+        /// Premultiplied alpha in the bits the visual leaves over (ARGB windows).
+        alpha: Option<Channel>,
+    },
+    /// Indexed visuals: the pixel is a colormap entry.
+    Palette(Vec<[u8; 3]>),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    mask: u32,
+    shift: u32,
+    max: u32,
+}
+
+impl Channel {
+    fn new(mask: u32) -> Self {
+        let shift = if mask == 0 { 0 } else { mask.trailing_zeros() };
+        Self { mask, shift, max: mask >> shift }
+    }
+
+    /// The channel scaled to 8 bits.
+    fn get(self, pixel: u32) -> u8 {
+        let v = (pixel & self.mask) >> self.shift;
+        match self.max {
+            0 => 0,
+            255 => v as u8,
+            max => ((v as u64 * 255 + max as u64 / 2) / max as u64) as u8,
+        }
+    }
+}
+
+impl PixelFormat {
+    /// The format of drawables with `depth` using `visual`. `colormap` is only
+    /// read for indexed visuals.
+    pub(super) fn new(
+        conn: &impl Connection,
+        depth: u8,
+        visual: Visualid,
+        colormap: Colormap,
+    ) -> Result<Self, CaptureError> {
+        let setup = conn.setup();
+        let format = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == depth)
+            .ok_or_else(|| CaptureError::X11(format!("no pixmap format for depth {depth}")))?;
+        if !matches!(format.bits_per_pixel, 1 | 4 | 8 | 16 | 24 | 32) {
+            return Err(CaptureError::X11(format!(
+                "unsupported pixel format: {} bits per pixel",
+                format.bits_per_pixel
+            )));
+        }
+        let visual = setup
+            .roots
+            .iter()
+            .flat_map(|s| &s.allowed_depths)
+            .flat_map(|d| &d.visuals)
+            .find(|v| v.visual_id == visual)
+            .ok_or_else(|| CaptureError::X11(format!("unknown visual {visual:#x}")))?;
+
+        let colors = match visual.class {
+            VisualClass::TRUE_COLOR | VisualClass::DIRECT_COLOR => Colors::Masks {
+                red: Channel::new(visual.red_mask),
+                green: Channel::new(visual.green_mask),
+                blue: Channel::new(visual.blue_mask),
+                alpha: None,
+            },
+            _ => Colors::Palette(read_palette(conn, colormap, depth)?),
+        };
+
+        Ok(Self {
+            depth,
+            bits_per_pixel: format.bits_per_pixel,
+            scanline_pad: format.scanline_pad,
+            msb_first: setup.image_byte_order == ImageOrder::MSB_FIRST,
+            bit_msb_first: setup.bitmap_format_bit_order == ImageOrder::MSB_FIRST,
+            colors,
+        })
+    }
+
+    /// The format of the root window and pixmaps created at root depth.
+    pub(super) fn root(conn: &impl Connection, screen: &Screen) -> Result<Self, CaptureError> {
+        Self::new(conn, screen.root_depth, screen.root_visual, screen.default_colormap)
+    }
+
+    /// Keep the premultiplied alpha in the depth bits the visual doesn't use
+    /// for colour, as ARGB (depth 32) windows have.
+    pub(super) fn with_alpha(mut self) -> Self {
+        if let Colors::Masks { red, green, blue, alpha } = &mut self.colors {
+            let depth_mask = if self.depth >= 32 { u32::MAX } else { (1 << self.depth) - 1 };
+            let rest = depth_mask & !(red.mask | green.mask | blue.mask);
+            *alpha = (rest != 0).then(|| Channel::new(rest));
+        }
+        self
+    }
+
+    /// Bytes in one row of a `width`-pixel image, padding included.
+    pub(super) fn row_bytes(&self, width: u16) -> usize {
+        let pad = self.scanline_pad as usize;
+        (width as usize * self.bits_per_pixel as usize).div_ceil(pad) * pad / 8
+    }
+
+    /// Bytes in a whole `width`x`height` image.
+    pub(super) fn image_size(&self, width: u16, height: u16) -> usize {
+        self.row_bytes(width) * height as usize
+    }
+
+    /// 32-bit little-endian BGRX, by far the common case, converts with a
+    /// byte shuffle.
+    fn is_bgrx(&self) -> bool {
+        matches!(
+            self.colors,
+            Colors::Masks { red, green, blue, alpha: None }
+                if red.mask == 0xff0000 && green.mask == 0xff00 && blue.mask == 0xff
+        ) && self.bits_per_pixel == 32
+            && !self.msb_first
+    }
+
+    /// The `x`th pixel value in `row`.
+    fn pixel(&self, row: &[u8], x: usize) -> u32 {
+        match self.bits_per_pixel {
+            1 => {
+                let bit = if self.bit_msb_first { 7 - x % 8 } else { x % 8 };
+                (row[x / 8] >> bit) as u32 & 1
+            }
+            4 => {
+                let high = x.is_multiple_of(2) == self.msb_first;
+                let byte = row[x / 2];
+                (if high { byte >> 4 } else { byte & 0x0f }) as u32
+            }
+            8 => row[x] as u32,
+            16 => {
+                let b = [row[2 * x], row[2 * x + 1]];
+                (if self.msb_first { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) }) as u32
+            }
+            24 => {
+                let b = &row[3 * x..3 * x + 3];
+                let (hi, lo) = if self.msb_first { (b[0], b[2]) } else { (b[2], b[0]) };
+                (hi as u32) << 16 | (b[1] as u32) << 8 | lo as u32
+            }
+            _ => {
+                let b = [row[4 * x], row[4 * x + 1], row[4 * x + 2], row[4 * x + 3]];
+                if self.msb_first { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) }
+            }
+        }
+    }
+
+    /// Decode a `width`x`height` Z_PIXMAP image into RGBA.
+    pub(super) fn to_rgba(
+        &self,
+        data: &[u8],
+        width: u16,
+        height: u16,
+    ) -> Result<RgbaImage, CaptureError> {
+        let stride = self.row_bytes(width);
+        let (w, h) = (width as usize, height as usize);
+        if data.len() < stride * h {
+            return Err(CaptureError::X11(format!(
+                "short image data: got {} bytes, expected {}",
+                data.len(),
+                stride * h
+            )));
+        }
+
+        let mut out = Vec::with_capacity(w * h * 4);
+        let rows = data.chunks(stride.max(1)).take(h);
+        if self.is_bgrx() {
+            for row in rows {
+                for px in row[..w * 4].chunks_exact(4) {
+                    out.extend_from_slice(&[px[2], px[1], px[0], 255]);
+                }
+            }
+        } else {
+            let index_mask = if self.depth >= 32 { u32::MAX } else { (1 << self.depth) - 1 };
+            for row in rows {
+                for x in 0..w {
+                    let pixel = self.pixel(row, x);
+                    match &self.colors {
+                        Colors::Masks { red, green, blue, alpha } => {
+                            let rgb = [red.get(pixel), green.get(pixel), blue.get(pixel)];
+                            let a = alpha.map_or(255, |a| a.get(pixel));
+                            out.extend_from_slice(&unpremultiply(rgb, a));
+                        }
+                        Colors::Palette(palette) => {
+                            let [r, g, b] = palette
+                                .get((pixel & index_mask) as usize)
+                                .copied()
+                                .unwrap_or_default();
+                            out.extend_from_slice(&[r, g, b, 255]);
+                        }
+                    }
+                }
+            }
+        }
+
+        RgbaImage::from_raw(width as u32, height as u32, out)
+            .ok_or_else(|| CaptureError::X11("failed to create image from pixel data".to_string()))
+    }
+}
+
+fn unpremultiply([r, g, b]: [u8; 3], a: u8) -> [u8; 4] {
+    if a == 0 || a == 255 {
+        return [r, g, b, a];
+    }
+    let a32 = a as u32;
+    let c = |v: u8| ((v as u32 * 255 + a32 / 2) / a32).min(255) as u8;
+    [c(r), c(g), c(b), a]
+}
+
+/// Every entry of an indexed visual's colormap, scaled to 8 bits.
+fn read_palette(
+    conn: &impl Connection,
+    colormap: Colormap,
+    depth: u8,
+) -> Result<Vec<[u8; 3]>, CaptureError> {
+    if depth > MAX_PALETTE_BITS {
+        return Err(CaptureError::X11(format!(
+            "unsupported indexed visual of depth {depth}"
+        )));
+    }
+    let pixels: Vec<u32> = (0..1u32 << depth).collect();
+    let reply = conn
+        .query_colors(colormap, &pixels)
+        .map_err(|e| CaptureError::X11(format!("query_colors: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("query_colors reply: {e}")))?;
+    Ok(reply
+        .colors
+        .iter()
+        .map(|c| [(c.red >> 8) as u8, (c.green >> 8) as u8, (c.blue >> 8) as u8])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn true_color(depth: u8, bits_per_pixel: u8, msb_first: bool, masks: [u32; 3]) -> PixelFormat {
+        PixelFormat {
+            depth,
+            bits_per_pixel,
+            scanline_pad: 32,
+            msb_first,
+            bit_msb_first: msb_first,
+            colors: Colors::Masks {
+                red: Channel::new(masks[0]),
+                green: Channel::new(masks[1]),
+                blue: Channel::new(masks[2]),
+                alpha: None,
+            },
+        }
+    }
+
+    fn pixels(image: &RgbaImage) -> Vec<[u8; 4]> {
+        image.pixels().map(|p| p.0).collect()
+    }
+
+    const RGB565: [u32; 3] = [0xf800, 0x07e0, 0x001f];
+    /// Red, green, blue in the first row; white, black and mid grey in the
+    /// second.
+    const RGB565_PIXELS: [u16; 6] = [0xf800, 0x07e0, 0x001f, 0xffff, 0x0000, 0x8410];
+    const RGB565_DECODED: [[u8; 4]; 6] = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 255, 255],
+        [0, 0, 0, 255],
+        [132, 130, 132, 255],
+    ];
+
+    /// Three 16-bit pixels per row, padded from 6 to 8 bytes.
+    fn rgb565_data(to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for row in RGB565_PIXELS.chunks(3) {
+            for &p in row {
+                data.extend_from_slice(&to_bytes(p));
+            }
+            data.extend_from_slice(&[0xaa, 0xaa]);
+        }
+        data
+    }
+
+    #[test]
+    fn rgb565_lsb_first() {
+        let format = true_color(16, 16, false, RGB565);
+        assert_eq!(format.row_bytes(3), 8);
+        let image = format.to_rgba(&rgb565_data(u16::to_le_bytes), 3, 2).unwrap();
+        assert_eq!(pixels(&image), RGB565_DECODED);
+    }
+
+    #[test]
+    fn rgb565_msb_first() {
+        let format = true_color(16, 16, true, RGB565);
+        let image = format.to_rgba(&rgb565_data(u16::to_be_bytes), 3, 2).unwrap();
+        assert_eq!(pixels(&image), RGB565_DECODED);
+    }
+
+    #[test]
+    fn depth_30_ten_bit_channels() {
+        let masks = [0x3ff0_0000, 0x000f_fc00, 0x0000_03ff];
+        let format = true_color(30, 32, false, masks).with_alpha();
+        let data: Vec<u8> = [
+            0x3ff0_0000u32,
+            // Half green; the two bits above the depth are not alpha.
+            0xc000_0000 | 512 << 10,
+            0x0000_03ff,
+        ]
+        .iter()
+        .flat_map(|p| p.to_le_bytes())
+        .collect();
+        let image = format.to_rgba(&data, 3, 1).unwrap();
+        assert_eq!(pixels(&image), [[255, 0, 0, 255], [0, 128, 0, 255], [0, 0, 255, 255]]);
+    }
+
+    #[test]
+    fn packed_24_bits_with_padding() {
+        let format = true_color(24, 24, false, [0xff0000, 0x00ff00, 0x0000ff]);
+        assert_eq!(format.row_bytes(2), 8);
+        let data = [
+            0x33, 0x22, 0x11, 0x66, 0x55, 0x44, 0xaa, 0xaa, //
+            0xcc, 0xbb, 0xaa, 0x00, 0xff, 0x00, 0xaa, 0xaa,
+        ];
+        let image = format.to_rgba(&data, 2, 2).unwrap();
+        let expected = [
+            [0x11, 0x22, 0x33, 255],
+            [0x44, 0x55, 0x66, 255],
+            [0xaa, 0xbb, 0xcc, 255],
+            [0x00, 0xff, 0x00, 255],
+        ];
+        assert_eq!(pixels(&image), expected);
+    }
+
+    #[test]
+    fn palette_lookup() {
+        let format = PixelFormat {
+            depth: 8,
+            bits_per_pixel: 8,
+            scanline_pad: 32,
+            msb_first: false,
+            bit_msb_first: false,
+            colors: Colors::Palette(vec![[0, 0, 0], [255, 0, 0], [10, 20, 30]]),
+        };
+        // Three pixels padded to four bytes; index 7 is past the colormap.
+        let data = [2, 1, 7, 0xaa];
+        let image = format.to_rgba(&data, 3, 1).unwrap();
+        assert_eq!(pixels(&image), [[10, 20, 30, 255], [255, 0, 0, 255], [0, 0, 0, 255]]);
+    }
+
+    #[test]
+    fn short_data_is_an_error() {
+        let format = true_color(24, 32, false, [0xff0000, 0x00ff00, 0x0000ff]);
+        assert!(format.to_rgba(&[0; 7], 2, 1).is_err());
+    }
+}
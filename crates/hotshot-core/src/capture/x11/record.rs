@@ -0,0 +1,226 @@
+//! Grabbing frames for recordings.
+//!
+//! The area is picked once with the screenshot selectors; after that every
+//! frame is a plain read of that rectangle off the root window, so windows
+//! moving over it show up as they would on screen.
+
+use super::{
+    CaptureError, CaptureMode, CaptureOptions, Region, active_window, clip_to_screen, connect,
+    cursor, region, windows,
+};
+use crate::recording::{RecordControl, RecordOptions};
+use image::RgbaImage;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+
+/// The root-window rectangle a recording of `mode` covers.
+pub(super) fn area(
+    mode: &CaptureMode,
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Region, CaptureError> {
+    let rect = match mode {
+        CaptureMode::Fullscreen => match display_bounds {
+            Some(bounds) => bounds,
+            None => {
+                let (conn, screen_num) = connect()?;
+                let screen = &conn.setup().roots[screen_num];
+                Region {
+                    x: 0,
+                    y: 0,
+                    width: screen.width_in_pixels as u32,
+                    height: screen.height_in_pixels as u32,
+                }
+            }
+        },
+        CaptureMode::Region(region) => *region,
+        CaptureMode::RegionInteractive => origin_rect(region::select(display_bounds, opts)?),
+        CaptureMode::WindowInteractive => {
+            origin_rect(super::capture_window_interactive(display_bounds, opts)?)
+        }
+        CaptureMode::ActiveWindow => {
+            let (conn, screen_num) = connect()?;
+            let root = conn.setup().roots[screen_num].root;
+            let window = active_window(&conn, root)?;
+            windows::capture_rect(&conn, root, window, opts)?
+        }
+        CaptureMode::Window(selector) => {
+            let list = super::list_windows()?;
+            let window = super::super::find_window(&list, selector)?;
+            let (conn, screen_num) = connect()?;
+            let root = conn.setup().roots[screen_num].root;
+            windows::capture_rect(&conn, root, window.id, opts)?
+        }
+        CaptureMode::Scroll(_) => {
+            return Err(CaptureError::Other("scroll capture can't be recorded".to_string()));
+        }
+    };
+
+    let (conn, screen_num) = connect()?;
+    clip_to_screen(rect, &conn.setup().roots[screen_num])
+        .ok_or_else(|| CaptureError::X11("recording area is off-screen".to_string()))
+}
+
+/// The rectangle a selector's capture came from.
+fn origin_rect((image, (x, y)): super::Captured) -> Region {
+    Region { x, y, width: image.width(), height: image.height() }
+}
+
+/// Grab frames of `area` at `rec.fps` until `max_frames` have been taken
+/// or `control` stops it, passing each to `sink` with how long it stays on
+/// screen. Returns the total of those delays, the recording's playback
+/// length.
+///
+/// Frames are handed over one behind, so each delay is the real gap to the
+/// next grab rather than the nominal 1/fps; a slow grab or encode stretches
+/// the frame before it instead of speeding up the playback. Time spent
+/// paused is left out.
+pub(super) fn record(
+    area: Region,
+    rec: &RecordOptions,
+    max_frames: u32,
+    mut include_cursor: bool,
+    control: &RecordControl,
+    mut sink: impl FnMut(RgbaImage, Duration) -> Result<(), CaptureError>,
+) -> Result<Duration, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let screen = conn.setup().roots[screen_num].clone();
+    let format = super::PixelFormat::root(&conn, &screen)?;
+    let interval = Duration::from_secs_f64(1.0 / rec.fps as f64);
+    let mut clicks = ClickHighlight::default();
+
+    let mut start = Instant::now();
+    let mut pending: Option<(RgbaImage, Instant)> = None;
+    let mut total = Duration::ZERO;
+    for n in 0..max_frames {
+        // Always keep the first frame, so there's something to encode.
+        if n > 0 && wait_while_paused(control, &mut start, &mut pending) {
+            break;
+        }
+        let deadline = start + interval * n;
+        if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+
+        let grabbed_at = Instant::now();
+        let mut image = super::get_image(
+            &conn,
+            screen.root,
+            &format,
+            area.x as i16,
+            area.y as i16,
+            area.width as u16,
+            area.height as u16,
+        )?;
+        if rec.highlight_clicks {
+            clicks.draw(&conn, screen.root, &mut image, (area.x, area.y))?;
+        }
+        if include_cursor {
+            match cursor::get_cursor_image(&conn) {
+                Ok(cursor) => _ = cursor.draw_onto(&mut image, (area.x, area.y)),
+                Err(e) => {
+                    eprintln!("warning: recording without the cursor: {e}");
+                    include_cursor = false;
+                }
+            }
+        }
+
+        if let Some((frame, at)) = pending.replace((image, grabbed_at)) {
+            total += grabbed_at - at;
+            sink(frame, grabbed_at - at)?;
+        }
+    }
+
+    if let Some((frame, _)) = pending {
+        total += interval;
+        sink(frame, interval)?;
+    }
+    Ok(total)
+}
+
+/// Block while `control` is paused, then shift the frame schedule and the
+/// held-back frame past the pause so it doesn't count as recorded time.
+/// Returns true once the recording should stop.
+fn wait_while_paused(
+    control: &RecordControl,
+    start: &mut Instant,
+    pending: &mut Option<(RgbaImage, Instant)>,
+) -> bool {
+    if !control.is_paused() {
+        return control.is_stopped();
+    }
+    let paused_at = Instant::now();
+    while control.is_paused() && !control.is_stopped() {
+        std::thread::sleep(PAUSE_POLL);
+    }
+    let paused = paused_at.elapsed();
+    *start += paused;
+    if let Some((_, at)) = pending {
+        *at += paused;
+    }
+    control.is_stopped()
+}
+
+/// How often a paused recording checks whether it's been resumed.
+const PAUSE_POLL: Duration = Duration::from_millis(20);
+/// Ring drawn around the pointer for a click.
+const CLICK_RADIUS: f32 = 18.0;
+const CLICK_WIDTH: f32 = 3.0;
+const CLICK_COLOR: [u8; 3] = [255, 200, 0];
+/// How long the ring stays after the button is released, so quick clicks
+/// between two frames still show up.
+const CLICK_LINGER: Duration = Duration::from_millis(250);
+
+/// Draws a ring at the pointer while a mouse button is held.
+#[derive(Default)]
+struct ClickHighlight {
+    last_down: Option<Instant>,
+}
+
+impl ClickHighlight {
+    fn draw(
+        &mut self,
+        conn: &impl Connection,
+        root: Window,
+        image: &mut RgbaImage,
+        origin: (i32, i32),
+    ) -> Result<(), CaptureError> {
+        let pointer = conn
+            .query_pointer(root)
+            .map_err(|e| CaptureError::X11(format!("query_pointer: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("query_pointer reply: {e}")))?;
+        let buttons = KeyButMask::BUTTON1 | KeyButMask::BUTTON2 | KeyButMask::BUTTON3;
+        if u16::from(pointer.mask) & u16::from(buttons) != 0 {
+            self.last_down = Some(Instant::now());
+        }
+        if self.last_down.is_none_or(|t| t.elapsed() > CLICK_LINGER) {
+            return Ok(());
+        }
+
+        let cx = (pointer.root_x as i32 - origin.0) as f32;
+        let cy = (pointer.root_y as i32 - origin.1) as f32;
+        let reach = (CLICK_RADIUS + CLICK_WIDTH).ceil() as i32;
+        for y in cy as i32 - reach..=cy as i32 + reach {
+            for x in cx as i32 - reach..=cx as i32 + reach {
+                if x < 0 || y < 0 || x >= image.width() as i32 || y >= image.height() as i32 {
+                    continue;
+                }
+                let d = ((x as f32 - cx).powi(2) + (y as f32 - cy).powi(2)).sqrt();
+                // Antialiased edge: full strength within the ring, fading
+                // over one pixel outside it.
+                let coverage = (CLICK_WIDTH / 2.0 + 0.5 - (d - CLICK_RADIUS).abs()).clamp(0.0, 1.0);
+                if coverage == 0.0 {
+                    continue;
+                }
+                let alpha = coverage * 0.8;
+                let px = image.get_pixel_mut(x as u32, y as u32);
+                for c in 0..3 {
+                    px[c] = (px[c] as f32 * (1.0 - alpha) + CLICK_COLOR[c] as f32 * alpha) as u8;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+//! Interactive region selector.
+//!
+//! Dragging draws a selection; releasing the button leaves it on screen
+//! for adjustment rather than capturing straight away. From there the
+//! handles resize it, arrows nudge it (Shift+arrows resize), dragging with
+//! the move key held moves it, and right-click or the restart key starts
+//! over. The confirm key captures. Keys come from the `[selector]` config.
+//!
+//! The selection's size and position are shown next to it, and with
+//! `magnifier` enabled a loupe follows the pointer for pixel-exact edges.
+//!
+//! With `snap` enabled, dragged edges jump onto nearby window, monitor and
+//! earlier-selection edges; holding Ctrl turns that off for the moment.
+
+use super::keys::{Action, KeyBindings};
+use super::overlay::{Extras, HANDLE_SIZE, Overlay, draw_overlay, handle_positions};
+use super::snap::Snap;
+use super::{CaptureError, CaptureOptions, Captured, Monitor, Region, connect, windows};
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+
+/// A selection in overlay coordinates: x, y, width, height.
+type Selection = (i16, i16, u16, u16);
+
+/// Which edges of the selection follow the pointer while dragging a handle.
+#[derive(Debug, Clone, Copy)]
+struct Edges {
+    left: bool,
+    right: bool,
+    top: bool,
+    bottom: bool,
+}
+
+const fn edges(left: bool, right: bool, top: bool, bottom: bool) -> Edges {
+    Edges { left, right, top, bottom }
+}
+
+/// Edges moved by each handle, in [`handle_positions`] order.
+const HANDLE_EDGES: [Edges; 8] = [
+    edges(true, false, true, false),  // NW
+    edges(false, false, true, false), // N
+    edges(false, true, true, false),  // NE
+    edges(false, true, false, false), // E
+    edges(false, true, false, true),  // SE
+    edges(false, false, false, true), // S
+    edges(true, false, false, true),  // SW
+    edges(true, false, false, false), // W
+];
+
+/// What the left button is currently doing.
+enum Drag {
+    /// Drawing a new selection from `anchor`.
+    Draw { anchor: (i16, i16) },
+    /// Moving the selection; `grab` is the pointer's offset from its corner.
+    Move { grab: (i16, i16) },
+    /// Dragging a resize handle.
+    Resize { edges: Edges },
+}
+
+/// Compute normalised selection rectangle from drag start/current positions,
+/// with each side snapped to a nearby edge when `snap` is given.
+fn compute_selection(
+    x0: i16,
+    y0: i16,
+    x1: i16,
+    y1: i16,
+    sw: u16,
+    sh: u16,
+    snap: Option<&Snap>,
+) -> Selection {
+    let (x0, y0, x1, y1) = match snap {
+        Some(snap) => {
+            let rows = (y0.min(y1), y0.max(y1));
+            let cols = (x0.min(x1), x0.max(x1));
+            (snap.x(x0, rows), snap.y(y0, cols), snap.x(x1, rows), snap.y(y1, cols))
+        }
+        None => (x0, y0, x1, y1),
+    };
+    let lx = x0.min(x1).max(0);
+    let ly = y0.min(y1).max(0);
+    let rx = x0.max(x1).min(sw as i16);
+    let ry = y0.max(y1).min(sh as i16);
+    let w = (rx - lx).max(0) as u16;
+    let h = (ry - ly).max(0) as u16;
+    (lx, ly, w, h)
+}
+
+pub(super) fn select(
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Captured, CaptureError> {
+    let (conn, screen_num) = connect()?;
+    let screen = &conn.setup().roots[screen_num].clone();
+    let keys = KeyBindings::load(&conn, &opts.selector)?;
+    // Collected before the overlay goes up; the windows won't move meanwhile.
+    let snap = match opts.selector.snap {
+        true => Some(snap_targets(&conn, screen.root, opts)?),
+        false => None,
+    };
+
+    let mut overlay = Overlay::new(&conn, screen, display_bounds)?;
+    let (ox, oy, sw, sh) = (overlay.ox, overlay.oy, overlay.sw, overlay.sh);
+    let snap = snap.map(|rects| Snap::new(&rects, ox, oy, opts.selector.snap_threshold));
+
+    let mut sel: Option<Selection> = None;
+    let mut drag: Option<Drag> = None;
+    let mut pointer: (i16, i16) = (0, 0);
+    // The magnifier follows the pointer once we know where it is.
+    let mut hovering = false;
+    let mut move_held = false;
+
+    loop {
+        let redraw = match overlay.next_event()? {
+            Event::Expose(_) => true,
+            Event::ButtonPress(ev) if ev.detail == 1 => {
+                pointer = (ev.event_x, ev.event_y);
+                let handle = sel.and_then(|s| handle_at(s, pointer));
+                drag = Some(match (sel, handle) {
+                    (Some((x, y, _, _)), _) if move_held => Drag::Move {
+                        grab: (pointer.0 - x, pointer.1 - y),
+                    },
+                    (_, Some(edges)) => Drag::Resize { edges },
+                    _ => {
+                        sel = None;
+                        Drag::Draw { anchor: pointer }
+                    }
+                });
+                true
+            }
+            Event::ButtonPress(ev) if ev.detail == 3 => {
+                sel = None;
+                drag = None;
+                true
+            }
+            Event::MotionNotify(ev) => {
+                let prev = pointer;
+                pointer = (ev.event_x, ev.event_y);
+                hovering = true;
+                let snap = snap.as_ref().filter(|_| !ev.state.contains(KeyButMask::CONTROL));
+                match (&mut drag, sel) {
+                    (Some(Drag::Draw { anchor }), _) => {
+                        // Holding the move key while drawing carries the
+                        // whole rectangle along with the pointer.
+                        if move_held {
+                            anchor.0 += pointer.0 - prev.0;
+                            anchor.1 += pointer.1 - prev.1;
+                        }
+                        sel = Some(compute_selection(
+                            anchor.0, anchor.1, pointer.0, pointer.1, sw, sh, snap,
+                        ));
+                        true
+                    }
+                    (Some(Drag::Move { grab }), Some((_, _, w, h))) => {
+                        let mut x = pointer.0 - grab.0;
+                        let mut y = pointer.1 - grab.1;
+                        if let Some(snap) = snap {
+                            let (cols, rows) = ((x, x + w as i16), (y, y + h as i16));
+                            x += snap.shift_x(cols, rows);
+                            y += snap.shift_y(rows, cols);
+                        }
+                        let x = x.clamp(0, (sw - w) as i16);
+                        let y = y.clamp(0, (sh - h) as i16);
+                        sel = Some((x, y, w, h));
+                        true
+                    }
+                    (Some(Drag::Resize { edges }), Some(s)) => {
+                        sel = Some(resize(s, edges, pointer, sw, sh, snap));
+                        true
+                    }
+                    _ => opts.selector.magnifier,
+                }
+            }
+            Event::ButtonRelease(ev) if ev.detail == 1 => {
+                drag = None;
+                if sel.is_some_and(|(_, _, w, h)| w == 0 || h == 0) {
+                    sel = None;
+                }
+                true
+            }
+            Event::KeyPress(ev) => match keys.action(ev.detail) {
+                Some(Action::Cancel) => return Err(CaptureError::SelectionCancelled),
+                Some(Action::Confirm) => {
+                    if let Some((x, y, w, h)) = sel
+                        && w > 0
+                        && h > 0
+                    {
+                        let img = overlay.extract(x, y, w, h)?;
+                        return Ok((img, (ox as i32 + x as i32, oy as i32 + y as i32)));
+                    }
+                    false
+                }
+                Some(Action::Restart) => {
+                    sel = None;
+                    drag = None;
+                    true
+                }
+                Some(Action::Move) => {
+                    move_held = true;
+                    false
+                }
+                Some(arrow) => match sel {
+                    Some(s) if drag.is_none() => {
+                        let shift = ev.state.contains(KeyButMask::SHIFT);
+                        sel = Some(nudge(s, arrow, shift, sw, sh));
+                        true
+                    }
+                    _ => false,
+                },
+                None => false,
+            },
+            Event::KeyRelease(ev) => {
+                if keys.action(ev.detail) == Some(Action::Move) {
+                    move_held = false;
+                }
+                false
+            }
+            _ => false,
+        };
+
+        if redraw {
+            let extras = Extras {
+                handles: drag.is_none(),
+                label: true,
+                loupe: (opts.selector.magnifier && hovering).then_some(pointer),
+                ..Extras::default()
+            };
+            draw_overlay(&overlay, sel, extras)?;
+        }
+    }
+}
+
+/// The edges moved by the handle under `p`, if any.
+fn handle_at(sel: Selection, p: (i16, i16)) -> Option<Edges> {
+    // Be a little generous: the handles are small.
+    let slop = (HANDLE_SIZE / 2) as i16;
+    let size = HANDLE_SIZE as i16;
+    handle_positions(sel)
+        .iter()
+        .position(|&(hx, hy)| {
+            p.0 >= hx - slop && p.0 < hx + size + slop && p.1 >= hy - slop && p.1 < hy + size + slop
+        })
+        .map(|i| HANDLE_EDGES[i])
+}
+
+/// Move the dragged edges to the pointer. When an edge is dragged past its
+/// opposite the rectangle flips, and so do the edges being dragged.
+fn resize(
+    sel: Selection,
+    edges: &mut Edges,
+    p: (i16, i16),
+    sw: u16,
+    sh: u16,
+    snap: Option<&Snap>,
+) -> Selection {
+    let (mut x0, mut y0) = (sel.0, sel.1);
+    let (mut x1, mut y1) = (sel.0 + sel.2 as i16, sel.1 + sel.3 as i16);
+    if edges.left {
+        x0 = p.0;
+    } else if edges.right {
+        x1 = p.0;
+    }
+    if edges.top {
+        y0 = p.1;
+    } else if edges.bottom {
+        y1 = p.1;
+    }
+    if x0 > x1 {
+        std::mem::swap(&mut edges.left, &mut edges.right);
+    }
+    if y0 > y1 {
+        std::mem::swap(&mut edges.top, &mut edges.bottom);
+    }
+    compute_selection(x0, y0, x1, y1, sw, sh, snap)
+}
+
+/// Rectangles whose edges the selection snaps to: visible windows (frame
+/// and client area), monitors, and the caller's earlier regions.
+fn snap_targets(
+    conn: &impl Connection,
+    root: Window,
+    opts: &CaptureOptions,
+) -> Result<Vec<Region>, CaptureError> {
+    let mut rects = opts.snap_regions.clone();
+    for w in windows::visible_toplevels(conn, root, opts)? {
+        rects.extend([w.frame_rect, w.rect]);
+    }
+    rects.extend(super::monitors(conn, root)?.iter().map(Monitor::to_region));
+    Ok(rects)
+}
+
+/// Arrow keys: move the selection by one pixel, or with `grow` move its
+/// right/bottom edge instead.
+fn nudge(sel: Selection, arrow: Action, grow: bool, sw: u16, sh: u16) -> Selection {
+    let (dx, dy) = match arrow {
+        Action::Left => (-1, 0),
+        Action::Right => (1, 0),
+        Action::Up => (0, -1),
+        Action::Down => (0, 1),
+        _ => (0, 0),
+    };
+    let (x, y, w, h) = (sel.0 as i32, sel.1 as i32, sel.2 as i32, sel.3 as i32);
+    if grow {
+        let w = (w + dx).clamp(1, sw as i32 - x);
+        let h = (h + dy).clamp(1, sh as i32 - y);
+        (x as i16, y as i16, w as u16, h as u16)
+    } else {
+        let x = (x + dx).clamp(0, sw as i32 - w);
+        let y = (y + dy).clamp(0, sh as i32 - h);
+        (x as i16, y as i16, w as u16, h as u16)
+    }
+}
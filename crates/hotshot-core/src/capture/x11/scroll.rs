@@ -0,0 +1,322 @@
+//! Scrolling capture.
+//!
+//! Captures a region, scrolls whatever is under it with XTest wheel clicks,
+//! captures again and appends the rows that scrolled into view. Consecutive
+//! frames are lined up by comparing row hashes. Rows that stay put between
+//! the first two frames at the top and bottom (sticky headers, status bars)
+//! are left out of the comparison and appear once in the result.
+
+use super::{
+    CaptureError, CaptureOptions, Region, ScrollSpec, capture_window_region, clip_to_screen,
+    connect, region,
+};
+use image::RgbaImage;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+use x11rb::protocol::xtest::ConnectionExt as _;
+
+/// Wheel-down button.
+const SCROLL_DOWN: u8 = 5;
+/// Fewest rows two frames must share before we trust the offset between them.
+const MIN_OVERLAP: usize = 16;
+
+/// Capture and stitch. Returns the tall image and the screen area it was
+/// scrolled through.
+pub(super) fn capture(
+    spec: &ScrollSpec,
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<(RgbaImage, Region), CaptureError> {
+    let (first, area) = match spec.region {
+        Some(area) => (None, area),
+        None => {
+            let (image, (x, y)) = region::select(display_bounds, opts)?;
+            let area = Region { x, y, width: image.width(), height: image.height() };
+            (Some(image), area)
+        }
+    };
+
+    let (conn, screen_num) = connect()?;
+    let screen = conn.setup().roots[screen_num].clone();
+    conn.xtest_get_version(2, 2)
+        .map_err(|e| CaptureError::X11(format!("xtest get_version: {e}")))?
+        .reply()
+        .map_err(|_| CaptureError::X11("scroll capture needs the XTEST extension".to_string()))?;
+
+    let area = clip_to_screen(area, &screen)
+        .ok_or_else(|| CaptureError::X11("scroll region is off-screen".to_string()))?;
+    let grab = || {
+        capture_window_region(
+            &conn,
+            &screen,
+            area.x as i16,
+            area.y as i16,
+            area.width as u16,
+            area.height as u16,
+        )
+    };
+    let first = match first {
+        Some(image) => image,
+        None => grab()?,
+    };
+
+    // The wheel scrolls whatever is under the pointer; put it back afterwards.
+    let pointer = conn
+        .query_pointer(screen.root)
+        .map_err(|e| CaptureError::X11(format!("query_pointer: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("query_pointer reply: {e}")))?;
+    let centre = (
+        (area.x + area.width as i32 / 2) as i16,
+        (area.y + area.height as i32 / 2) as i16,
+    );
+    move_pointer(&conn, screen.root, centre)?;
+
+    let mut stitcher = Stitcher::new(first);
+    let result = (|| -> Result<(), CaptureError> {
+        while stitcher.height() < spec.max_height as usize {
+            scroll(&conn, screen.root, spec.clicks)?;
+            std::thread::sleep(spec.settle);
+            if !stitcher.push(grab()?) {
+                break;
+            }
+        }
+        Ok(())
+    })();
+
+    move_pointer(&conn, screen.root, (pointer.root_x, pointer.root_y))?;
+    result?;
+    Ok((stitcher.finish(spec.max_height as usize)?, area))
+}
+
+fn move_pointer(conn: &impl Connection, root: Window, (x, y): (i16, i16)) -> Result<(), CaptureError> {
+    conn.xtest_fake_input(MOTION_NOTIFY_EVENT, 0, x11rb::CURRENT_TIME, root, x, y, 0)
+        .map_err(|e| CaptureError::X11(format!("xtest fake_input motion: {e}")))?;
+    conn.flush()
+        .map_err(|e| CaptureError::X11(format!("flush: {e}")))
+}
+
+fn scroll(conn: &impl Connection, root: Window, clicks: u32) -> Result<(), CaptureError> {
+    for _ in 0..clicks {
+        for event in [BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT] {
+            conn.xtest_fake_input(event, SCROLL_DOWN, x11rb::CURRENT_TIME, root, 0, 0, 0)
+                .map_err(|e| CaptureError::X11(format!("xtest fake_input scroll: {e}")))?;
+        }
+    }
+    // A round trip, so the server has processed the clicks before we time
+    // the settle.
+    conn.get_input_focus()
+        .map_err(|e| CaptureError::X11(format!("get_input_focus: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("get_input_focus reply: {e}")))?;
+    Ok(())
+}
+
+/// Builds the tall image one frame at a time.
+struct Stitcher {
+    width: u32,
+    /// Bytes per row.
+    stride: usize,
+    frame_rows: usize,
+    /// Rows fixed at the top and bottom of the frame, from the first two frames.
+    fixed: Option<(usize, usize)>,
+    /// Stitched rows so far, without the bottom fixed rows.
+    body: Vec<u8>,
+    last: RgbaImage,
+    last_hashes: Vec<u64>,
+}
+
+impl Stitcher {
+    fn new(first: RgbaImage) -> Self {
+        Self {
+            width: first.width(),
+            stride: first.width() as usize * 4,
+            frame_rows: first.height() as usize,
+            fixed: None,
+            body: first.as_raw().clone(),
+            last_hashes: row_hashes(&first),
+            last: first,
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.body.len() / self.stride.max(1)
+    }
+
+    /// Add the next frame. Returns false once there's nothing new: the
+    /// content stopped moving or we lost track of it.
+    fn push(&mut self, frame: RgbaImage) -> bool {
+        let hashes = row_hashes(&frame);
+        let h = self.frame_rows;
+        let (top, bottom) = match self.fixed {
+            Some(fixed) => fixed,
+            None => {
+                let fixed = fixed_rows(&self.last_hashes, &hashes);
+                self.fixed = Some(fixed);
+                // The footer goes back on at the end, from the last frame.
+                self.body.truncate((h - fixed.1) * self.stride);
+                fixed
+            }
+        };
+        if top + bottom >= h {
+            return false;
+        }
+
+        let (prev, next) = (&self.last_hashes[top..h - bottom], &hashes[top..h - bottom]);
+        if prev == next {
+            return false;
+        }
+        let Some(dy) = scroll_offset(prev, next) else {
+            return false;
+        };
+
+        let end = (h - bottom) * self.stride;
+        self.body
+            .extend_from_slice(&frame.as_raw()[end - dy * self.stride..end]);
+        self.last = frame;
+        self.last_hashes = hashes;
+        true
+    }
+
+    /// The stitched image with the last frame's footer, at most `max_height` tall.
+    fn finish(self, max_height: usize) -> Result<RgbaImage, CaptureError> {
+        let footer_rows = self.fixed.map_or(0, |(_, bottom)| bottom);
+        let footer = &self.last.as_raw()[(self.frame_rows - footer_rows) * self.stride..];
+        let mut data = self.body;
+        let body_rows = data.len() / self.stride;
+        let keep = body_rows.min(max_height.saturating_sub(footer_rows).max(1));
+        data.truncate(keep * self.stride);
+        data.extend_from_slice(footer);
+        let height = (data.len() / self.stride) as u32;
+        RgbaImage::from_raw(self.width, height, data)
+            .ok_or_else(|| CaptureError::X11("failed to create image from stitched rows".to_string()))
+    }
+}
+
+fn row_hashes(image: &RgbaImage) -> Vec<u64> {
+    image
+        .as_raw()
+        .chunks(image.width() as usize * 4)
+        .map(|row| {
+            let mut hasher = DefaultHasher::new();
+            row.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Rows identical at the same position in both frames, from the top and
+/// from the bottom.
+fn fixed_rows(a: &[u64], b: &[u64]) -> (usize, usize) {
+    let top = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let bottom = a[top..]
+        .iter()
+        .rev()
+        .zip(b[top..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    (top, bottom)
+}
+
+/// How many rows the content moved up from `prev` to `next`: the smallest
+/// shift under which all overlapping rows match.
+fn scroll_offset(prev: &[u64], next: &[u64]) -> Option<usize> {
+    let n = prev.len();
+    (1..=n.saturating_sub(MIN_OVERLAP)).find(|&dy| prev[dy..] == next[..n - dy])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: [u32; 2] = [1000, 1001];
+    const FOOTER: [u32; 1] = [2000];
+    /// Rows of content visible between the header and footer.
+    const VIEW: u32 = 30;
+
+    /// A one-pixel-wide image whose rows encode `ids`.
+    fn image(ids: &[u32]) -> RgbaImage {
+        let data = ids.iter().flat_map(|id| id.to_le_bytes()).collect();
+        RgbaImage::from_raw(1, ids.len() as u32, data).unwrap()
+    }
+
+    fn ids(image: &RgbaImage) -> Vec<u32> {
+        image.pixels().map(|p| u32::from_le_bytes(p.0)).collect()
+    }
+
+    /// The window scrolled down to content row `at`.
+    fn frame(at: u32) -> RgbaImage {
+        let body: Vec<u32> = (at..at + VIEW).collect();
+        image(&[&HEADER[..], &body, &FOOTER].concat())
+    }
+
+    fn stitch(positions: &[u32]) -> (Vec<bool>, Vec<u32>) {
+        let mut stitcher = Stitcher::new(frame(positions[0]));
+        let pushed = positions[1..].iter().map(|&at| stitcher.push(frame(at))).collect();
+        (pushed, ids(&stitcher.finish(usize::MAX).unwrap()))
+    }
+
+    fn expected(content: std::ops::Range<u32>) -> Vec<u32> {
+        [&HEADER[..], &content.collect::<Vec<_>>(), &FOOTER].concat()
+    }
+
+    #[test]
+    fn sticky_header_and_footer_appear_once() {
+        let (pushed, rows) = stitch(&[0, 5, 10]);
+        assert_eq!(pushed, [true, true]);
+        assert_eq!(rows, expected(0..VIEW + 10));
+    }
+
+    #[test]
+    fn stops_when_nothing_moves() {
+        let (pushed, rows) = stitch(&[0, 5, 5]);
+        assert_eq!(pushed, [true, false]);
+        assert_eq!(rows, expected(0..VIEW + 5));
+    }
+
+    #[test]
+    fn final_partial_scroll() {
+        let (pushed, rows) = stitch(&[0, 12, 24, 26, 26]);
+        assert_eq!(pushed, [true, true, true, false]);
+        assert_eq!(rows, expected(0..VIEW + 26));
+    }
+
+    #[test]
+    fn too_little_overlap_is_not_trusted() {
+        let prev: Vec<u64> = (0..VIEW as u64).collect();
+        let shifted = |dy: u64| -> Vec<u64> { (dy..dy + VIEW as u64).collect() };
+        let furthest = VIEW as usize - MIN_OVERLAP;
+        assert_eq!(scroll_offset(&prev, &shifted(furthest as u64)), Some(furthest));
+        assert_eq!(scroll_offset(&prev, &shifted(furthest as u64 + 1)), None);
+
+        let (pushed, rows) = stitch(&[0, furthest as u32 + 1]);
+        assert_eq!(pushed, [false]);
+        assert_eq!(rows, expected(0..VIEW));
+    }
+
+    #[test]
+    fn fixed_rows_from_both_ends() {
+        let a = [1, 2, 10, 11, 12, 3];
+        let b = [1, 2, 11, 12, 13, 3];
+        assert_eq!(fixed_rows(&a, &b), (2, 1));
+        assert_eq!(fixed_rows(&a, &a), (a.len(), 0));
+    }
+
+    #[test]
+    fn nothing_left_between_fixed_rows() {
+        // Two identical frames: every row looks fixed, so nothing scrolls.
+        let (pushed, rows) = stitch(&[0, 0]);
+        assert_eq!(pushed, [false]);
+        assert_eq!(rows, expected(0..VIEW));
+    }
+
+    #[test]
+    fn finish_caps_the_height_and_keeps_the_footer() {
+        let mut stitcher = Stitcher::new(frame(0));
+        assert!(stitcher.push(frame(10)));
+        let rows = ids(&stitcher.finish(20).unwrap());
+        assert_eq!(rows.len(), 20);
+        assert_eq!(rows.last(), Some(&FOOTER[0]));
+    }
+}
@@ -0,0 +1,152 @@
+//! MIT-SHM fast path for moving pixels between the X server and us.
+//!
+//! Core `get_image`/`put_image` push every pixel through the X socket, which
+//! dominates capture time on large multi-monitor roots. With SHM 1.2 the
+//! server creates a memfd-backed segment that both sides map, so transfers
+//! become a memcpy on the server side. Segments can't be shared with remote
+//! displays (no fd passing over TCP), so `ShmSegment::new` returns `None`
+//! there and callers keep using the core-protocol path.
+
+use super::CaptureError;
+use std::os::fd::AsRawFd;
+use x11rb::connection::Connection;
+use x11rb::protocol::shm::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::ImageFormat;
+
+/// A shared memory segment attached on both the client and the server.
+pub(super) struct ShmSegment<'a, C: Connection> {
+    conn: &'a C,
+    seg: shm::Seg,
+    addr: *mut u8,
+    size: usize,
+}
+
+/// Whether the server speaks MIT-SHM 1.2 (needed for `create_segment`).
+fn supported(conn: &impl Connection) -> bool {
+    if !matches!(conn.extension_information(shm::X11_EXTENSION_NAME), Ok(Some(_))) {
+        return false;
+    }
+    match conn.shm_query_version().ok().and_then(|c| c.reply().ok()) {
+        Some(v) => (v.major_version, v.minor_version) >= (1, 2),
+        None => false,
+    }
+}
+
+impl<'a, C: Connection> ShmSegment<'a, C> {
+    /// Create and map a segment of `size` bytes.
+    ///
+    /// Returns `None` if the server lacks MIT-SHM 1.2 or the segment can't be
+    /// shared with this client; callers should fall back to core requests.
+    pub(super) fn new(conn: &'a C, size: usize) -> Option<Self> {
+        if size == 0 || size > u32::MAX as usize || !supported(conn) {
+            return None;
+        }
+
+        let seg = conn.generate_id().ok()?;
+        let reply = conn
+            .shm_create_segment(seg, size as u32, false)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        // SAFETY: the fd refers to a segment of exactly `size` bytes created by
+        // the server; the mapping is released in `Drop`.
+        let addr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                reply.shm_fd.as_raw_fd(),
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            let _ = conn.shm_detach(seg);
+            return None;
+        }
+
+        Some(Self {
+            conn,
+            seg,
+            addr: addr as *mut u8,
+            size,
+        })
+    }
+
+    /// Read a Z_PIXMAP image of `drawable` into the segment and return its bytes.
+    pub(super) fn get_image(
+        &self,
+        drawable: u32,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<&[u8], CaptureError> {
+        let reply = self
+            .conn
+            .shm_get_image(
+                drawable,
+                x,
+                y,
+                width,
+                height,
+                !0,
+                ImageFormat::Z_PIXMAP.into(),
+                self.seg,
+                0,
+            )
+            .map_err(|e| CaptureError::X11(format!("shm_get_image: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("shm_get_image reply: {e}")))?;
+
+        let len = (reply.size as usize).min(self.size);
+        // SAFETY: the server has finished writing (we waited for the reply) and
+        // `len` is within the mapping.
+        Ok(unsafe { std::slice::from_raw_parts(self.addr, len) })
+    }
+
+    /// Write the segment's contents (a `width`x`height` Z_PIXMAP image) into
+    /// `drawable` at the origin.
+    pub(super) fn put_image(
+        &self,
+        drawable: u32,
+        gc: u32,
+        width: u16,
+        height: u16,
+        depth: u8,
+    ) -> Result<(), CaptureError> {
+        self.conn
+            .shm_put_image(
+                drawable,
+                gc,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                depth,
+                ImageFormat::Z_PIXMAP.into(),
+                false,
+                self.seg,
+                0,
+            )
+            .map_err(|e| CaptureError::X11(format!("shm_put_image: {e}")))?
+            .check()
+            .map_err(|e| CaptureError::X11(format!("shm_put_image check: {e}")))
+    }
+}
+
+impl<C: Connection> Drop for ShmSegment<'_, C> {
+    fn drop(&mut self) {
+        let _ = self.conn.shm_detach(self.seg);
+        let _ = self.conn.flush();
+        // SAFETY: `addr`/`size` are exactly what `mmap` returned in `new`.
+        unsafe {
+            libc::munmap(self.addr as *mut libc::c_void, self.size);
+        }
+    }
+}
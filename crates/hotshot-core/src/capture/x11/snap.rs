@@ -0,0 +1,91 @@
+//! Edge snapping for the region selector.
+//!
+//! Every rectangle we know about (window frames and client areas, monitors,
+//! earlier selections) contributes its four edges. A dragged coordinate
+//! within the threshold of an edge jumps onto it, as long as the edge
+//! actually runs alongside the selection.
+
+use super::Region;
+
+/// A vertical or horizontal line segment in overlay coordinates.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    /// x for vertical edges, y for horizontal ones.
+    pos: i16,
+    /// Extent along the other axis.
+    start: i16,
+    end: i16,
+}
+
+pub(super) struct Snap {
+    threshold: i16,
+    vertical: Vec<Edge>,
+    horizontal: Vec<Edge>,
+}
+
+impl Snap {
+    /// Collect the edges of `rects` (root coordinates) for an overlay whose
+    /// origin is at `(ox, oy)`.
+    pub(super) fn new(rects: &[Region], ox: i16, oy: i16, threshold: u16) -> Self {
+        let mut snap = Self {
+            threshold: threshold as i16,
+            vertical: Vec::new(),
+            horizontal: Vec::new(),
+        };
+        for r in rects {
+            let x0 = (r.x - ox as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            let y0 = (r.y - oy as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            let x1 = x0.saturating_add(r.width.min(i16::MAX as u32) as i16);
+            let y1 = y0.saturating_add(r.height.min(i16::MAX as u32) as i16);
+            for pos in [x0, x1] {
+                snap.vertical.push(Edge { pos, start: y0, end: y1 });
+            }
+            for pos in [y0, y1] {
+                snap.horizontal.push(Edge { pos, start: x0, end: x1 });
+            }
+        }
+        snap
+    }
+
+    /// `x` moved onto the nearest vertical edge alongside rows `span`.
+    pub(super) fn x(&self, x: i16, span: (i16, i16)) -> i16 {
+        nearest(&self.vertical, x, span, self.threshold).unwrap_or(x)
+    }
+
+    /// `y` moved onto the nearest horizontal edge alongside columns `span`.
+    pub(super) fn y(&self, y: i16, span: (i16, i16)) -> i16 {
+        nearest(&self.horizontal, y, span, self.threshold).unwrap_or(y)
+    }
+
+    /// How far to shift a rectangle spanning `lo..hi` horizontally so one of
+    /// its sides lands on an edge (the closer one wins).
+    pub(super) fn shift_x(&self, (lo, hi): (i16, i16), span: (i16, i16)) -> i16 {
+        self.shift(&self.vertical, (lo, hi), span)
+    }
+
+    /// Vertical counterpart of [`Snap::shift_x`].
+    pub(super) fn shift_y(&self, (lo, hi): (i16, i16), span: (i16, i16)) -> i16 {
+        self.shift(&self.horizontal, (lo, hi), span)
+    }
+
+    fn shift(&self, edges: &[Edge], (lo, hi): (i16, i16), span: (i16, i16)) -> i16 {
+        [lo, hi]
+            .into_iter()
+            .filter_map(|v| nearest(edges, v, span, self.threshold).map(|p| p - v))
+            .min_by_key(|d| d.abs())
+            .unwrap_or(0)
+    }
+}
+
+/// The edge position closest to `v` within `threshold` among edges that
+/// overlap `span` (with the same tolerance).
+fn nearest(edges: &[Edge], v: i16, (lo, hi): (i16, i16), threshold: i16) -> Option<i16> {
+    let (v, lo, hi, threshold) = (v as i32, lo as i32, hi as i32, threshold as i32);
+    let distance = |pos: i16| (pos as i32 - v).abs();
+    edges
+        .iter()
+        .filter(|e| e.start as i32 <= hi + threshold && e.end as i32 >= lo - threshold)
+        .map(|e| e.pos)
+        .filter(|&pos| distance(pos) <= threshold)
+        .min_by_key(|&pos| distance(pos))
+}
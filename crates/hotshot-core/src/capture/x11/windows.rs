@@ -0,0 +1,262 @@
+//! Helpers for walking the X window tree: atoms, client lookup, geometry.
+
+use super::{CaptureError, CaptureOptions, Region};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::*;
+
+pub(super) fn intern_atom(conn: &impl Connection, name: &[u8]) -> Result<Atom, CaptureError> {
+    Ok(conn
+        .intern_atom(false, name)
+        .map_err(|e| CaptureError::X11(format!("intern_atom failed: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("intern_atom reply failed: {e}")))?
+        .atom)
+}
+
+/// Read a property as a list of 32-bit values (empty if unset).
+pub(super) fn get_u32s(
+    conn: &impl Connection,
+    window: Window,
+    atom: Atom,
+    type_: impl Into<Atom>,
+) -> Result<Vec<u32>, CaptureError> {
+    let reply = conn
+        .get_property(false, window, atom, type_, 0, u32::MAX / 4)
+        .map_err(|e| CaptureError::X11(format!("get_property: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("get_property reply: {e}")))?;
+    Ok(reply.value32().map(|v| v.collect()).unwrap_or_default())
+}
+
+/// Read a property as raw bytes (empty if unset). `AtomEnum::ANY` accepts any type.
+pub(super) fn get_bytes(
+    conn: &impl Connection,
+    window: Window,
+    atom: Atom,
+    type_: impl Into<Atom>,
+) -> Result<Vec<u8>, CaptureError> {
+    let reply = conn
+        .get_property(false, window, atom, type_, 0, u32::MAX / 4)
+        .map_err(|e| CaptureError::X11(format!("get_property: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("get_property reply: {e}")))?;
+    Ok(reply.value)
+}
+
+/// A viewable top-level client window.
+#[derive(Debug, Clone)]
+pub(super) struct TopLevel {
+    /// The client window (the one carrying `WM_STATE`).
+    pub id: Window,
+    /// Frame rectangle (including border) in root coordinates.
+    pub frame_rect: Region,
+    /// Area to capture in root coordinates (see [`capture_rect`]).
+    pub rect: Region,
+}
+
+/// Geometry of `window` (excluding its border) in root coordinates.
+pub(super) fn window_rect(
+    conn: &impl Connection,
+    root: Window,
+    window: Window,
+) -> Result<Region, CaptureError> {
+    let geo = conn
+        .get_geometry(window)
+        .map_err(|e| CaptureError::X11(format!("get_geometry failed: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("get_geometry reply failed: {e}")))?;
+
+    let translated = conn
+        .translate_coordinates(window, root, 0, 0)
+        .map_err(|e| CaptureError::X11(format!("translate_coordinates failed: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("translate reply failed: {e}")))?;
+
+    Ok(Region {
+        x: translated.dst_x as i32,
+        y: translated.dst_y as i32,
+        width: geo.width as u32,
+        height: geo.height as u32,
+    })
+}
+
+/// Read a `CARDINAL[4]` extents property (left, right, top, bottom).
+fn read_extents(
+    conn: &impl Connection,
+    window: Window,
+    atom: Atom,
+) -> Result<Option<[u32; 4]>, CaptureError> {
+    let values = get_u32s(conn, window, atom, AtomEnum::CARDINAL)?;
+    Ok(<[u32; 4]>::try_from(values).ok())
+}
+
+/// The child of the root that contains `window` (its WM frame when the
+/// window manager reparents, otherwise the window itself).
+pub(super) fn toplevel_ancestor(
+    conn: &impl Connection,
+    root: Window,
+    window: Window,
+) -> Result<Window, CaptureError> {
+    let mut current = window;
+    loop {
+        let tree = conn
+            .query_tree(current)
+            .map_err(|e| CaptureError::X11(format!("query_tree: {e}")))?
+            .reply()
+            .map_err(|e| CaptureError::X11(format!("query_tree reply: {e}")))?;
+        if tree.parent == root || tree.parent == x11rb::NONE {
+            return Ok(current);
+        }
+        current = tree.parent;
+    }
+}
+
+/// The rectangle to capture for a client window, in root coordinates.
+///
+/// Starts from the client area. `decorations` grows it to the WM frame
+/// (from `_NET_FRAME_EXTENTS`, or the reparenting frame window when the WM
+/// doesn't publish extents). `strip_shadow` shrinks it by the invisible
+/// shadow margins that client-side-decorated GTK windows advertise in
+/// `_GTK_FRAME_EXTENTS`.
+pub(super) fn capture_rect(
+    conn: &impl Connection,
+    root: Window,
+    client: Window,
+    opts: &CaptureOptions,
+) -> Result<Region, CaptureError> {
+    let mut rect = window_rect(conn, root, client)?;
+
+    if opts.decorations {
+        let net_frame = intern_atom(conn, b"_NET_FRAME_EXTENTS")?;
+        if let Some(ext) = read_extents(conn, client, net_frame)? {
+            rect = grow(rect, ext);
+        } else {
+            let frame = toplevel_ancestor(conn, root, client)?;
+            if frame != client {
+                rect = window_rect(conn, root, frame)?;
+            }
+        }
+    }
+
+    if opts.strip_shadow {
+        let gtk_frame = intern_atom(conn, b"_GTK_FRAME_EXTENTS")?;
+        if let Some([l, r, t, b]) = read_extents(conn, client, gtk_frame)? {
+            rect = Region {
+                x: rect.x + l as i32,
+                y: rect.y + t as i32,
+                width: rect.width.saturating_sub(l + r),
+                height: rect.height.saturating_sub(t + b),
+            };
+        }
+    }
+
+    Ok(rect)
+}
+
+fn grow(rect: Region, [l, r, t, b]: [u32; 4]) -> Region {
+    Region {
+        x: rect.x - l as i32,
+        y: rect.y - t as i32,
+        width: rect.width + l + r,
+        height: rect.height + t + b,
+    }
+}
+
+/// Find the client window at or below `window`: the first one, searching
+/// breadth-first from the top of the stack, that has `WM_STATE` set.
+pub(super) fn find_client(
+    conn: &impl Connection,
+    window: Window,
+    wm_state: Atom,
+) -> Result<Option<Window>, CaptureError> {
+    let mut queue = std::collections::VecDeque::from([window]);
+    while let Some(w) = queue.pop_front() {
+        let prop = conn
+            .get_property(false, w, wm_state, AtomEnum::ANY, 0, 0)
+            .map_err(|e| CaptureError::X11(format!("get_property WM_STATE: {e}")))?
+            .reply();
+        // The window may have been destroyed since we listed it.
+        let Ok(prop) = prop else { continue };
+        if prop.type_ != x11rb::NONE {
+            return Ok(Some(w));
+        }
+
+        let Ok(tree) = conn
+            .query_tree(w)
+            .map_err(|e| CaptureError::X11(format!("query_tree: {e}")))?
+            .reply()
+        else {
+            continue;
+        };
+        queue.extend(tree.children.iter().rev());
+    }
+    Ok(None)
+}
+
+/// All viewable top-level client windows, topmost first.
+///
+/// Override-redirect windows (menus, tooltips, our own overlays) and frames
+/// without a client are skipped.
+pub(super) fn visible_toplevels(
+    conn: &impl Connection,
+    root: Window,
+    opts: &CaptureOptions,
+) -> Result<Vec<TopLevel>, CaptureError> {
+    let wm_state = intern_atom(conn, b"WM_STATE")?;
+    let tree = conn
+        .query_tree(root)
+        .map_err(|e| CaptureError::X11(format!("query_tree root: {e}")))?
+        .reply()
+        .map_err(|e| CaptureError::X11(format!("query_tree root reply: {e}")))?;
+
+    // Pipeline the attribute/geometry requests; replies are read in order.
+    let mut cookies = Vec::with_capacity(tree.children.len());
+    for &child in &tree.children {
+        let attrs = conn
+            .get_window_attributes(child)
+            .map_err(|e| CaptureError::X11(format!("get_window_attributes: {e}")))?;
+        let geo = conn
+            .get_geometry(child)
+            .map_err(|e| CaptureError::X11(format!("get_geometry: {e}")))?;
+        cookies.push((child, attrs, geo));
+    }
+
+    let mut result = Vec::new();
+    for (frame, attrs, geo) in cookies.into_iter().rev() {
+        let (Ok(attrs), Ok(geo)) = (attrs.reply(), geo.reply()) else {
+            continue;
+        };
+        if attrs.map_state != MapState::VIEWABLE
+            || attrs.class == WindowClass::INPUT_ONLY
+            || attrs.override_redirect
+        {
+            continue;
+        }
+        let Some(client) = find_client(conn, frame, wm_state)? else {
+            continue;
+        };
+        let Ok(rect) = capture_rect(conn, root, client, opts) else {
+            continue;
+        };
+        let bw = geo.border_width as u32;
+        result.push(TopLevel {
+            id: client,
+            frame_rect: Region {
+                x: geo.x as i32,
+                y: geo.y as i32,
+                width: geo.width as u32 + 2 * bw,
+                height: geo.height as u32 + 2 * bw,
+            },
+            rect,
+        });
+    }
+    Ok(result)
+}
+
+/// The topmost window whose frame contains the root-space point.
+pub(super) fn toplevel_at(windows: &[TopLevel], x: i32, y: i32) -> Option<&TopLevel> {
+    windows.iter().find(|w| {
+        let r = w.frame_rect;
+        x >= r.x && y >= r.y && x < r.x + r.width as i32 && y < r.y + r.height as i32
+    })
+}
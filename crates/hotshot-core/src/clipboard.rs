@@ -20,3 +20,9 @@ pub fn copy_image(image: &RgbaImage) -> Result<(), ClipboardError> {
     clipboard.set_image(img_data)?;
     Ok(())
 }
+
+pub fn copy_text(text: &str) -> Result<(), ClipboardError> {
+    let mut clipboard = Clipboard::new().map_err(|_| ClipboardError::Init)?;
+    clipboard.set_text(text)?;
+    Ok(())
+}
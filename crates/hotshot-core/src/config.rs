@@ -23,6 +23,12 @@ pub struct Config {
     #[serde(default)]
     pub storage: StorageConfig,
 
+    #[serde(default)]
+    pub capture: CaptureConfig,
+
+    #[serde(default)]
+    pub selector: SelectorConfig,
+
     #[serde(default)]
     pub behavior: BehaviorConfig,
 }
@@ -35,6 +41,8 @@ pub struct ImageConfig {
     pub quality: u8,
     #[serde(default = "default_filename_template")]
     pub filename_template: String,
+    #[serde(default)]
+    pub scale: ImageScale,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +59,45 @@ pub enum OrganizeBy {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureConfig {
+    #[serde(default)]
+    pub include_cursor: bool,
+    #[serde(default = "default_countdown")]
+    pub countdown: bool,
+}
+
+/// Key bindings for the interactive region selector, as X keysym names.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectorConfig {
+    #[serde(default = "default_confirm_keys")]
+    pub confirm: Vec<String>,
+    #[serde(default = "default_cancel_keys")]
+    pub cancel: Vec<String>,
+    #[serde(default = "default_restart_keys")]
+    pub restart: Vec<String>,
+    /// Hold while dragging to move the selection instead of resizing it.
+    #[serde(rename = "move", default = "default_move_keys")]
+    pub move_selection: Vec<String>,
+    #[serde(default = "default_left_keys")]
+    pub left: Vec<String>,
+    #[serde(default = "default_right_keys")]
+    pub right: Vec<String>,
+    #[serde(default = "default_up_keys")]
+    pub up: Vec<String>,
+    #[serde(default = "default_down_keys")]
+    pub down: Vec<String>,
+    /// Show a magnifier with the colour under the pointer.
+    #[serde(default)]
+    pub magnifier: bool,
+    /// Snap the selection to window, monitor and earlier region edges.
+    #[serde(default)]
+    pub snap: bool,
+    /// How close (in pixels) an edge has to be to snap to it.
+    #[serde(default = "default_snap_threshold")]
+    pub snap_threshold: u16,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BehaviorConfig {
     #[serde(default)]
     pub copy_to_clipboard: bool,
@@ -88,6 +135,59 @@ impl std::str::FromStr for ImageFormat {
     }
 }
 
+/// How captures from HiDPI monitors are sized when saved.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ImageScale {
+    /// Keep every captured pixel.
+    #[default]
+    Physical,
+    /// Undo the monitor's scaling, so the same UI comes out the same size
+    /// from every monitor.
+    Logical,
+    /// Resample by a fixed factor.
+    Factor(f64),
+}
+
+impl std::fmt::Display for ImageScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageScale::Physical => write!(f, "physical"),
+            ImageScale::Logical => write!(f, "logical"),
+            ImageScale::Factor(factor) => write!(f, "{factor}"),
+        }
+    }
+}
+
+impl std::str::FromStr for ImageScale {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "physical" => Ok(ImageScale::Physical),
+            "logical" => Ok(ImageScale::Logical),
+            other => match other.parse::<f64>() {
+                Ok(f) if f > 0.0 && f <= 8.0 => Ok(ImageScale::Factor(f)),
+                _ => Err(format!(
+                    "invalid scale: {s}. use: physical, logical, or a factor like 0.5"
+                )),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for ImageScale {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ImageScale> for String {
+    fn from(scale: ImageScale) -> Self {
+        scale.to_string()
+    }
+}
+
 impl ImageFormat {
     pub fn extension(&self) -> &str {
         match self {
@@ -120,12 +220,58 @@ fn default_organize_by() -> OrganizeBy {
     OrganizeBy::Month
 }
 
+fn default_countdown() -> bool {
+    true
+}
+
+fn default_snap_threshold() -> u16 {
+    8
+}
+
+fn keys(names: &[&str]) -> Vec<String> {
+    names.iter().map(|s| s.to_string()).collect()
+}
+
+fn default_confirm_keys() -> Vec<String> {
+    keys(&["Return", "KP_Enter"])
+}
+
+fn default_cancel_keys() -> Vec<String> {
+    keys(&["Escape"])
+}
+
+fn default_restart_keys() -> Vec<String> {
+    keys(&["BackSpace"])
+}
+
+fn default_move_keys() -> Vec<String> {
+    keys(&["space"])
+}
+
+fn default_left_keys() -> Vec<String> {
+    keys(&["Left"])
+}
+
+fn default_right_keys() -> Vec<String> {
+    keys(&["Right"])
+}
+
+fn default_up_keys() -> Vec<String> {
+    keys(&["Up"])
+}
+
+fn default_down_keys() -> Vec<String> {
+    keys(&["Down"])
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             storage_dir: default_storage_dir(),
             image: ImageConfig::default(),
             storage: StorageConfig::default(),
+            capture: CaptureConfig::default(),
+            selector: SelectorConfig::default(),
             behavior: BehaviorConfig::default(),
         }
     }
@@ -137,6 +283,7 @@ impl Default for ImageConfig {
             format: default_format(),
             quality: default_quality(),
             filename_template: default_filename_template(),
+            scale: ImageScale::default(),
         }
     }
 }
@@ -149,11 +296,29 @@ impl Default for StorageConfig {
     }
 }
 
-impl Default for BehaviorConfig {
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            include_cursor: false,
+            countdown: default_countdown(),
+        }
+    }
+}
+
+impl Default for SelectorConfig {
     fn default() -> Self {
         Self {
-            copy_to_clipboard: false,
-            notification: false,
+            confirm: default_confirm_keys(),
+            cancel: default_cancel_keys(),
+            restart: default_restart_keys(),
+            move_selection: default_move_keys(),
+            left: default_left_keys(),
+            right: default_right_keys(),
+            up: default_up_keys(),
+            down: default_down_keys(),
+            magnifier: false,
+            snap: false,
+            snap_threshold: default_snap_threshold(),
         }
     }
 }
@@ -214,9 +379,42 @@ impl Config {
             "filename_template = \"{}\"\n",
             self.image.filename_template
         ));
+        s.push_str("# Size on HiDPI monitors: physical (every pixel), logical (undo the\n");
+        s.push_str("# monitor's scaling), or a factor like \"0.5\"\n");
+        s.push_str(&format!("scale = \"{}\"\n", self.image.scale));
         s.push_str("\n[storage]\n");
         s.push_str("# How to organize screenshots: \"month\" (YYYY-MM subdirs) or \"none\" (flat)\n");
         s.push_str(&format!("organize_by = \"{}\"\n", self.storage.organize_by));
+        s.push_str("\n[capture]\n");
+        s.push_str("# Draw the mouse pointer into captures\n");
+        s.push_str(&format!(
+            "include_cursor = {}\n",
+            self.capture.include_cursor
+        ));
+        s.push_str("# Show an on-screen countdown during --delay (X11)\n");
+        s.push_str(&format!("countdown = {}\n", self.capture.countdown));
+        s.push_str("\n[selector]\n");
+        s.push_str("# Region selector keys (X keysym names). Arrows nudge the selection\n");
+        s.push_str("# by 1px, Shift+arrows resize it; right-click also restarts.\n");
+        let sel = &self.selector;
+        for (key, names) in [
+            ("confirm", &sel.confirm),
+            ("cancel", &sel.cancel),
+            ("restart", &sel.restart),
+            ("move", &sel.move_selection),
+            ("left", &sel.left),
+            ("right", &sel.right),
+            ("up", &sel.up),
+            ("down", &sel.down),
+        ] {
+            s.push_str(&format!("{key} = {}\n", toml_list(names)));
+        }
+        s.push_str("# Show a magnifier with the pixel colour under the pointer\n");
+        s.push_str(&format!("magnifier = {}\n", self.selector.magnifier));
+        s.push_str("# Snap to window, monitor and earlier region edges (hold Ctrl to disable)\n");
+        s.push_str(&format!("snap = {}\n", self.selector.snap));
+        s.push_str("# Snap distance in pixels\n");
+        s.push_str(&format!("snap_threshold = {}\n", self.selector.snap_threshold));
         s.push_str("\n[behavior]\n");
         s.push_str("# Automatically copy screenshot to clipboard after capture\n");
         s.push_str(&format!(
@@ -261,6 +459,7 @@ impl Config {
             "image.filename_template" | "filename_template" => {
                 self.image.filename_template = value.to_string();
             }
+            "image.scale" | "scale" => self.image.scale = value.parse()?,
             "storage.organize_by" | "organize_by" => {
                 self.storage.organize_by = match value.to_lowercase().as_str() {
                     "month" => OrganizeBy::Month,
@@ -268,6 +467,39 @@ impl Config {
                     _ => return Err(format!("invalid organize_by: {value}. use: month, none")),
                 }
             }
+            "capture.include_cursor" | "include_cursor" => {
+                self.capture.include_cursor = value
+                    .parse()
+                    .map_err(|_| format!("invalid bool: {value}. use: true/false"))?;
+            }
+            "capture.countdown" | "countdown" => {
+                self.capture.countdown = value
+                    .parse()
+                    .map_err(|_| format!("invalid bool: {value}. use: true/false"))?;
+            }
+            "selector.confirm" => self.selector.confirm = parse_keys(value)?,
+            "selector.cancel" => self.selector.cancel = parse_keys(value)?,
+            "selector.restart" => self.selector.restart = parse_keys(value)?,
+            "selector.move" => self.selector.move_selection = parse_keys(value)?,
+            "selector.left" => self.selector.left = parse_keys(value)?,
+            "selector.right" => self.selector.right = parse_keys(value)?,
+            "selector.up" => self.selector.up = parse_keys(value)?,
+            "selector.down" => self.selector.down = parse_keys(value)?,
+            "selector.snap" | "snap" => {
+                self.selector.snap = value
+                    .parse()
+                    .map_err(|_| format!("invalid bool: {value}. use: true/false"))?;
+            }
+            "selector.snap_threshold" | "snap_threshold" => {
+                self.selector.snap_threshold = value
+                    .parse()
+                    .map_err(|_| format!("invalid snap_threshold: {value}. use: pixels, e.g. 8"))?;
+            }
+            "selector.magnifier" | "magnifier" => {
+                self.selector.magnifier = value
+                    .parse()
+                    .map_err(|_| format!("invalid bool: {value}. use: true/false"))?;
+            }
             "behavior.copy_to_clipboard" | "copy_to_clipboard" => {
                 self.behavior.copy_to_clipboard = value
                     .parse()
@@ -287,3 +519,21 @@ impl Config {
         self.to_commented_toml()
     }
 }
+
+fn toml_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items.iter().map(|s| format!("{s:?}")).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+/// Parse a comma-separated list of key names ("Return,KP_Enter").
+fn parse_keys(value: &str) -> Result<Vec<String>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| match crate::capture::x11::keysym_from_name(name) {
+            Some(_) => Ok(name.to_string()),
+            None => Err(format!("unknown key name: {name}. use X keysym names like Return, space, a")),
+        })
+        .collect()
+}
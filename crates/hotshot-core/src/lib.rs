@@ -2,7 +2,10 @@ pub mod capture;
 pub mod clipboard;
 pub mod config;
 pub mod metadata;
+pub mod recording;
+pub mod regions;
 pub mod storage;
+pub mod watch;
 
 pub use image;
 
@@ -1,3 +1,4 @@
+use crate::capture::{CursorPosition, Region};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -27,6 +28,29 @@ pub struct Metadata {
     pub tags: Vec<String>,
     #[serde(default)]
     pub notes: String,
+    /// Pointer hotspot in image coordinates, when the cursor was captured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<CursorPosition>,
+    /// Screen area the screenshot was taken from, in root coordinates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+    /// Shared by the per-monitor screenshots of one `--display all` capture
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Monitor a grouped screenshot shows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    /// Size as captured, before `--scale` resampled it to `width`x`height`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_height: Option<u32>,
+    /// Playback length of a recording
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Frames in a recording
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_count: Option<u32>,
 }
 
 /// The database: all screenshot metadata in one file
@@ -57,6 +81,14 @@ impl Metadata {
             file_size: 0,
             tags: Vec::new(),
             notes: String::new(),
+            cursor: None,
+            region: None,
+            group: None,
+            monitor: None,
+            physical_width: None,
+            physical_height: None,
+            duration_ms: None,
+            frame_count: None,
         }
     }
 
@@ -85,6 +117,9 @@ impl Metadata {
         if self.id.contains(&q) {
             return true;
         }
+        if self.group.as_deref().is_some_and(|g| g.contains(&q)) {
+            return true;
+        }
         false
     }
 }
@@ -164,7 +199,7 @@ impl MetadataDb {
     /// List all, sorted newest first
     pub fn list_sorted(&self) -> Vec<&Metadata> {
         let mut entries: Vec<_> = self.screenshots.iter().collect();
-        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        entries.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
         entries
     }
 
@@ -0,0 +1,529 @@
+//! Screen recordings: animated images and video.
+//!
+//! Frames are grabbed from the X11 root at a fixed rate and encoded as they
+//! arrive, so a long recording never sits in memory uncompressed. GIF goes
+//! through the `image` crate's encoder. The `image` crate can't write
+//! animations for APNG or WebP: APNG uses the `png` crate it's built on,
+//! and animated WebP wraps each frame from its lossless WebP encoder in the
+//! RIFF animation chunks. MP4 and WebM are piped as raw RGBA into a locally
+//! installed `ffmpeg`.
+
+use crate::capture::{self, CaptureError, CaptureMode, CaptureOptions, DisplayServer, Region};
+use image::RgbaImage;
+use image::codecs::gif::{GifEncoder, Repeat};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("{0}")]
+    Capture(#[from] CaptureError),
+    #[error("failed to encode recording: {0}")]
+    Encode(String),
+    #[error("failed to write recording: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingFormat {
+    #[default]
+    Gif,
+    Apng,
+    Webp,
+    Mp4,
+    Webm,
+}
+
+impl std::fmt::Display for RecordingFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingFormat::Gif => write!(f, "gif"),
+            RecordingFormat::Apng => write!(f, "apng"),
+            RecordingFormat::Webp => write!(f, "webp"),
+            RecordingFormat::Mp4 => write!(f, "mp4"),
+            RecordingFormat::Webm => write!(f, "webm"),
+        }
+    }
+}
+
+impl std::str::FromStr for RecordingFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gif" => Ok(RecordingFormat::Gif),
+            "apng" | "png" => Ok(RecordingFormat::Apng),
+            "webp" => Ok(RecordingFormat::Webp),
+            "mp4" => Ok(RecordingFormat::Mp4),
+            "webm" => Ok(RecordingFormat::Webm),
+            _ => Err(format!(
+                "unknown recording format: {s}. use: gif, apng, webp, mp4, webm"
+            )),
+        }
+    }
+}
+
+impl RecordingFormat {
+    /// APNG keeps the `.png` extension so any PNG viewer still opens it.
+    pub fn extension(&self) -> &str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png",
+            RecordingFormat::Webp => "webp",
+            RecordingFormat::Mp4 => "mp4",
+            RecordingFormat::Webm => "webm",
+        }
+    }
+
+    /// Encoded by `ffmpeg` rather than in-process.
+    pub fn is_video(&self) -> bool {
+        matches!(self, RecordingFormat::Mp4 | RecordingFormat::Webm)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordOptions {
+    /// Recorded time to stop after, pauses not included.
+    pub duration: Duration,
+    pub fps: u32,
+    pub format: RecordingFormat,
+    /// Draw a ring around the pointer while a mouse button is down.
+    pub highlight_clicks: bool,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(5),
+            fps: 15,
+            format: RecordingFormat::Gif,
+            highlight_clicks: false,
+        }
+    }
+}
+
+/// Stops or pauses a running recording from another thread (a signal
+/// handler, a key listener).
+#[derive(Debug, Default)]
+pub struct RecordControl {
+    stop: AtomicBool,
+    paused: AtomicBool,
+}
+
+impl RecordControl {
+    /// End the recording after the current frame; the file is finalized
+    /// as if the duration had run out.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    /// Pause a running recording or resume a paused one. Returns whether
+    /// it's now paused.
+    pub fn toggle_pause(&self) -> bool {
+        !self.paused.fetch_xor(true, Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+/// What was recorded.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub duration: Duration,
+    /// Screen area recorded, in root coordinates.
+    pub region: Region,
+}
+
+/// The screen area a recording of `mode` covers, picked with the same
+/// selectors as screenshots.
+pub fn pick_area(
+    mode: &CaptureMode,
+    display_bounds: Option<Region>,
+    opts: &CaptureOptions,
+) -> Result<Region, RecordingError> {
+    match capture::detect_display_server()? {
+        DisplayServer::X11 => Ok(capture::x11::record_area(mode, display_bounds, opts)?),
+        DisplayServer::Wayland => Err(CaptureError::Wayland(
+            "recording is not supported on Wayland".to_string(),
+        )
+        .into()),
+    }
+}
+
+/// Record `area` into a new file at `path`, after the capture delay (with
+/// its countdown over the area) if one is set. Runs until `rec.duration`
+/// of unpaused time has been recorded or `control` stops it.
+pub fn record(
+    path: &Path,
+    area: Region,
+    opts: &CaptureOptions,
+    rec: &RecordOptions,
+    control: &RecordControl,
+) -> Result<Recording, RecordingError> {
+    if rec.fps == 0 {
+        return Err(CaptureError::Other("--fps must be at least 1".to_string()).into());
+    }
+    if let Some(delay) = opts.delay {
+        match opts.countdown {
+            true => capture::x11::countdown(delay, Some(area))?,
+            false => std::thread::sleep(delay),
+        }
+    }
+
+    let max_frames = ((rec.duration.as_secs_f64() * rec.fps as f64).round() as u32).max(1);
+    let mut encoder = Encoder::new(path, rec.format, area.width, area.height, rec.fps, max_frames)?;
+    let mut frames = 0;
+    let mut result = Ok(());
+    let duration = capture::x11::record(area, rec, max_frames, opts.include_cursor, control, |frame, delay| {
+        frames += 1;
+        if let Err(e) = encoder.add_frame(&frame, delay) {
+            result = Err(e);
+            control.stop();
+        }
+        Ok(())
+    })?;
+    result?;
+    encoder.finish()?;
+
+    Ok(Recording {
+        width: area.width,
+        height: area.height,
+        frames,
+        duration,
+        region: area,
+    })
+}
+
+enum Encoder {
+    Gif(GifEncoder<BufWriter<File>>),
+    Apng(Apng),
+    Webp(WebpAnimation<BufWriter<File>>),
+    Video(Ffmpeg),
+}
+
+impl Encoder {
+    fn new(
+        path: &Path,
+        format: RecordingFormat,
+        width: u32,
+        height: u32,
+        fps: u32,
+        max_frames: u32,
+    ) -> Result<Self, RecordingError> {
+        if format.is_video() {
+            return Ok(Encoder::Video(Ffmpeg::spawn(path, format, width, height, fps)?));
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(match format {
+            RecordingFormat::Gif => {
+                let mut gif = GifEncoder::new_with_speed(writer, 10);
+                gif.set_repeat(Repeat::Infinite).map_err(encode_error)?;
+                Encoder::Gif(gif)
+            }
+            RecordingFormat::Apng => {
+                Encoder::Apng(Apng::new(path, writer, width, height, max_frames)?)
+            }
+            RecordingFormat::Webp => Encoder::Webp(WebpAnimation::new(writer, width, height)),
+            RecordingFormat::Mp4 | RecordingFormat::Webm => unreachable!("handled above"),
+        })
+    }
+
+    /// Append a frame shown for `delay`.
+    fn add_frame(&mut self, frame: &RgbaImage, delay: Duration) -> Result<(), RecordingError> {
+        let ms = delay.as_millis().clamp(1, u16::MAX as u128) as u16;
+        match self {
+            Encoder::Gif(gif) => {
+                let delay = image::Delay::from_saturating_duration(delay);
+                let frame = image::Frame::from_parts(frame.clone(), 0, 0, delay);
+                gif.encode_frame(frame).map_err(encode_error)
+            }
+            Encoder::Apng(apng) => apng.add_frame(frame, ms),
+            Encoder::Webp(webp) => webp.add_frame(frame, ms as u32),
+            Encoder::Video(ffmpeg) => ffmpeg.add_frame(frame, delay),
+        }
+    }
+
+    fn finish(self) -> Result<(), RecordingError> {
+        match self {
+            // The trailer is written when the encoder is dropped.
+            Encoder::Gif(gif) => drop(gif),
+            Encoder::Apng(apng) => apng.finish()?,
+            Encoder::Webp(webp) => webp.finish()?,
+            Encoder::Video(ffmpeg) => ffmpeg.finish()?,
+        }
+        Ok(())
+    }
+}
+
+fn encode_error(e: impl std::fmt::Display) -> RecordingError {
+    RecordingError::Encode(e.to_string())
+}
+
+/// APNG declares its frame count up front, before we know whether the
+/// recording will be stopped early: the most it could take is declared,
+/// and the count is corrected in place once the file is finished.
+struct Apng {
+    writer: png::Writer<BufWriter<File>>,
+    path: PathBuf,
+    written: u32,
+}
+
+impl Apng {
+    fn new(
+        path: &Path,
+        writer: BufWriter<File>,
+        width: u32,
+        height: u32,
+        max_frames: u32,
+    ) -> Result<Self, RecordingError> {
+        let mut png = png::Encoder::new(writer, width, height);
+        png.set_color(png::ColorType::Rgba);
+        png.set_depth(png::BitDepth::Eight);
+        png.set_animated(max_frames, 0).map_err(encode_error)?;
+        Ok(Self {
+            writer: png.write_header().map_err(encode_error)?,
+            path: path.to_path_buf(),
+            written: 0,
+        })
+    }
+
+    fn add_frame(&mut self, frame: &RgbaImage, delay_ms: u16) -> Result<(), RecordingError> {
+        self.writer.set_frame_delay(delay_ms, 1000).map_err(encode_error)?;
+        self.writer.write_image_data(frame.as_raw()).map_err(encode_error)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), RecordingError> {
+        self.writer.finish().map_err(encode_error)?;
+        set_apng_frame_count(&self.path, self.written)
+    }
+}
+
+/// Rewrite `num_frames` in the acTL chunk of the APNG at `path`, and the
+/// chunk's CRC with it.
+fn set_apng_frame_count(path: &Path, frames: u32) -> Result<(), RecordingError> {
+    let mut file = File::options().read(true).write(true).open(path)?;
+    // Chunks start after the 8-byte signature; acTL comes before any image
+    // data.
+    let mut pos = 8;
+    loop {
+        let mut header = [0u8; 8];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        match &header[4..] {
+            b"acTL" => break,
+            b"IDAT" | b"IEND" => {
+                return Err(RecordingError::Encode("APNG has no acTL chunk".to_string()));
+            }
+            _ => pos += 12 + len as u64,
+        }
+    }
+
+    let mut actl = *b"acTL\0\0\0\0\0\0\0\0";
+    file.read_exact(&mut actl[4..])?;
+    actl[4..8].copy_from_slice(&frames.to_be_bytes());
+    file.seek(SeekFrom::Start(pos + 4))?;
+    file.write_all(&actl)?;
+    file.write_all(&crc32fast::hash(&actl).to_be_bytes())?;
+    Ok(())
+}
+
+/// Raw RGBA frames piped into `ffmpeg`, which writes the file.
+///
+/// The raw stream has a constant frame rate, so a frame shown longer than
+/// 1/fps (a slow grab) is sent several times and one shown for less is
+/// dropped, keeping the video in step with the wall clock.
+struct Ffmpeg {
+    child: Child,
+    stdin: ChildStdin,
+    fps: u32,
+    /// Recorded time so far.
+    elapsed: Duration,
+    sent: u64,
+}
+
+impl Ffmpeg {
+    fn spawn(
+        path: &Path,
+        format: RecordingFormat,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<Self, RecordingError> {
+        use std::os::unix::process::CommandExt;
+
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-video_size", &format!("{width}x{height}")])
+            .args(["-framerate", &fps.to_string(), "-i", "-"])
+            // 4:2:0 chroma needs even dimensions.
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-pix_fmt", "yuv420p"]);
+        match format {
+            RecordingFormat::Mp4 => cmd
+                .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "23"])
+                .args(["-movflags", "+faststart"]),
+            _ => cmd
+                .args(["-c:v", "libvpx-vp9", "-crf", "32", "-b:v", "0"])
+                .args(["-deadline", "realtime", "-cpu-used", "8"]),
+        };
+        cmd.arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            // Its own process group, so Ctrl-C in the terminal reaches only
+            // us and we get to close the pipe and let it finish the file.
+            .process_group(0);
+
+        let mut child = cmd.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                RecordingError::Encode(format!("ffmpeg not found; install it to record {format}"))
+            }
+            _ => RecordingError::Encode(format!("failed to start ffmpeg: {e}")),
+        })?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        Ok(Self { child, stdin, fps, elapsed: Duration::ZERO, sent: 0 })
+    }
+
+    fn add_frame(&mut self, frame: &RgbaImage, delay: Duration) -> Result<(), RecordingError> {
+        self.elapsed += delay;
+        let due = (self.elapsed.as_secs_f64() * self.fps as f64).round() as u64;
+        while self.sent < due {
+            self.stdin.write_all(frame.as_raw()).map_err(|e| {
+                RecordingError::Encode(format!("ffmpeg stopped reading frames: {e}"))
+            })?;
+            self.sent += 1;
+        }
+        Ok(())
+    }
+
+    /// Close the pipe and wait for ffmpeg to write the trailer.
+    fn finish(self) -> Result<(), RecordingError> {
+        let Self { mut child, stdin, .. } = self;
+        drop(stdin);
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(RecordingError::Encode(format!("ffmpeg exited with {status}")));
+        }
+        Ok(())
+    }
+}
+
+/// Animated WebP: a VP8X header with the animation flag, an ANIM chunk and
+/// one ANMF chunk per frame wrapping a lossless still.
+struct WebpAnimation<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+    /// ANMF chunks so far; the RIFF size in front of them isn't known yet.
+    frames: Vec<u8>,
+}
+
+impl<W: Write> WebpAnimation<W> {
+    fn new(writer: W, width: u32, height: u32) -> Self {
+        Self { writer, width, height, frames: Vec::new() }
+    }
+
+    fn add_frame(&mut self, frame: &RgbaImage, duration_ms: u32) -> Result<(), RecordingError> {
+        let mut still = Vec::new();
+        image::codecs::webp::WebPEncoder::new_lossless(&mut still)
+            .encode(frame.as_raw(), frame.width(), frame.height(), image::ExtendedColorType::Rgba8)
+            .map_err(encode_error)?;
+        // Skip "RIFF", size and "WEBP"; the bitstream chunks follow.
+        let bitstream = still
+            .get(12..)
+            .ok_or_else(|| RecordingError::Encode("truncated WebP frame".to_string()))?;
+
+        let mut payload = Vec::with_capacity(16 + bitstream.len());
+        payload.extend_from_slice(&u24(0)); // x / 2
+        payload.extend_from_slice(&u24(0)); // y / 2
+        payload.extend_from_slice(&u24(frame.width() - 1));
+        payload.extend_from_slice(&u24(frame.height() - 1));
+        payload.extend_from_slice(&u24(duration_ms));
+        payload.push(0b10); // no blending, no disposal
+        payload.extend_from_slice(bitstream);
+        write_chunk(&mut self.frames, b"ANMF", &payload);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), RecordingError> {
+        let mut header = Vec::new();
+        let mut vp8x = vec![0b0001_0010, 0, 0, 0]; // alpha, animation
+        vp8x.extend_from_slice(&u24(self.width - 1));
+        vp8x.extend_from_slice(&u24(self.height - 1));
+        write_chunk(&mut header, b"VP8X", &vp8x);
+        // Background colour (BGRA) and loop count (0 = forever).
+        write_chunk(&mut header, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+
+        let size = 4 + header.len() + self.frames.len();
+        self.writer.write_all(b"RIFF")?;
+        self.writer.write_all(&(size as u32).to_le_bytes())?;
+        self.writer.write_all(b"WEBP")?;
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&self.frames)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn u24(v: u32) -> [u8; 3] {
+    let b = v.to_le_bytes();
+    [b[0], b[1], b[2]]
+}
+
+/// Append a RIFF chunk, padded to an even length.
+fn write_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apng_stopped_early_declares_only_its_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rec.png");
+        let writer = BufWriter::new(File::create(&path).unwrap());
+        let mut apng = Apng::new(&path, writer, 4, 3, 100).unwrap();
+        for shade in [0, 128, 255] {
+            let frame = RgbaImage::from_pixel(4, 3, image::Rgba([shade, 0, 0, 255]));
+            apng.add_frame(&frame, 66).unwrap();
+        }
+        apng.finish().unwrap();
+
+        // The decoder checks every chunk's CRC.
+        let mut reader = png::Decoder::new(std::io::BufReader::new(File::open(&path).unwrap()))
+            .read_info()
+            .unwrap();
+        let actl = reader.info().animation_control.unwrap();
+        assert_eq!(actl.num_frames, 3);
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let mut frames = 0;
+        while reader.next_frame(&mut buf).is_ok() {
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+    }
+}
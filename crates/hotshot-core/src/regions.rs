@@ -0,0 +1,263 @@
+use crate::capture::{self, Monitor, Region};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// How many interactive selections are remembered
+const RECENT_LIMIT: usize = 10;
+
+#[derive(Error, Debug)]
+pub enum RegionsError {
+    #[error("failed to read regions: {0}")]
+    Read(#[from] std::io::Error),
+    #[error("failed to parse regions: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+/// Add an interactive selection (root coordinates) to the recent list
+pub fn remember(region: Region) -> Result<(), RegionsError> {
+    let mut store = RegionStore::load()?;
+    store.push_recent(region);
+    store.save()
+}
+
+/// A remembered region. When `monitor` is set the region is stored relative
+/// to that monitor, so it follows the monitor if the layout changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedRegion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    pub region: Region,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl SavedRegion {
+    /// Remember `region` (root coordinates) relative to the monitor that
+    /// contains its centre, if we can tell.
+    pub fn new(region: Region) -> Self {
+        let cx = region.x + region.width as i32 / 2;
+        let cy = region.y + region.height as i32 / 2;
+        let monitor = capture::list_monitors()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|m| m.contains(cx, cy));
+        let region = match &monitor {
+            Some(m) => Region {
+                x: region.x - m.x as i32,
+                y: region.y - m.y as i32,
+                ..region
+            },
+            None => region,
+        };
+        Self {
+            monitor: monitor.map(|m| m.name),
+            region,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Store `region` as given: relative to `monitor` when one is named,
+    /// otherwise in root coordinates.
+    pub fn relative_to(region: Region, monitor: Option<String>) -> Self {
+        Self {
+            monitor,
+            region,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// The region in root coordinates on the current monitor layout.
+    pub fn resolve(&self) -> Result<Region, String> {
+        if self.monitor.is_none() {
+            return Ok(self.region);
+        }
+        let monitors = capture::list_monitors().map_err(|e| e.to_string())?;
+        self.resolve_on(&monitors)
+    }
+
+    fn resolve_on(&self, monitors: &[Monitor]) -> Result<Region, String> {
+        let Some(name) = &self.monitor else {
+            return Ok(self.region);
+        };
+        let monitor = monitors
+            .iter()
+            .find(|m| &m.name == name)
+            .ok_or_else(|| format!("monitor '{name}' is not connected"))?;
+        Ok(Region {
+            x: self.region.x + monitor.x as i32,
+            y: self.region.y + monitor.y as i32,
+            ..self.region
+        })
+    }
+}
+
+impl std::fmt::Display for SavedRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let r = self.region;
+        write!(f, "{}x{}+{}+{}", r.width, r.height, r.x, r.y)?;
+        if let Some(monitor) = &self.monitor {
+            write!(f, " on {monitor}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Recent interactive selections (newest first) and named regions
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RegionStore {
+    #[serde(default)]
+    pub recent: Vec<SavedRegion>,
+    #[serde(default)]
+    pub named: BTreeMap<String, SavedRegion>,
+}
+
+impl RegionStore {
+    pub fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from(".config"))
+            .join("hotshot")
+            .join("regions.json")
+    }
+
+    pub fn load() -> Result<Self, RegionsError> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self) -> Result<(), RegionsError> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json)?;
+        Ok(())
+    }
+
+    /// Record an interactive selection (root coordinates)
+    pub fn push_recent(&mut self, region: Region) {
+        self.recent.insert(0, SavedRegion::new(region));
+        self.recent.truncate(RECENT_LIMIT);
+    }
+
+    /// Recent selections in root coordinates, newest first, for the region
+    /// selector to snap to. Ones on monitors that aren't connected are left
+    /// out.
+    pub fn snap_regions(&self) -> Vec<Region> {
+        let monitors = capture::list_monitors().unwrap_or_default();
+        self.recent
+            .iter()
+            .filter_map(|saved| saved.resolve_on(&monitors).ok())
+            .collect()
+    }
+
+    /// The `n`th most recent selection; 1 is the last one
+    pub fn recent(&self, n: usize) -> Result<&SavedRegion, String> {
+        n.checked_sub(1)
+            .and_then(|i| self.recent.get(i))
+            .ok_or_else(|| match self.recent.len() {
+                0 => "no recent regions yet".to_string(),
+                len => format!("only {len} recent regions remembered"),
+            })
+    }
+
+    pub fn named(&self, name: &str) -> Result<&SavedRegion, String> {
+        self.named
+            .get(name)
+            .ok_or_else(|| format!("no region named '{name}'"))
+    }
+
+    pub fn set_named(&mut self, name: &str, region: SavedRegion) -> Result<(), String> {
+        // Names share parse_region's argument with geometries, so keep them apart.
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!(
+                "invalid region name: '{name}'. use letters, digits, '-' and '_', starting with a letter"
+            ));
+        }
+        self.named.insert(name.to_string(), region);
+        Ok(())
+    }
+
+    pub fn remove_named(&mut self, name: &str) -> Result<SavedRegion, String> {
+        self.named
+            .remove(name)
+            .ok_or_else(|| format!("no region named '{name}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(width: u32) -> Region {
+        Region { x: 10, y: 20, width, height: 30 }
+    }
+
+    fn monitor(name: &str, x: i16, y: i16) -> Monitor {
+        Monitor { name: name.to_string(), x, y, width: 1920, height: 1080, ..Default::default() }
+    }
+
+    #[test]
+    fn recent_list_is_newest_first_and_capped() {
+        let mut store = RegionStore::default();
+        for width in 1..=RECENT_LIMIT as u32 + 3 {
+            store.push_recent(region(width));
+        }
+        let widths: Vec<u32> = store.recent.iter().map(|s| s.region.width).collect();
+        let expected: Vec<u32> = (4..=RECENT_LIMIT as u32 + 3).rev().collect();
+        assert_eq!(widths, expected);
+
+        assert_eq!(store.recent(1).unwrap().region.width, RECENT_LIMIT as u32 + 3);
+        assert_eq!(store.recent(RECENT_LIMIT).unwrap().region.width, 4);
+        assert!(store.recent(0).is_err());
+        assert!(store.recent(RECENT_LIMIT + 1).unwrap_err().contains("only 10"));
+        assert_eq!(RegionStore::default().recent(1).unwrap_err(), "no recent regions yet");
+    }
+
+    #[test]
+    fn named_regions() {
+        let mut store = RegionStore::default();
+        let saved = SavedRegion::relative_to(region(100), Some("DP-1".to_string()));
+        store.set_named("top-bar_2", saved).unwrap();
+        assert_eq!(store.named("top-bar_2").unwrap().region.width, 100);
+        assert!(store.named("top-bar").is_err());
+
+        // Names can't be mistaken for geometries or contain odd characters.
+        for bad in ["100x200", "2x", "", "a b", "-a", "a/b"] {
+            let saved = SavedRegion::relative_to(region(1), None);
+            assert!(store.set_named(bad, saved).is_err(), "{bad:?} was accepted");
+        }
+
+        assert_eq!(store.remove_named("top-bar_2").unwrap().region.width, 100);
+        assert!(store.remove_named("top-bar_2").is_err());
+    }
+
+    #[test]
+    fn resolve_follows_the_monitor() {
+        let monitors = [monitor("DP-1", 0, 0), monitor("HDMI-1", 1920, 0)];
+        let saved = SavedRegion::relative_to(region(100), Some("HDMI-1".to_string()));
+        let r = saved.resolve_on(&monitors).unwrap();
+        assert_eq!((r.x, r.y, r.width, r.height), (1930, 20, 100, 30));
+
+        let root = SavedRegion::relative_to(region(100), None);
+        let r = root.resolve_on(&[]).unwrap();
+        assert_eq!((r.x, r.y), (10, 20));
+    }
+
+    #[test]
+    fn resolve_on_missing_monitor() {
+        let saved = SavedRegion::relative_to(region(100), Some("HDMI-1".to_string()));
+        let err = saved.resolve_on(&[monitor("DP-1", 0, 0)]).unwrap_err();
+        assert_eq!(err, "monitor 'HDMI-1' is not connected");
+    }
+}
@@ -1,10 +1,10 @@
-use crate::capture::{CaptureMode, DisplayServer};
+use crate::capture::{Capture, CaptureMode, DisplayServer, Monitor};
 use crate::config::{Config, ImageFormat};
 use crate::metadata::{Metadata, MetadataDb};
+use crate::recording::{Recording, RecordingError, RecordingFormat};
 use chrono::Utc;
-use image::RgbaImage;
 use rand::Rng;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,6 +13,8 @@ pub enum StorageError {
     Io(#[from] std::io::Error),
     #[error("image error: {0}")]
     Image(#[from] image::ImageError),
+    #[error("recording error: {0}")]
+    Recording(#[from] RecordingError),
     #[error("metadata error: {0}")]
     Metadata(#[from] crate::metadata::MetadataError),
     #[error("{0}")]
@@ -49,11 +51,108 @@ impl Storage {
     /// Save a captured screenshot to disk and record in metadata DB
     pub fn save(
         &self,
-        image: &RgbaImage,
+        capture: &Capture,
         mode: &CaptureMode,
         display_server: DisplayServer,
         format: Option<&ImageFormat>,
     ) -> Result<Metadata, StorageError> {
+        let metadata = self.write(capture, mode, display_server, format)?;
+
+        // Add to DB
+        let mut db = MetadataDb::load()?;
+        db.add(metadata.clone());
+        db.save()?;
+
+        Ok(metadata)
+    }
+
+    /// Save one screenshot per monitor, linked by a shared group id
+    pub fn save_group(
+        &self,
+        parts: &[(Monitor, Capture)],
+        mode: &CaptureMode,
+        display_server: DisplayServer,
+        format: Option<&ImageFormat>,
+    ) -> Result<Vec<Metadata>, StorageError> {
+        let group = Self::generate_id();
+        let mut entries: Vec<Metadata> = Vec::with_capacity(parts.len());
+        for (monitor, capture) in parts {
+            // Don't leave the parts already written behind without entries.
+            let mut metadata = match self.write(capture, mode, display_server, format) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    for written in &entries {
+                        let _ = std::fs::remove_file(&written.path);
+                    }
+                    return Err(e);
+                }
+            };
+            metadata.group = Some(group.clone());
+            metadata.monitor = Some(monitor.name.clone());
+            entries.push(metadata);
+        }
+
+        let mut db = MetadataDb::load()?;
+        for metadata in &entries {
+            db.add(metadata.clone());
+        }
+        db.save()?;
+
+        Ok(entries)
+    }
+
+    /// Have `record` write a new library file at the path it's given, then
+    /// add it to the metadata DB. The partial file is removed if recording
+    /// fails.
+    pub fn save_recording(
+        &self,
+        format: RecordingFormat,
+        display_server: DisplayServer,
+        record: impl FnOnce(&Path) -> Result<Recording, RecordingError>,
+    ) -> Result<Metadata, StorageError> {
+        let id = Self::generate_id();
+        let dir = self.target_dir();
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.{}", id, format.extension()));
+
+        let recording = match record(&path) {
+            Ok(recording) => recording,
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                return Err(e.into());
+            }
+        };
+
+        let mut metadata = Metadata::new(
+            &id,
+            path.clone(),
+            recording.width,
+            recording.height,
+            &format.to_string(),
+            "recording",
+            &display_server.to_string(),
+        );
+        metadata.file_size = std::fs::metadata(&path)?.len();
+        metadata.region = Some(recording.region);
+        metadata.duration_ms = Some(recording.duration.as_millis() as u64);
+        metadata.frame_count = Some(recording.frames);
+
+        let mut db = MetadataDb::load()?;
+        db.add(metadata.clone());
+        db.save()?;
+
+        Ok(metadata)
+    }
+
+    /// Write the image file and build its metadata (not yet in the DB)
+    fn write(
+        &self,
+        capture: &Capture,
+        mode: &CaptureMode,
+        display_server: DisplayServer,
+        format: Option<&ImageFormat>,
+    ) -> Result<Metadata, StorageError> {
+        let image = &capture.image;
         let id = Self::generate_id();
         let fmt = format.unwrap_or(&self.config.image.format);
         let dir = self.target_dir();
@@ -85,6 +184,9 @@ impl Storage {
             CaptureMode::Region(_) => "region",
             CaptureMode::RegionInteractive => "region-interactive",
             CaptureMode::ActiveWindow => "active-window",
+            CaptureMode::WindowInteractive => "window-interactive",
+            CaptureMode::Window(_) => "window",
+            CaptureMode::Scroll(_) => "scroll",
         };
 
         let mut metadata = Metadata::new(
@@ -97,11 +199,11 @@ impl Storage {
             &display_server.to_string(),
         );
         metadata.file_size = file_size;
-
-        // Add to DB
-        let mut db = MetadataDb::load()?;
-        db.add(metadata.clone());
-        db.save()?;
+        metadata.cursor = capture.cursor;
+        metadata.region = capture.region;
+        let (width, height) = capture.physical_size.unwrap_or(image.dimensions());
+        metadata.physical_width = Some(width);
+        metadata.physical_height = Some(height);
 
         Ok(metadata)
     }
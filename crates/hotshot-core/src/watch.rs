@@ -0,0 +1,117 @@
+//! Unattended capture sessions: a screenshot every interval, kept only when
+//! enough of the screen changed since the last one that was kept.
+
+use image::RgbaImage;
+
+/// Per-channel difference below which a pixel counts as unchanged, so
+/// dithering and compositor noise don't register as change.
+const PIXEL_TOLERANCE: u8 = 8;
+
+/// Fraction of pixels (0.0 to 1.0) that differ between `a` and `b`. Images
+/// of different sizes count as entirely changed.
+pub fn changed_fraction(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    if a.dimensions() != b.dimensions() {
+        return 1.0;
+    }
+    let total = a.width() as u64 * a.height() as u64;
+    if total == 0 {
+        return 0.0;
+    }
+    let changed = a
+        .pixels()
+        .zip(b.pixels())
+        .filter(|(p, q)| {
+            p.0.iter()
+                .zip(q.0.iter())
+                .any(|(x, y)| x.abs_diff(*y) > PIXEL_TOLERANCE)
+        })
+        .count() as u64;
+    changed as f64 / total as f64
+}
+
+/// Drops frames too similar to the last kept one.
+pub struct ChangeFilter {
+    /// Smallest changed fraction worth keeping; 0 keeps any change.
+    threshold: f64,
+    last: Option<RgbaImage>,
+}
+
+impl ChangeFilter {
+    pub fn new(threshold: f64) -> Self {
+        Self { threshold, last: None }
+    }
+
+    /// Whether to keep `image`, and how much of it changed. The first
+    /// frame is always kept; after that a frame must differ from the last
+    /// kept one by at least the threshold, and unchanged frames never pass.
+    pub fn check(&mut self, image: &RgbaImage) -> Option<f64> {
+        let changed = match &self.last {
+            Some(last) => changed_fraction(last, image),
+            None => 1.0,
+        };
+        if changed == 0.0 || changed < self.threshold {
+            return None;
+        }
+        self.last = Some(image.clone());
+        Some(changed)
+    }
+}
+
+/// Parse a change threshold: a percentage like "2%" or "0.5%", or a
+/// fraction like "0.02".
+pub fn parse_threshold(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let (num, scale) = match s.strip_suffix('%') {
+        Some(n) => (n, 0.01),
+        None => (s, 1.0),
+    };
+    let value = num
+        .trim()
+        .parse::<f64>()
+        .map(|v| v * scale)
+        .map_err(|_| format!("invalid threshold: '{s}'. use a percentage like 2% or 0.5%"))?;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(format!("invalid threshold: '{s}'. must be between 0% and 100%"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds_as_percentages_or_fractions() {
+        assert_eq!(parse_threshold("2%"), Ok(0.02));
+        assert_eq!(parse_threshold(" 50 % "), Ok(0.5));
+        assert_eq!(parse_threshold("0.02"), Ok(0.02));
+        assert_eq!(parse_threshold("0%"), Ok(0.0));
+        assert_eq!(parse_threshold("100%"), Ok(1.0));
+        assert_eq!(parse_threshold("1"), Ok(1.0));
+    }
+
+    #[test]
+    fn thresholds_out_of_range_or_malformed() {
+        for bad in ["101%", "1.5", "-1%", "-0.1", "%", "", "two%", "2%%"] {
+            assert!(parse_threshold(bad).is_err(), "{bad:?} was accepted");
+        }
+        assert!(parse_threshold("150%").unwrap_err().contains("between 0% and 100%"));
+    }
+
+    #[test]
+    fn filter_keeps_first_and_changed_frames() {
+        let black = RgbaImage::new(10, 10);
+        let mut one_pixel = black.clone();
+        one_pixel.put_pixel(0, 0, image::Rgba([255, 255, 255, 255]));
+        let mut noise = black.clone();
+        noise.put_pixel(5, 5, image::Rgba([PIXEL_TOLERANCE, 0, 0, 0]));
+
+        let mut filter = ChangeFilter::new(0.05);
+        assert_eq!(filter.check(&black), Some(1.0));
+        assert_eq!(filter.check(&black), None);
+        assert_eq!(filter.check(&noise), None);
+        // 1% changed, under the threshold.
+        assert_eq!(filter.check(&one_pixel), None);
+        assert_eq!(filter.check(&RgbaImage::new(5, 5)), Some(1.0));
+    }
+}
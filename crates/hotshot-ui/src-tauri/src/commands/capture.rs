@@ -1,15 +1,46 @@
 use crate::state::AppState;
 use hotshot_core::capture;
 use hotshot_core::clipboard;
+use hotshot_core::config::ImageScale;
 use hotshot_core::metadata::Metadata;
+use hotshot_core::regions::{self, RegionStore};
+use std::time::Duration;
 use tauri::Manager;
 
+/// Time for the main window to disappear before capturing.
+const HIDE_DELAY: Duration = Duration::from_millis(150);
+
+/// Capture options derived from the current config and the requested delay.
+fn capture_options(
+    state: &tauri::State<'_, AppState>,
+    delay: Option<f64>,
+) -> Result<capture::CaptureOptions, String> {
+    let config = state.config.lock().map_err(|e| e.to_string())?;
+    let delay = delay
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|e| format!("invalid delay: {e}"))?;
+    Ok(capture::CaptureOptions {
+        include_cursor: config.capture.include_cursor,
+        delay,
+        countdown: config.capture.countdown,
+        selector: config.selector.clone(),
+        ..Default::default()
+    })
+}
+
+/// The configured `[image] scale`.
+fn image_scale(state: &tauri::State<'_, AppState>) -> Result<ImageScale, String> {
+    Ok(state.config.lock().map_err(|e| e.to_string())?.image.scale)
+}
+
 #[tauri::command]
 pub async fn capture_fullscreen(
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     display: Option<String>,
     copy_to_clipboard: Option<bool>,
+    delay: Option<f64>,
 ) -> Result<Metadata, String> {
     // Hide window before capture
     if let Some(window) = app.get_webview_window("main") {
@@ -17,7 +48,7 @@ pub async fn capture_fullscreen(
     }
 
     // Brief delay for window to hide
-    tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+    tokio::time::sleep(HIDE_DELAY).await;
 
     let result = (|| -> Result<Metadata, String> {
         let display_bounds = if let Some(ref d) = display {
@@ -28,15 +59,18 @@ pub async fn capture_fullscreen(
         };
 
         let mode = capture::CaptureMode::Fullscreen;
-        let image = capture::capture(&mode, display_bounds).map_err(|e| e.to_string())?;
+        let opts = capture_options(&state, delay)?;
+        let mut shot = capture::capture(&mode, display_bounds, &opts).map_err(|e| e.to_string())?;
+
+        shot.apply_scale(image_scale(&state)?).map_err(|e| e.to_string())?;
 
         let should_copy = copy_to_clipboard.unwrap_or(true);
         if should_copy {
-            let _ = clipboard::copy_image(&image);
+            let _ = clipboard::copy_image(&shot.image);
         }
 
         let storage = state.storage.lock().map_err(|e| e.to_string())?;
-        let metadata = storage.save(&image, &mode, capture::detect_display_server().map_err(|e| e.to_string())?, None)
+        let metadata = storage.save(&shot, &mode, capture::detect_display_server().map_err(|e| e.to_string())?, None)
             .map_err(|e| e.to_string())?;
 
         Ok(metadata)
@@ -52,18 +86,23 @@ pub async fn capture_fullscreen(
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn capture_region(
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     display: Option<String>,
     copy_to_clipboard: Option<bool>,
+    delay: Option<f64>,
+    last: Option<bool>,
+    recent: Option<usize>,
+    named: Option<String>,
 ) -> Result<Metadata, String> {
     // Hide window before capture
     if let Some(window) = app.get_webview_window("main") {
         let _ = window.hide();
     }
 
-    tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+    tokio::time::sleep(HIDE_DELAY).await;
 
     let result = (|| -> Result<Metadata, String> {
         let display_bounds = if let Some(ref d) = display {
@@ -73,16 +112,43 @@ pub async fn capture_region(
             None
         };
 
-        let mode = capture::CaptureMode::RegionInteractive;
-        let image = capture::capture(&mode, display_bounds).map_err(|e| e.to_string())?;
+        // A saved or recent region skips the interactive selector.
+        let store = RegionStore::load().map_err(|e| e.to_string())?;
+        let recent = recent.or(last.unwrap_or(false).then_some(1));
+        let recalled = if named.is_some() || recent.is_some() {
+            let saved = match &named {
+                Some(name) => store.named(name)?,
+                None => store.recent(recent.unwrap_or(1))?,
+            };
+            Some(saved.resolve()?)
+        } else {
+            None
+        };
+
+        let mut opts = capture_options(&state, delay)?;
+        let mode = match recalled {
+            Some(region) => capture::CaptureMode::Region(region),
+            None => {
+                opts.snap_regions = store.snap_regions();
+                capture::CaptureMode::RegionInteractive
+            }
+        };
+        let mut shot = capture::capture(&mode, display_bounds, &opts).map_err(|e| e.to_string())?;
+        if let capture::CaptureMode::RegionInteractive = mode
+            && let Some(region) = shot.region
+        {
+            let _ = regions::remember(region);
+        }
+
+        shot.apply_scale(image_scale(&state)?).map_err(|e| e.to_string())?;
 
         let should_copy = copy_to_clipboard.unwrap_or(true);
         if should_copy {
-            let _ = clipboard::copy_image(&image);
+            let _ = clipboard::copy_image(&shot.image);
         }
 
         let storage = state.storage.lock().map_err(|e| e.to_string())?;
-        let metadata = storage.save(&image, &mode, capture::detect_display_server().map_err(|e| e.to_string())?, None)
+        let metadata = storage.save(&shot, &mode, capture::detect_display_server().map_err(|e| e.to_string())?, None)
             .map_err(|e| e.to_string())?;
 
         Ok(metadata)
@@ -97,30 +163,81 @@ pub async fn capture_region(
     result
 }
 
+/// Capture every monitor at once, saving one grouped screenshot per monitor.
+#[tauri::command]
+pub async fn capture_all_displays(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    copy_to_clipboard: Option<bool>,
+    delay: Option<f64>,
+) -> Result<Vec<Metadata>, String> {
+    // Hide window before capture
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+
+    tokio::time::sleep(HIDE_DELAY).await;
+
+    let result = (|| -> Result<Vec<Metadata>, String> {
+        let mode = capture::CaptureMode::Fullscreen;
+        let opts = capture_options(&state, delay)?;
+        let shot = capture::capture(&mode, None, &opts).map_err(|e| e.to_string())?;
+        let monitors = capture::list_monitors().map_err(|e| e.to_string())?;
+        let mut parts = capture::split_by_monitor(&shot, &monitors).map_err(|e| e.to_string())?;
+        let scale = image_scale(&state)?;
+        for (_, part) in &mut parts {
+            part.apply_scale(scale).map_err(|e| e.to_string())?;
+        }
+
+        let should_copy = copy_to_clipboard.unwrap_or(true);
+        if should_copy {
+            let _ = clipboard::copy_image(&shot.image);
+        }
+
+        let storage = state.storage.lock().map_err(|e| e.to_string())?;
+        let entries = storage.save_group(&parts, &mode, capture::detect_display_server().map_err(|e| e.to_string())?, None)
+            .map_err(|e| e.to_string())?;
+
+        Ok(entries)
+    })();
+
+    // Show window after capture
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+
+    result
+}
+
 #[tauri::command]
 pub async fn capture_window(
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     copy_to_clipboard: Option<bool>,
+    delay: Option<f64>,
 ) -> Result<Metadata, String> {
     // Hide window before capture
     if let Some(window) = app.get_webview_window("main") {
         let _ = window.hide();
     }
 
-    tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+    tokio::time::sleep(HIDE_DELAY).await;
 
     let result = (|| -> Result<Metadata, String> {
         let mode = capture::CaptureMode::ActiveWindow;
-        let image = capture::capture(&mode, None).map_err(|e| e.to_string())?;
+        let opts = capture_options(&state, delay)?;
+        let mut shot = capture::capture(&mode, None, &opts).map_err(|e| e.to_string())?;
+
+        shot.apply_scale(image_scale(&state)?).map_err(|e| e.to_string())?;
 
         let should_copy = copy_to_clipboard.unwrap_or(true);
         if should_copy {
-            let _ = clipboard::copy_image(&image);
+            let _ = clipboard::copy_image(&shot.image);
         }
 
         let storage = state.storage.lock().map_err(|e| e.to_string())?;
-        let metadata = storage.save(&image, &mode, capture::detect_display_server().map_err(|e| e.to_string())?, None)
+        let metadata = storage.save(&shot, &mode, capture::detect_display_server().map_err(|e| e.to_string())?, None)
             .map_err(|e| e.to_string())?;
 
         Ok(metadata)
@@ -134,3 +251,8 @@ pub async fn capture_window(
 
     result
 }
+
+#[tauri::command]
+pub fn list_regions() -> Result<RegionStore, String> {
+    RegionStore::load().map_err(|e| e.to_string())
+}
@@ -13,7 +13,9 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             commands::capture::capture_fullscreen,
             commands::capture::capture_region,
+            commands::capture::capture_all_displays,
             commands::capture::capture_window,
+            commands::capture::list_regions,
             commands::screenshots::list_screenshots,
             commands::screenshots::get_screenshot,
             commands::screenshots::search_screenshots,
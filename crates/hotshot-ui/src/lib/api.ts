@@ -1,31 +1,61 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { Metadata, Monitor, Config } from "./types";
+import type {
+  Metadata,
+  Monitor,
+  Config,
+  RegionRecall,
+  RegionStore,
+} from "./types";
 
 export async function captureFullscreen(
   display?: string,
   copyToClipboard?: boolean,
+  delay?: number,
 ): Promise<Metadata> {
   return invoke("capture_fullscreen", {
     display,
     copyToClipboard: copyToClipboard ?? true,
+    delay,
   });
 }
 
 export async function captureRegion(
   display?: string,
   copyToClipboard?: boolean,
+  delay?: number,
+  recall?: RegionRecall,
 ): Promise<Metadata> {
   return invoke("capture_region", {
     display,
     copyToClipboard: copyToClipboard ?? true,
+    delay,
+    last: recall?.last,
+    recent: recall?.recent,
+    named: recall?.named,
+  });
+}
+
+export async function listRegions(): Promise<RegionStore> {
+  return invoke("list_regions");
+}
+
+export async function captureAllDisplays(
+  copyToClipboard?: boolean,
+  delay?: number,
+): Promise<Metadata[]> {
+  return invoke("capture_all_displays", {
+    copyToClipboard: copyToClipboard ?? true,
+    delay,
   });
 }
 
 export async function captureWindow(
   copyToClipboard?: boolean,
+  delay?: number,
 ): Promise<Metadata> {
   return invoke("capture_window", {
     copyToClipboard: copyToClipboard ?? true,
+    delay,
   });
 }
 
@@ -10,6 +10,43 @@ export interface Metadata {
   file_size: number;
   tags: string[];
   notes: string;
+  cursor?: CursorPosition;
+  region?: Region;
+  group?: string;
+  monitor?: string;
+  physical_width?: number;
+  physical_height?: number;
+  duration_ms?: number;
+  frame_count?: number;
+}
+
+export interface Region {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface CursorPosition {
+  x: number;
+  y: number;
+}
+
+export interface SavedRegion {
+  monitor?: string;
+  region: Region;
+  timestamp: string;
+}
+
+export interface RegionStore {
+  recent: SavedRegion[];
+  named: Record<string, SavedRegion>;
+}
+
+export interface RegionRecall {
+  last?: boolean;
+  recent?: number;
+  named?: string;
 }
 
 export interface Monitor {
@@ -18,12 +55,24 @@ export interface Monitor {
   y: number;
   width: number;
   height: number;
+  primary: boolean;
+  rotation: number;
+  refresh: number | null;
+  width_mm: number | null;
+  height_mm: number | null;
+  dpi: number | null;
+  manufacturer: string | null;
+  model: string | null;
+  description: string | null;
+  scale: number | null;
 }
 
 export interface Config {
   storage_dir: string;
   image: ImageConfig;
   storage: StorageConfig;
+  capture: CaptureConfig;
+  selector: SelectorConfig;
   behavior: BehaviorConfig;
 }
 
@@ -31,12 +80,32 @@ export interface ImageConfig {
   format: string;
   quality: number;
   filename_template: string;
+  scale: string;
 }
 
 export interface StorageConfig {
   organize_by: string;
 }
 
+export interface CaptureConfig {
+  include_cursor: boolean;
+  countdown: boolean;
+}
+
+export interface SelectorConfig {
+  confirm: string[];
+  cancel: string[];
+  restart: string[];
+  move: string[];
+  left: string[];
+  right: string[];
+  up: string[];
+  down: string[];
+  magnifier: boolean;
+  snap: boolean;
+  snap_threshold: number;
+}
+
 export interface BehaviorConfig {
   copy_to_clipboard: boolean;
   notification: boolean;